@@ -11,9 +11,18 @@ pub enum Action {
     /// Advance the machine by one step
     #[key("space")]
     Step,
+    /// Undo the machine's last step
+    #[key("b", "backspace")]
+    StepBack,
+    /// Toggle a breakpoint on the current state
+    #[key("t")]
+    ToggleBreakpoint,
     /// Toggle auto-play
     #[key("p")]
     ToggleAutoPlay,
+    /// Toggle loop detection
+    #[key("l")]
+    ToggleLoopDetection,
     /// Toggle help display
     #[key("h")]
     ToggleHelp,
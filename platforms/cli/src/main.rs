@@ -1,15 +1,49 @@
-use clap::Parser;
+use clap::{Args, Parser, Subcommand};
+use std::fs::{self, File};
+use std::io::BufWriter;
 use std::io::{self, BufRead};
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use tur::busy_beaver::{Enumerator, MAX_BUSY_BEAVER_STEPS};
+use tur::coverage::Outcome;
 use tur::loader::ProgramLoader;
 use tur::machine::TuringMachine;
-use tur::Step;
+use tur::types::{Mode, INPUT_BLANK_SYMBOL, MAX_EXECUTION_STEPS, MAX_EXPLORATION_WIDTH};
+use tur::{
+    coverage, diagnose, format_source, head_analysis, to_source, AnalysisOptions, Breakpoint,
+    DirectionStyle, Exploration, FormatOptions, Halt, NondeterministicMachine, Pause, Program,
+    Severity, Step, Tape, Trace, TuringMachineError,
+};
+
+/// Exit code when the machine accepts, or halts without declaring any accept/reject states.
+const EXIT_ACCEPT: i32 = 0;
+/// Exit code when the machine halts in a reject state.
+const EXIT_REJECT: i32 = 1;
+/// Exit code when the program cannot be loaded or the machine halts with an error.
+const EXIT_ERROR: i32 = 2;
+/// Exit code when a program with accept/reject states halts outside of them.
+const EXIT_HALT: i32 = 3;
+/// Exit code when the machine reaches the step limit without halting.
+const EXIT_STEP_LIMIT: i32 = 4;
+/// Exit code when loop detection finds a repeated configuration.
+const EXIT_LOOP: i32 = 5;
 
 #[derive(Parser)]
-#[clap(author, version, about, long_about = None, arg_required_else_help = true)]
+#[clap(
+    author,
+    version,
+    about,
+    long_about = None,
+    arg_required_else_help = true,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
+
     /// The Turing machine program file to execute
-    program: String,
+    #[clap(required = true)]
+    program: Option<String>,
 
     /// The input to the Turing machine
     #[clap(short, long)]
@@ -18,48 +52,581 @@ struct Cli {
     /// Print each step of the execution
     #[clap(short = 'd', long)]
     debug: bool,
+
+    /// The maximum number of steps to execute before giving up
+    /// (per branch for nondeterministic programs)
+    #[clap(long, default_value_t = MAX_EXECUTION_STEPS)]
+    max_steps: usize,
+
+    /// Pause and print the machine when a breakpoint is hit, instead of at every step with
+    /// --debug. Repeatable. A breakpoint is a state name, or one of `state=NAME`,
+    /// `read=SYMBOLS` (comma-separated, one per tape), `write=SYMBOL`, `head=[TAPE:]POSITION`
+    /// or `steps=N`. Not supported for nondeterministic programs
+    #[clap(short = 'b', long = "break", value_name = "BREAKPOINT")]
+    breakpoints: Vec<Breakpoint>,
+
+    /// Write an execution trace to FILE, as CSV if it ends in `.csv` or JSON Lines otherwise
+    #[clap(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+
+    /// Include a snapshot of the tapes in every step of the trace
+    #[clap(long, requires = "trace")]
+    trace_tapes: bool,
+
+    /// Stop with exit code 5 as soon as the machine repeats a configuration, since it will then
+    /// never halt
+    #[clap(long)]
+    detect_loops: bool,
+
+    /// The maximum number of branches to explore at once for nondeterministic programs
+    #[clap(long, default_value_t = MAX_EXPLORATION_WIDTH)]
+    max_branches: usize,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Enumerate every busy beaver candidate with the given number of states and symbols, and
+    /// print the champions
+    BusyBeaver(BusyBeaverArgs),
+    /// Check a program for errors, and list the symbols each state has no transition for with
+    /// --coverage, the transitions that overlap with --deterministic or the symbols each state
+    /// can read with --heads
+    Check(CheckArgs),
+    /// Print a program with every macro expanded into plain states
+    Expand {
+        /// The Turing machine program file to expand
+        program: String,
+    },
+    /// Format program files in place
+    Fmt(FmtArgs),
+}
+
+#[derive(Args)]
+struct CheckArgs {
+    /// The Turing machine program file to check
+    program: String,
+
+    /// Print a table of the symbols each reachable state has no transition for
+    #[clap(long)]
+    coverage: bool,
+
+    /// Report transitions that are shadowed by or overlap an earlier transition of the same
+    /// state as errors, so that every state has at most one transition for any symbols it reads
+    #[clap(long)]
+    deterministic: bool,
+
+    /// Print a table of the symbols each reachable state can read, and whether its heads can
+    /// run off the left end of the input
+    #[clap(long)]
+    heads: bool,
+}
+
+#[derive(Args)]
+struct FmtArgs {
+    /// The Turing machine program files to format
+    #[clap(required = true)]
+    files: Vec<PathBuf>,
+
+    /// List the files that aren't formatted, with exit code 1, instead of formatting them
+    #[clap(long)]
+    check: bool,
+
+    /// Sort the states by name, after the initial state
+    #[clap(long)]
+    sort_states: bool,
+
+    /// Write directions as `<`, `>` and `-` instead of `L`, `R` and `S`
+    #[clap(long)]
+    arrows: bool,
+}
+
+#[derive(Args)]
+struct BusyBeaverArgs {
+    /// The number of states
+    states: usize,
+
+    /// The number of symbols, including blank
+    #[clap(short, long, default_value_t = 2)]
+    symbols: usize,
+
+    /// The maximum number of steps to run each machine for
+    #[clap(long, default_value_t = MAX_BUSY_BEAVER_STEPS)]
+    max_steps: usize,
+
+    /// The number of worker threads (defaults to the number of CPUs)
+    #[clap(short = 'j', long)]
+    workers: Option<usize>,
 }
 
 fn main() {
     let cli = Cli::parse();
 
-    let program = match ProgramLoader::load_program(Path::new(&cli.program)) {
+    match &cli.command {
+        Some(Command::BusyBeaver(args)) => {
+            run_busy_beaver(args);
+            return;
+        }
+        Some(Command::Check(args)) => {
+            run_check(args);
+            return;
+        }
+        Some(Command::Expand { program }) => {
+            print!(
+                "{}",
+                to_source(&load_program(program), &FormatOptions::default())
+            );
+            return;
+        }
+        Some(Command::Fmt(args)) => std::process::exit(run_fmt(args)),
+        None => {}
+    }
+
+    let path = cli
+        .program
+        .as_deref()
+        .expect("program is required without a subcommand");
+    let program = load_program(path);
+    let is_decider = program.is_decider();
+
+    // Branches are explored breadth-first, so there's no single execution to pause
+    if program.mode == Mode::Nondeterministic && !cli.breakpoints.is_empty() {
+        eprintln!("Breakpoints aren't supported for nondeterministic programs");
+        std::process::exit(EXIT_ERROR);
+    }
+
+    // Get tape inputs from either CLI args or stdin
+    let tapes = match read_tape_inputs(&cli.input) {
+        Ok(inputs) => inputs,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(EXIT_ERROR);
+        }
+    };
+
+    let (result, machine) = if program.mode == Mode::Nondeterministic {
+        run_nondeterministic(program, &tapes, &cli)
+    } else {
+        run_deterministic(program, &tapes, &cli)
+    };
+
+    println!("{}", format_tapes(machine.tapes()).join("\n"));
+
+    std::process::exit(exit_code(&result, is_decider));
+}
+
+/// Loads a program from a file, exiting on failure.
+fn load_program(path: &str) -> Program {
+    match ProgramLoader::load_program(Path::new(path)) {
         Ok(p) => p,
         Err(e) => {
             eprintln!("Error loading program: {}", e);
-            std::process::exit(1);
+            std::process::exit(EXIT_ERROR);
+        }
+    }
+}
+
+/// Checks a program, reporting every error with its location, warning about states that loop
+/// forever on blank and reporting overlapping transitions with `--deterministic`. Prints the
+/// transition coverage of a program without errors with `--coverage` and what its heads can
+/// read with `--heads`.
+fn run_check(args: &CheckArgs) {
+    let program = match ProgramLoader::load_program_unchecked(Path::new(&args.program)) {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("Error loading program: {}", e);
+            std::process::exit(EXIT_ERROR);
         }
     };
-    let mut machine = TuringMachine::new(program);
+    let options = AnalysisOptions {
+        shadowed_transitions: if args.deterministic {
+            Severity::Error
+        } else {
+            Severity::Warning
+        },
+        overlapping_transitions: args.deterministic.then_some(Severity::Error),
+        blank_loops: Some(Severity::Warning),
+        ..AnalysisOptions::default()
+    };
 
-    // Get tape inputs from either CLI args or stdin
-    let tapes = match read_tape_inputs(&cli.input) {
-        Ok(inputs) => inputs,
+    let diagnostics = diagnose(&program, &options);
+    if diagnostics.is_empty() {
+        println!("{}: ok", args.program);
+    }
+    for diagnostic in &diagnostics {
+        println!("{}: {}", args.program, diagnostic);
+    }
+
+    if diagnostics.has_errors() {
+        std::process::exit(EXIT_ERROR);
+    }
+    if args.coverage {
+        print_coverage(&program);
+    }
+    if args.heads {
+        print_heads(&program);
+    }
+}
+
+/// Prints a table of the symbols each reachable state of a program can read from each tape, and
+/// whether its heads can be left of the initial tape content.
+fn print_heads(program: &Program) {
+    let analysis = head_analysis(program);
+    let symbols = |symbols: &Vec<char>| {
+        let symbols: Vec<String> = symbols
+            .iter()
+            .map(|&symbol| {
+                if symbol == program.blank {
+                    INPUT_BLANK_SYMBOL
+                } else {
+                    symbol
+                }
+                .to_string()
+            })
+            .collect();
+        format!("{{{}}}", symbols.join(", "))
+    };
+
+    let rows: Vec<(&str, String, String)> = analysis
+        .states
+        .iter()
+        .map(|state| {
+            let reads: Vec<String> = state
+                .heads
+                .iter()
+                .map(|head| symbols(&head.symbols))
+                .collect();
+            let left: Vec<&str> = state
+                .heads
+                .iter()
+                .map(|head| if head.left_of_input { "yes" } else { "no" })
+                .collect();
+            (state.state.as_str(), reads.join(" "), left.join(" "))
+        })
+        .collect();
+
+    let width = rows
+        .iter()
+        .map(|(state, _, _)| state.chars().count())
+        .chain(["State".len()])
+        .max()
+        .unwrap_or_default();
+    let reads_width = rows
+        .iter()
+        .map(|(_, reads, _)| reads.chars().count())
+        .chain(["Reads".len()])
+        .max()
+        .unwrap_or_default();
+
+    println!(
+        "{:width$}  {:reads_width$}  Left of input",
+        "State", "Reads"
+    );
+    for (state, reads, left) in &rows {
+        println!("{:width$}  {:reads_width$}  {}", state, reads, left);
+    }
+
+    if analysis.runs_off_left() {
+        println!("\nA head can run off the left end of the input");
+    } else {
+        println!("\nNo head runs off the left end of the input");
+    }
+}
+
+/// Prints a table of the symbols each reachable state of a program has no transition for.
+fn print_coverage(program: &Program) {
+    let coverage = coverage(program);
+    let outcome = match coverage.outcome {
+        Outcome::Halt => "halts",
+        Outcome::Error => "errors",
+    };
+    let symbol = |symbol: char| {
+        if symbol == program.blank {
+            INPUT_BLANK_SYMBOL
+        } else {
+            symbol
+        }
+    };
+    let symbols = |symbols: &Vec<char>| match symbols.as_slice() {
+        [read] => symbol(*read).to_string(),
+        reads => {
+            let reads: Vec<String> = reads.iter().map(|&read| symbol(read).to_string()).collect();
+            format!("[{}]", reads.join(", "))
+        }
+    };
+
+    let width = coverage
+        .states
+        .iter()
+        .map(|state| state.state.len())
+        .chain(["State".len()])
+        .max()
+        .unwrap_or_default();
+
+    println!("{:width$}  {:8}  Missing ({})", "State", "Covered", outcome);
+    for state in &coverage.states {
+        let missing: Vec<String> = state.missing.iter().map(symbols).collect();
+        let covered = format!("{}/{}", state.covered, state.total());
+        let row = format!(
+            "{:width$}  {:8}  {}",
+            state.state,
+            covered,
+            missing.join(" ")
+        );
+        println!("{}", row.trim_end());
+    }
+
+    let missing: usize = coverage
+        .states
+        .iter()
+        .map(|state| state.missing.len())
+        .sum();
+    if missing == 0 {
+        println!("\nEvery reachable state has a transition for every symbol");
+    } else {
+        let mode = match coverage.outcome {
+            Outcome::Halt => "the machine halts on them",
+            Outcome::Error => "they are errors in strict mode",
+        };
+        println!("\n{missing} missing transition(s): {mode}");
+    }
+}
+
+/// Formats program files, or checks that they're formatted with `--check`. Returns the exit
+/// code.
+fn run_fmt(args: &FmtArgs) -> i32 {
+    let options = FormatOptions {
+        sort_states: args.sort_states,
+        directions: if args.arrows {
+            DirectionStyle::Arrows
+        } else {
+            DirectionStyle::Letters
+        },
+    };
+    let mut code = EXIT_ACCEPT;
+
+    for path in &args.files {
+        let result = fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|source| {
+                let formatted = format_source(&source, &options).map_err(|e| e.to_string())?;
+                Ok((source, formatted))
+            });
+
+        match result {
+            Ok((source, formatted)) if source == formatted => {}
+            Ok(_) if args.check => {
+                println!("{}", path.display());
+                code = code.max(EXIT_REJECT);
+            }
+            Ok((_, formatted)) => {
+                if let Err(e) = fs::write(path, formatted) {
+                    eprintln!("Error writing {}: {}", path.display(), e);
+                    code = EXIT_ERROR;
+                }
+            }
+            Err(e) => {
+                eprintln!("Error formatting {}: {}", path.display(), e);
+                code = EXIT_ERROR;
+            }
+        }
+    }
+
+    code
+}
+
+/// Enumerates busy beaver candidates and prints the counts and champions.
+fn run_busy_beaver(args: &BusyBeaverArgs) {
+    let mut enumerator = match Enumerator::new(args.states, args.symbols) {
+        Ok(enumerator) => enumerator.with_max_steps(args.max_steps),
         Err(e) => {
             eprintln!("{}", e);
-            std::process::exit(1);
+            std::process::exit(EXIT_ERROR);
         }
     };
+    if let Some(workers) = args.workers {
+        enumerator = enumerator.with_workers(workers);
+    }
+
+    let summary = enumerator.run();
+
+    println!("Machines:      {}", summary.machines);
+    println!("Halting:       {}", summary.halting);
+    println!("Never halting: {}", summary.never_halting);
+    println!("Unknown:       {}", summary.unknown);
+
+    if let Some(champion) = &summary.max_steps {
+        println!(
+            "Max steps:     {} ({}, {} ones)",
+            champion.steps, champion.notation, champion.ones
+        );
+    }
+    if let Some(champion) = &summary.max_ones {
+        println!(
+            "Max ones:      {} ({}, {} steps)",
+            champion.ones, champion.notation, champion.steps
+        );
+    }
+}
+
+/// Runs a deterministic program, stepping a single machine until it halts.
+fn run_deterministic(program: Program, tapes: &[String], cli: &Cli) -> (Step, TuringMachine) {
+    let mut machine = TuringMachine::new(program)
+        .with_max_steps(cli.max_steps)
+        .with_history(false)
+        .with_loop_detection(cli.detect_loops);
+    set_tapes_content(tapes, |tapes| machine.set_tapes_content(tapes));
+
+    for breakpoint in &cli.breakpoints {
+        machine.add_breakpoint(breakpoint.clone());
+    }
+
+    let mut trace = cli.trace.as_ref().map(|_| Trace::new(cli.trace_tapes));
+
+    let result = if cli.debug || !cli.breakpoints.is_empty() {
+        run_with_debug(&mut machine, trace.as_mut())
+    } else if let Some(trace) = trace.as_mut() {
+        trace.run(&mut machine)
+    } else {
+        machine.run()
+    };
+
+    if let (Some(path), Some(trace)) = (&cli.trace, &trace) {
+        write_trace(path, trace);
+    }
 
-    // Set tape contents if any inputs were provided
+    if result == Step::Halt(Halt::StepLimit) {
+        eprintln!(
+            "Step limit of {} reached without halting (use --max-steps to raise it)",
+            machine.max_steps()
+        );
+    }
+
+    if let Step::Halt(Halt::Loop {
+        first_seen_step,
+        period,
+    }) = result
+    {
+        eprintln!(
+            "Loop detected: the configuration at step {} repeats step {first_seen_step} (period {period})",
+            machine.step_count()
+        );
+    }
+
+    (result, machine)
+}
+
+/// Runs a nondeterministic program, exploring all branches of its computation.
+///
+/// `--max-steps` bounds the depth of each branch and `--max-branches` the number of branches
+/// explored at once. The returned machine holds the final configuration of the reported branch.
+fn run_nondeterministic(program: Program, tapes: &[String], cli: &Cli) -> (Step, TuringMachine) {
+    let mut machine = NondeterministicMachine::new(program)
+        .with_max_depth(cli.max_steps)
+        .with_max_width(cli.max_branches);
+    set_tapes_content(tapes, |tapes| machine.set_tapes_content(tapes));
+
+    let exploration = machine.run();
+
+    if cli.debug {
+        print_trace(&exploration);
+    }
+
+    if let Some(path) = &cli.trace {
+        write_trace(path, &machine.trace(&exploration, cli.trace_tapes));
+    }
+
+    if exploration.halt == Halt::StepLimit {
+        eprintln!(
+            "Exploration limit of {} steps or {} branches reached without an accepting branch \
+             (use --max-steps or --max-branches to raise it)",
+            machine.max_depth(),
+            machine.max_width()
+        );
+    }
+
+    (Step::Halt(exploration.halt), exploration.machine)
+}
+
+/// Writes an execution trace to a file, as CSV if the file name ends in `.csv` or JSON Lines
+/// otherwise. Exits on failure.
+fn write_trace(path: &Path, trace: &Trace) {
+    let result = File::create(path).and_then(|file| {
+        let writer = BufWriter::new(file);
+        if path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("csv"))
+        {
+            trace.write_csv(writer)
+        } else {
+            trace.write_jsonl(writer)
+        }
+    });
+
+    if let Err(e) = result {
+        eprintln!("Error writing trace to {}: {}", path.display(), e);
+        std::process::exit(EXIT_ERROR);
+    }
+}
+
+/// Sets the tape contents if any inputs were provided, exiting on failure.
+fn set_tapes_content(
+    tapes: &[String],
+    set: impl FnOnce(&[String]) -> Result<(), TuringMachineError>,
+) {
     if !tapes.is_empty() {
-        if let Err(e) = machine.set_tapes_content(&tapes) {
+        if let Err(e) = set(tapes) {
             eprintln!("Error setting tape content: {}", e);
-            std::process::exit(1);
+            std::process::exit(EXIT_ERROR);
         }
     }
+}
 
-    if cli.debug {
-        run_with_debug(&mut machine);
-    } else {
-        machine.run();
+/// Maps the outcome of a run to the process exit code.
+fn exit_code(result: &Step, is_decider: bool) -> i32 {
+    match result {
+        Step::Halt(Halt::Accept) => EXIT_ACCEPT,
+        Step::Halt(Halt::Reject(_)) => EXIT_REJECT,
+        Step::Halt(Halt::Err(e)) => {
+            eprintln!("Error: {}", e);
+            EXIT_ERROR
+        }
+        Step::Halt(Halt::StepLimit) => EXIT_STEP_LIMIT,
+        Step::Halt(Halt::Loop { .. }) => EXIT_LOOP,
+        Step::Halt(Halt::Ok) | Step::Continue if is_decider => EXIT_HALT,
+        Step::Halt(Halt::Ok) | Step::Continue => EXIT_ACCEPT,
     }
+}
 
-    println!("{}", format_tapes(machine.tapes()).join("\n"));
+/// Prints the transitions taken by the reported branch of a nondeterministic run.
+fn print_trace(exploration: &Exploration) {
+    println!("Explored {} configurations.", exploration.branches);
+
+    for (i, transition) in exploration.trace.iter().enumerate() {
+        println!(
+            "Step: {}, Read: {:?}, Write: {:?}, Move: {:?}, Next: {}",
+            i + 1,
+            transition.read,
+            transition.write,
+            transition.directions,
+            transition.next_state
+        );
+    }
+
+    match &exploration.halt {
+        Halt::Accept => println!("\nBranch accepted."),
+        Halt::Reject(rejection) => println!(
+            "\nAll branches rejected or halted; first rejection in state {} reading {:?}.",
+            rejection.state, rejection.symbols
+        ),
+        Halt::StepLimit => println!("\nExploration limit reached."),
+        _ => println!("\nMachine halted."),
+    }
+    println!("\nFinal tapes:");
 }
 
-/// Runs the Turing machine with debug output, printing each step.
-fn run_with_debug(machine: &mut TuringMachine) {
+/// Runs the Turing machine with debug output, printing each step, or only the steps that hit a
+/// breakpoint if any are set.
+fn run_with_debug(machine: &mut TuringMachine, mut trace: Option<&mut Trace>) -> Step {
     let print_state = |machine: &TuringMachine| {
         println!(
             "Step: {}, State: {}, Tapes: [{}], Heads: {:?}",
@@ -70,21 +637,42 @@ fn run_with_debug(machine: &mut TuringMachine) {
         );
     };
 
+    let every_step = machine.breakpoints().is_empty();
+
     print_state(machine);
 
     loop {
-        match machine.step() {
-            Step::Continue => {
+        let pause = match trace.as_deref_mut() {
+            Some(trace) => trace.record(machine, TuringMachine::step_with_breakpoints),
+            None => machine.step_with_breakpoints(),
+        };
+
+        match pause {
+            None => {
+                if every_step {
+                    print_state(machine);
+                }
+            }
+            Some(Pause::Breakpoint(breakpoint)) => {
+                println!("Breakpoint {breakpoint} hit:");
                 print_state(machine);
             }
-            Step::Halt(_) => {
-                println!("\nMachine halted.");
-                break;
+            Some(Pause::Halt(halt)) => {
+                match &halt {
+                    Halt::Accept => println!("\nMachine accepted."),
+                    Halt::Reject(rejection) => println!(
+                        "\nMachine rejected in state {} reading {:?}.",
+                        rejection.state, rejection.symbols
+                    ),
+                    Halt::StepLimit => println!("\nStep limit reached."),
+                    Halt::Loop { .. } => println!("\nLoop detected."),
+                    _ => println!("\nMachine halted."),
+                }
+                println!("\nFinal tapes:");
+                return Step::Halt(halt);
             }
         }
     }
-
-    println!("\nFinal tapes:");
 }
 
 /// Gets tape input from either command line arguments or stdin.
@@ -113,6 +701,6 @@ fn read_tape_inputs(inputs: &[String]) -> Result<Vec<String>, String> {
 }
 
 /// Returns the content of all tapes as a vector of `String`s.
-pub fn format_tapes(tapes: &[Vec<char>]) -> Vec<String> {
-    tapes.iter().map(|tape| tape.iter().collect()).collect()
+pub fn format_tapes(tapes: &[Tape]) -> Vec<String> {
+    tapes.iter().map(Tape::to_string).collect()
 }
@@ -0,0 +1,832 @@
+//! An open `.tur` document, and the language features computed from it: diagnostics, state
+//! definitions and references, hover and completion.
+//!
+//! State names are found in the syntax tree, so they keep their position in the source, while
+//! diagnostics, hover and completion come from the parsed program.
+
+use pest::error::InputLocation;
+use serde_json::{json, Value};
+use std::collections::BTreeSet;
+use std::ops::Range;
+use std::path::PathBuf;
+use tur::types::{INPUT_BLANK_SYMBOL, WILDCARD_SYMBOL};
+use tur::{
+    diagnose, parse_syntax, parse_unchecked_with_imports, AnalysisOptions, Diagnostic,
+    DiagnosticCode, Direction, Location, Program, ProgramLoader, Rule, Severity, SyntaxNode,
+    SyntaxTree, Transition, TuringMachineError,
+};
+
+/// The analysis options of documents: unreachable states are warnings, since they're common in
+/// programs that are being written, as are shadowed transitions and states that loop forever on
+/// blank, and overlapping transitions are hints.
+const OPTIONS: AnalysisOptions = AnalysisOptions {
+    unreachable_states: Severity::Warning,
+    shadowed_transitions: Severity::Warning,
+    overlapping_transitions: Some(Severity::Hint),
+    blank_loops: Some(Severity::Warning),
+};
+/// The completion item kind for states (`EnumMember`).
+const KIND_STATE: u8 = 20;
+/// The completion item kind for symbols (`Constant`).
+const KIND_SYMBOL: u8 = 21;
+
+/// How a state name is used where it appears.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Role {
+    /// The header of the state's rules.
+    Header,
+    /// A stop, accept or reject state in the `states:` section.
+    Declaration,
+    /// A next state, a macro argument or the `start:` state.
+    Reference,
+}
+
+/// A state name in the source.
+#[derive(Debug, Clone)]
+struct Occurrence {
+    name: String,
+    span: Range<usize>,
+    role: Role,
+}
+
+/// An open document.
+pub struct Document {
+    text: String,
+    /// The directory imports are loaded from, if the document is a file.
+    directory: Option<PathBuf>,
+    /// The syntax tree of the text, unless it has syntax errors.
+    tree: Option<SyntaxTree>,
+    /// The error the text fails to parse with.
+    error: Option<TuringMachineError>,
+    /// The last program parsed from the document, which is kept while the text has errors so
+    /// hover and completion still work during edits.
+    program: Option<Program>,
+}
+
+impl Document {
+    /// Creates a document with the given text, at `uri`.
+    pub fn new(uri: &str, text: String) -> Self {
+        let mut document = Self {
+            text: String::new(),
+            directory: file_path(uri).and_then(|path| Some(path.parent()?.to_path_buf())),
+            tree: None,
+            error: None,
+            program: None,
+        };
+        document.update(text);
+        document
+    }
+
+    /// Replaces the text of the document. Imports are loaded relative to the document's file,
+    /// and aren't supported for documents that aren't files.
+    pub fn update(&mut self, text: String) {
+        self.tree = parse_syntax(&text).ok();
+        let directory = self.directory.as_ref();
+        let load = |import: &str| match directory {
+            Some(directory) => ProgramLoader::load_program(&directory.join(import)),
+            None => Err(TuringMachineError::FileError(
+                "Imports are only supported in files".to_string(),
+            )),
+        };
+        match parse_unchecked_with_imports(&text, load) {
+            Ok(program) => {
+                self.program = Some(program);
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error),
+        }
+        self.text = text;
+    }
+
+    /// Returns the diagnostics of the document: the error it fails to parse with, or every
+    /// finding of the analyzer.
+    pub fn diagnostics(&self, uri: &str) -> Vec<Value> {
+        if let Some(error) = &self.error {
+            let span = self.error_span(error);
+            return vec![self.diagnostic(span, Severity::Error, None, &message(error))];
+        }
+
+        let Some(program) = &self.program else {
+            return Vec::new();
+        };
+
+        diagnose(program, &OPTIONS)
+            .iter()
+            .map(|diagnostic| {
+                let mut value = self.diagnostic(
+                    self.diagnostic_span(diagnostic),
+                    diagnostic.severity,
+                    Some(diagnostic.code),
+                    &diagnostic.message,
+                );
+
+                // E.g. the earlier transition that shadows a transition
+                let related: Vec<Value> = diagnostic
+                    .related
+                    .iter()
+                    .filter_map(|&location| {
+                        let span = self.action_span(self.location_offset(location)?);
+                        Some(json!({
+                            "location": { "uri": uri, "range": self.range(&span) },
+                            "message": "Earlier transition",
+                        }))
+                    })
+                    .collect();
+                if !related.is_empty() {
+                    value["relatedInformation"] = related.into();
+                }
+
+                value
+            })
+            .collect()
+    }
+
+    /// Returns the range of the definition of the state at `offset`.
+    pub fn definition(&self, offset: usize) -> Option<Value> {
+        let occurrence = self.occurrence_at(offset)?;
+        let span = self.definition_span(&occurrence.name)?;
+
+        Some(self.range(&span))
+    }
+
+    /// Returns the ranges where the state at `offset` appears, with or without the places it's
+    /// defined.
+    pub fn references(&self, offset: usize, include_definition: bool) -> Vec<Value> {
+        let Some(occurrence) = self.occurrence_at(offset) else {
+            return Vec::new();
+        };
+
+        self.occurrences()
+            .iter()
+            .filter(|o| o.name == occurrence.name)
+            .filter(|o| include_definition || o.role == Role::Reference)
+            .map(|o| self.range(&o.span))
+            .collect()
+    }
+
+    /// Returns the edits that rename the state at `offset` to `new_name`, or `None` if there's
+    /// no state at `offset`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a message if the state can't be renamed to `new_name`.
+    pub fn rename(&self, offset: usize, new_name: &str) -> Result<Option<Vec<Value>>, String> {
+        let Some(occurrence) = self.occurrence_at(offset) else {
+            return Ok(None);
+        };
+
+        if occurrence.name.contains('(') {
+            return Err(format!(
+                "Can't rename the macro instance {}",
+                occurrence.name
+            ));
+        }
+        if !is_state_name(new_name) {
+            return Err(format!("Invalid state name: {new_name}"));
+        }
+
+        let occurrences = self.occurrences();
+        if new_name != occurrence.name && occurrences.iter().any(|o| o.name == new_name) {
+            return Err(format!("State {new_name} already exists"));
+        }
+
+        Ok(Some(
+            occurrences
+                .iter()
+                .filter(|o| o.name == occurrence.name)
+                .map(|o| json!({ "range": self.range(&o.span), "newText": new_name }))
+                .collect(),
+        ))
+    }
+
+    /// Returns the hover of the state at `offset`: its transition table, or the kind of stop
+    /// state it is.
+    pub fn hover(&self, offset: usize) -> Option<Value> {
+        let occurrence = self.occurrence_at(offset)?;
+        let program = self.program.as_ref()?;
+        let name = &occurrence.name;
+
+        let kind = if program.is_accept_state(name) {
+            " (accept state)"
+        } else if program.is_reject_state(name) {
+            " (reject state)"
+        } else if program.is_stop_state(name) || name == "halt" {
+            " (stop state)"
+        } else {
+            ""
+        };
+
+        let mut contents = format!("**{name}**{kind}");
+        match program.rules.get(name) {
+            Some(transitions) if !transitions.is_empty() => {
+                contents.push_str("\n\n| Read | Write | Move | Next |\n|---|---|---|---|");
+                for transition in transitions {
+                    contents.push_str(&table_row(transition));
+                }
+            }
+            Some(_) => contents.push_str("\n\nNo transitions"),
+            None if !kind.is_empty() => {}
+            None => return None,
+        }
+
+        Some(json!({
+            "contents": { "kind": "markdown", "value": contents },
+            "range": self.range(&occurrence.span),
+        }))
+    }
+
+    /// Returns the completion items: every defined state name and known symbol.
+    pub fn completion(&self) -> Vec<Value> {
+        let mut states: BTreeSet<String> = BTreeSet::from(["halt".to_string()]);
+        let mut symbols: BTreeSet<char> = BTreeSet::from([INPUT_BLANK_SYMBOL, WILDCARD_SYMBOL]);
+
+        states.extend(
+            self.occurrences()
+                .into_iter()
+                .filter(|o| o.role != Role::Reference)
+                .map(|o| o.name),
+        );
+        if let Some(program) = &self.program {
+            states.extend(program.rules.keys().cloned());
+            states.extend(program.declared_stop_states().cloned());
+            symbols.extend(program.tapes.iter().flat_map(|tape| tape.chars()));
+            symbols.extend(
+                program
+                    .rules
+                    .values()
+                    .flatten()
+                    .flat_map(|transition| transition.read.iter().chain(&transition.write)),
+            );
+        }
+
+        // Macro instances are used through their macro, so they aren't offered
+        let states = states
+            .into_iter()
+            .filter(|state| !state.contains('('))
+            .map(|state| json!({ "label": state, "kind": KIND_STATE, "detail": "state" }));
+        let symbols = symbols.into_iter().filter(|&symbol| symbol != ' ').map(
+            |symbol| json!({ "label": quote(symbol), "kind": KIND_SYMBOL, "detail": "symbol" }),
+        );
+
+        states.chain(symbols).collect()
+    }
+
+    /// Returns the byte offset of an LSP position, with its character counted in UTF-16 code
+    /// units, or `None` if the line is past the end of the text.
+    pub fn offset(&self, position: &Value) -> Option<usize> {
+        let line = position["line"].as_u64()? as usize;
+        let character = position["character"].as_u64()? as usize;
+
+        let start = self.line_start(line)?;
+        let text = &self.text[start..];
+        let text = &text[..text.find('\n').unwrap_or(text.len())];
+
+        let mut units = 0;
+        for (i, c) in text.char_indices() {
+            if units >= character {
+                return Some(start + i);
+            }
+            units += c.len_utf16();
+        }
+
+        Some(start + text.len())
+    }
+
+    /// Returns the LSP position of a byte offset.
+    fn position(&self, offset: usize) -> Value {
+        let before = &self.text[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+        json!({
+            "line": before.matches('\n').count(),
+            "character": before[line_start..].encode_utf16().count(),
+        })
+    }
+
+    /// Returns the LSP range of a byte range.
+    fn range(&self, span: &Range<usize>) -> Value {
+        json!({ "start": self.position(span.start), "end": self.position(span.end) })
+    }
+
+    /// Returns the byte offset of the start of a line, counted from 0.
+    fn line_start(&self, line: usize) -> Option<usize> {
+        if line == 0 {
+            return Some(0);
+        }
+
+        self.text
+            .match_indices('\n')
+            .nth(line - 1)
+            .map(|(i, _)| i + 1)
+    }
+
+    /// Returns the byte range from `offset` to the end of its line.
+    fn rest_of_line(&self, offset: usize) -> Range<usize> {
+        let end = self.text[offset..]
+            .find('\n')
+            .map_or(self.text.len(), |i| offset + i);
+
+        offset..end
+    }
+
+    /// Returns the byte offset of a source location.
+    fn location_offset(&self, location: Location) -> Option<usize> {
+        let start = self.line_start(location.line - 1)?;
+        let (i, _) = self.text[start..].char_indices().nth(location.column - 1)?;
+
+        Some(start + i)
+    }
+
+    /// Returns the byte range of the name that starts at `start`.
+    fn name_span(&self, start: usize) -> Range<usize> {
+        let length = self.text[start..]
+            .find(|c| !is_state_char(c))
+            .unwrap_or(self.text.len() - start);
+
+        start..start + length
+    }
+
+    /// Returns the byte range of the action that starts at `start`, or of the name there.
+    fn action_span(&self, start: usize) -> Range<usize> {
+        self.node_at(start, Rule::action)
+            .map_or_else(|| self.name_span(start), |action| action.span.clone())
+    }
+
+    /// Returns the node of the given kind that starts at `start`.
+    fn node_at(&self, start: usize, kind: Rule) -> Option<&SyntaxNode> {
+        self.tree
+            .iter()
+            .flat_map(|tree| &tree.sections)
+            .flat_map(|section| section.descendants())
+            .find(|node| node.kind == kind && node.span.start == start)
+    }
+
+    /// Returns an LSP diagnostic.
+    fn diagnostic(
+        &self,
+        span: Range<usize>,
+        severity: Severity,
+        code: Option<DiagnosticCode>,
+        message: &str,
+    ) -> Value {
+        let severity = match severity {
+            Severity::Error => 1,
+            Severity::Warning => 2,
+            Severity::Hint => 4,
+        };
+
+        let mut diagnostic = json!({
+            "range": self.range(&span),
+            "severity": severity,
+            "source": "tur",
+            "message": message,
+        });
+        if let Some(code) = code {
+            diagnostic["code"] = code.as_str().into();
+        }
+
+        diagnostic
+    }
+
+    /// Returns the byte range a parse error is reported at.
+    fn error_span(&self, error: &TuringMachineError) -> Range<usize> {
+        // The parser trims the text, so its positions start after the leading whitespace
+        let offset = self.text.len() - self.text.trim_start().len();
+
+        let TuringMachineError::ParseError(error) = error else {
+            return self.rest_of_line(offset);
+        };
+
+        let clamp = |position: usize| (offset + position).min(self.text.len());
+        match error.location {
+            InputLocation::Span((start, end)) => clamp(start)..clamp(end),
+            InputLocation::Pos(position) => {
+                // Cover the character at the position, so the error is visible
+                let start = clamp(position);
+                let length = self.text[start..].chars().next().map_or(0, char::len_utf8);
+                start..start + length
+            }
+        }
+    }
+
+    /// Returns the byte range a finding of the analyzer is reported at: the transition or state
+    /// it's about, or else the section it's about.
+    fn diagnostic_span(&self, diagnostic: &Diagnostic) -> Range<usize> {
+        let start = diagnostic
+            .location
+            .and_then(|location| self.location_offset(location));
+
+        match (diagnostic.code, start) {
+            (
+                DiagnosticCode::UndefinedNextState
+                | DiagnosticCode::ShadowedTransition
+                | DiagnosticCode::OverlappingTransition,
+                Some(start),
+            ) => {
+                if let Some(action) = self.node_at(start, Rule::action) {
+                    // Undefined states are reported at the next state of the action
+                    let next_state = action
+                        .descendants()
+                        .into_iter()
+                        .find(|node| node.kind == Rule::next_state)
+                        .filter(|_| diagnostic.code == DiagnosticCode::UndefinedNextState);
+
+                    return next_state.unwrap_or(action).span.clone();
+                }
+            }
+            (_, Some(start)) => return self.name_span(start),
+            (_, None) => {
+                if let Some(span) = diagnostic
+                    .states
+                    .first()
+                    .and_then(|state| self.definition_span(state))
+                {
+                    return span;
+                }
+            }
+        }
+
+        let sections: &[Rule] = match diagnostic.code {
+            DiagnosticCode::InvalidHead => &[Rule::head, Rule::heads],
+            DiagnosticCode::Structure | DiagnosticCode::UnhandledTapeSymbol => {
+                &[Rule::tape, Rule::tapes]
+            }
+            DiagnosticCode::InvalidStartState | DiagnosticCode::UnenteredStopState => {
+                &[Rule::states, Rule::rules]
+            }
+            _ => &[Rule::rules],
+        };
+
+        let offset = self.text.len() - self.text.trim_start().len();
+        let start = self
+            .tree
+            .iter()
+            .flat_map(|tree| &tree.sections)
+            .find(|section| sections.contains(&section.kind))
+            .map_or(offset, |section| section.span.start);
+
+        self.rest_of_line(start)
+    }
+
+    /// Returns the byte range of the definition of a state: its rules, or else its declaration
+    /// in the `states:` section, or the macro it's an instance of.
+    fn definition_span(&self, name: &str) -> Option<Range<usize>> {
+        let occurrences = self.occurrences();
+        let defined_as = |role| {
+            occurrences
+                .iter()
+                .find(|o| o.name == name && o.role == role)
+                .map(|o| o.span.clone())
+        };
+
+        defined_as(Role::Header)
+            .or_else(|| defined_as(Role::Declaration))
+            .or_else(|| {
+                let source_map = self.program.as_ref()?.source_map.as_ref()?;
+                let start = self.location_offset(*source_map.states.get(name)?)?;
+                Some(self.name_span(start))
+            })
+    }
+
+    /// Returns the innermost state name at `offset`, including an offset just past its end.
+    fn occurrence_at(&self, offset: usize) -> Option<Occurrence> {
+        self.occurrences()
+            .into_iter()
+            .filter(|o| o.span.start <= offset && offset <= o.span.end)
+            .min_by_key(|o| o.span.len())
+    }
+
+    /// Returns every state name in the source, in source order.
+    fn occurrences(&self) -> Vec<Occurrence> {
+        let mut occurrences = Vec::new();
+        let Some(tree) = &self.tree else {
+            return occurrences;
+        };
+
+        for section in &tree.sections {
+            match section.kind {
+                Rule::states => {
+                    for declaration in &section.children {
+                        let role = if declaration.kind == Rule::state_start {
+                            Role::Reference
+                        } else {
+                            Role::Declaration
+                        };
+                        for state in declaration.children_of(Rule::state) {
+                            add_names(state, role, &[], &mut occurrences);
+                        }
+                    }
+                }
+                Rule::rules => {
+                    for node in section.descendants() {
+                        match node.kind {
+                            Rule::state | Rule::macro_instance => {
+                                add_names(node, Role::Header, &[], &mut occurrences)
+                            }
+                            Rule::next_state => {
+                                add_names(node, Role::Reference, &[], &mut occurrences)
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Rule::macros => {
+                    // Macro bodies refer to states, except for the macro's parameters
+                    for definition in section.children_of(Rule::macro_def) {
+                        let params: Vec<&str> = definition
+                            .children_of(Rule::macro_params)
+                            .flat_map(|params| params.children_of(Rule::state))
+                            .map(|param| param.text.as_str())
+                            .collect();
+
+                        for node in definition.descendants() {
+                            if node.kind == Rule::next_state {
+                                add_names(node, Role::Reference, &params, &mut occurrences);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        occurrences
+    }
+}
+
+/// Adds the states named by a state, next state or macro instance node: the whole name, and
+/// the states passed to a macro as arguments. Names in `params` are macro parameters rather
+/// than states, and so are macro instances that take them.
+fn add_names(node: &SyntaxNode, role: Role, params: &[&str], occurrences: &mut Vec<Occurrence>) {
+    // The words of the name, and whether each is a macro name, i.e. followed by `(`
+    let mut words = Vec::new();
+    let mut chars = node.text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if !is_state_char(c) {
+            continue;
+        }
+
+        let mut end = start + c.len_utf8();
+        while let Some(&(i, c)) = chars.peek() {
+            if !is_state_char(c) {
+                break;
+            }
+            end = i + c.len_utf8();
+            chars.next();
+        }
+
+        let is_macro = node.text[end..].starts_with('(');
+        words.push((start..end, is_macro));
+    }
+
+    let is_param = |span: &Range<usize>| params.contains(&&node.text[span.clone()]);
+    let mut add = |name: String, span: &Range<usize>, role| {
+        occurrences.push(Occurrence {
+            name,
+            span: node.span.start + span.start..node.span.start + span.end,
+            role,
+        });
+    };
+
+    if words.iter().any(|(_, is_macro)| *is_macro) {
+        if words
+            .iter()
+            .any(|(span, is_macro)| !is_macro && is_param(span))
+        {
+            return;
+        }
+
+        let name = node.text.chars().filter(|c| !c.is_whitespace()).collect();
+        add(name, &(0..node.text.len()), role);
+        for (span, _) in words.iter().filter(|(_, is_macro)| !is_macro) {
+            add(node.text[span.clone()].to_string(), span, Role::Reference);
+        }
+    } else if let Some((span, _)) = words.first() {
+        if !is_param(span) {
+            add(node.text[span.clone()].to_string(), span, role);
+        }
+    }
+}
+
+/// Returns the path of a `file:` URI, or `None` for other URIs.
+fn file_path(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+
+    // Decode percent-encoded bytes, such as `%20` for a space
+    let encoded = path.as_bytes();
+    let mut bytes = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let hex = encoded
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (encoded[i], hex) {
+            (b'%', Some(byte)) => {
+                bytes.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                bytes.push(byte);
+                i += 1;
+            }
+        }
+    }
+
+    let path = String::from_utf8(bytes).ok()?;
+    // Windows paths are written as `/C:/...`
+    let path = match path.as_bytes() {
+        [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].to_string(),
+        _ => path,
+    };
+
+    Some(PathBuf::from(path))
+}
+
+/// Checks if a character can be part of a state name.
+fn is_state_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '$')
+}
+
+/// Checks if a name is a valid state name, which can be used as a rules header.
+fn is_state_name(name: &str) -> bool {
+    name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+}
+
+/// Returns the message of an error, without the kind of error it is.
+fn message(error: &TuringMachineError) -> String {
+    match error {
+        TuringMachineError::ParseError(error) => error.variant.message().to_string(),
+        TuringMachineError::ValidationError(message) | TuringMachineError::FileError(message) => {
+            message.clone()
+        }
+        error => error.to_string(),
+    }
+}
+
+/// Returns a symbol as it's written in the source, quoted if it's reserved.
+fn quote(symbol: char) -> String {
+    match symbol {
+        '#' | ' ' | ',' | '>' | '<' | '|' | '[' | ']' | '$' => format!("'{symbol}'"),
+        symbol => symbol.to_string(),
+    }
+}
+
+/// Returns the row of a transition in a hover's transition table.
+fn table_row(transition: &Transition) -> String {
+    let symbols = |symbols: &[char]| {
+        let symbols: Vec<String> = symbols.iter().map(|&symbol| quote(symbol)).collect();
+        let symbols = symbols.join(", ").replace('|', "\\|");
+        if transition.read.len() > 1 {
+            format!("`[{symbols}]`")
+        } else {
+            format!("`{symbols}`")
+        }
+    };
+    let directions: Vec<&str> = transition
+        .directions
+        .iter()
+        .map(|direction| match direction {
+            Direction::Left => "L",
+            Direction::Right => "R",
+            Direction::Stay => "S",
+        })
+        .collect();
+
+    format!(
+        "\n| {} | {} | {} | `{}` |",
+        symbols(&transition.read),
+        symbols(&transition.write),
+        directions.join(", "),
+        transition.next_state
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::tempdir;
+
+    const URI: &str = "untitled:Untitled-1";
+
+    const PROGRAM: &str = "name: Rename
+tape: a
+macros:
+  mark(next):
+    * -> X, S, next
+rules:
+  start:
+    a, R, scan
+  scan:
+    a, R, scan
+    _, L, mark(done)
+  done:
+";
+
+    /// Returns the byte offset of the `n`th occurrence of `pattern` in the text.
+    fn nth(text: &str, pattern: &str, n: usize) -> usize {
+        text.match_indices(pattern).nth(n).unwrap().0
+    }
+
+    #[test]
+    fn test_offset_and_position() {
+        // `é` takes 2 bytes and 1 UTF-16 unit, `🦀` 4 bytes and 2 units
+        let text = "name: é🦀x\ntape: a\nrules:\n  start:\n    a, R, halt\n";
+        let document = Document::new(URI, text.to_string());
+        let x = text.find('x').unwrap();
+
+        assert_eq!(
+            document.offset(&json!({ "line": 0, "character": 9 })),
+            Some(x)
+        );
+        assert_eq!(document.position(x), json!({ "line": 0, "character": 9 }));
+        assert_eq!(
+            document.offset(&json!({ "line": 0, "character": 7 })),
+            Some(text.find('🦀').unwrap())
+        );
+
+        // Characters past the end of a line are clamped to it, lines past the text aren't
+        assert_eq!(
+            document.offset(&json!({ "line": 0, "character": 100 })),
+            Some(x + 1)
+        );
+        assert_eq!(
+            document.offset(&json!({ "line": 100, "character": 0 })),
+            None
+        );
+        assert_eq!(
+            document.position(text.find("tape").unwrap()),
+            json!({ "line": 1, "character": 0 })
+        );
+    }
+
+    #[test]
+    fn test_references() {
+        let document = Document::new(URI, PROGRAM.to_string());
+        let scan = nth(PROGRAM, "scan", 0);
+
+        assert_eq!(document.references(scan, true).len(), 3);
+        let references = document.references(scan, false);
+        assert_eq!(references.len(), 2);
+        assert_eq!(references[0], document.range(&(scan..scan + "scan".len())));
+
+        // States passed to a macro are references too
+        let done = nth(PROGRAM, "done", 0);
+        assert_eq!(document.references(done, false).len(), 1);
+        assert!(document.references(0, true).is_empty());
+    }
+
+    #[test]
+    fn test_rename() {
+        let document = Document::new(URI, PROGRAM.to_string());
+        let scan = nth(PROGRAM, "scan", 1);
+
+        let edits = document.rename(scan, "sweep").unwrap().unwrap();
+        assert_eq!(edits.len(), 3);
+        assert!(edits.iter().all(|edit| edit["newText"] == "sweep"));
+
+        assert_eq!(
+            document.rename(scan, "start"),
+            Err("State start already exists".to_string())
+        );
+        assert_eq!(
+            document.rename(scan, "1st"),
+            Err("Invalid state name: 1st".to_string())
+        );
+        assert_eq!(
+            document.rename(nth(PROGRAM, "mark(done)", 0), "marked"),
+            Err("Can't rename the macro instance mark(done)".to_string())
+        );
+        assert_eq!(document.rename(0, "sweep"), Ok(None));
+    }
+
+    #[test]
+    fn test_imports() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("scan.tur"),
+            "name: Scan\ntape: a\nrules:\n  start:\n    a, R, start\n    _, L, done\n  done:",
+        )
+        .unwrap();
+
+        let text = "name: Main\nimport \"scan.tur\" as scan\ntape: a\nrules:\n  start:\n    \
+                    a, S, scan.start";
+        let path = dir.path().join("main file.tur");
+        let uri = format!("file://{}", path.display()).replace(' ', "%20");
+        assert_eq!(file_path(&uri), Some(path));
+
+        // Imports are loaded next to the document's file
+        let document = Document::new(&uri, text.to_string());
+        assert_eq!(document.diagnostics(&uri), Vec::<Value>::new());
+        assert!(document.program.unwrap().rules.contains_key("scan.start"));
+
+        let document = Document::new(URI, text.to_string());
+        let diagnostics = document.diagnostics(URI);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["severity"], 1);
+    }
+}
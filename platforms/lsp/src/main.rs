@@ -0,0 +1,44 @@
+//! A language server for `.tur` programs, speaking the Language Server Protocol over stdio.
+
+mod document;
+mod rpc;
+mod server;
+
+use rpc::{error_response, read_message, write_message, PARSE_ERROR};
+use serde_json::Value;
+use server::Server;
+use std::io::{self, BufReader};
+use std::process;
+
+fn main() {
+    let mut reader = BufReader::new(io::stdin().lock());
+    let mut writer = io::stdout().lock();
+    let mut server = Server::default();
+
+    loop {
+        let body = match read_message(&mut reader) {
+            Ok(Some(body)) => body,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("tur-lsp: {e}");
+                process::exit(1);
+            }
+        };
+
+        let replies = match serde_json::from_str::<Value>(&body) {
+            Ok(message) if message["method"] == "exit" => {
+                // Exiting without a shutdown request is an error
+                process::exit(if server.is_shutdown() { 0 } else { 1 });
+            }
+            Ok(message) => server.handle(&message),
+            Err(e) => vec![error_response(Value::Null, PARSE_ERROR, &e.to_string())],
+        };
+
+        for reply in replies {
+            if let Err(e) = write_message(&mut writer, &reply) {
+                eprintln!("tur-lsp: {e}");
+                process::exit(1);
+            }
+        }
+    }
+}
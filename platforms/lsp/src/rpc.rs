@@ -0,0 +1,113 @@
+//! JSON-RPC framing for the Language Server Protocol: each message is a JSON body preceded by
+//! a `Content-Length` header and a blank line.
+
+use serde_json::{json, Value};
+use std::io::{self, BufRead, Write};
+
+/// The JSON-RPC error code for a message that isn't valid JSON.
+pub const PARSE_ERROR: i64 = -32700;
+/// The JSON-RPC error code for a request the server doesn't handle.
+pub const METHOD_NOT_FOUND: i64 = -32601;
+/// The JSON-RPC error code for a request with invalid parameters.
+pub const INVALID_PARAMS: i64 = -32602;
+
+/// Reads the body of the next message, or `None` at the end of the input.
+pub fn read_message(reader: &mut impl BufRead) -> io::Result<Option<String>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+
+        // Other headers, such as `Content-Type`, are ignored
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+
+    let length = length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "Missing Content-Length header")
+    })?;
+
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+
+    String::from_utf8(body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Writes a message with its `Content-Length` header.
+pub fn write_message(writer: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    writer.flush()
+}
+
+/// Returns the response to the request `id` with its result.
+pub fn response(id: Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+/// Returns the response to the request `id` when it fails.
+pub fn error_response(id: Value, code: i64, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": { "code": code, "message": message },
+    })
+}
+
+/// Returns a notification sent to the client.
+pub fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_message() {
+        // The length counts bytes, and other headers are ignored
+        let input = "Content-Length: 4\r\n\r\n\"é\"\
+                     content-length: 2\r\nContent-Type: application/json\r\n\r\n{}";
+        let mut reader = input.as_bytes();
+
+        assert_eq!(
+            read_message(&mut reader).unwrap(),
+            Some("\"é\"".to_string())
+        );
+        assert_eq!(read_message(&mut reader).unwrap(), Some("{}".to_string()));
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+
+        let mut reader = "Content-Type: application/json\r\n\r\n{}".as_bytes();
+        assert!(read_message(&mut reader).is_err());
+
+        // A body shorter than its length is an error
+        let mut reader = "Content-Length: 10\r\n\r\n{}".as_bytes();
+        assert!(read_message(&mut reader).is_err());
+    }
+
+    #[test]
+    fn test_write_message() {
+        let mut output = Vec::new();
+        write_message(&mut output, &json!({ "id": 1 })).unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(output, "Content-Length: 8\r\n\r\n{\"id\":1}");
+        assert_eq!(
+            read_message(&mut output.as_bytes()).unwrap(),
+            Some("{\"id\":1}".to_string())
+        );
+    }
+}
@@ -0,0 +1,173 @@
+//! The language server: it keeps the open documents and answers the client's messages.
+
+use crate::document::Document;
+use crate::rpc::{error_response, notification, response, INVALID_PARAMS, METHOD_NOT_FOUND};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// The `textDocumentSync` kind for sending the full text of a document on every change.
+const SYNC_FULL: u8 = 1;
+
+/// The state of the language server.
+#[derive(Default)]
+pub struct Server {
+    /// The open documents, by URI.
+    documents: HashMap<String, Document>,
+    /// Whether the client asked the server to shut down.
+    shutdown: bool,
+}
+
+impl Server {
+    /// Returns whether the client asked the server to shut down before exiting.
+    pub fn is_shutdown(&self) -> bool {
+        self.shutdown
+    }
+
+    /// Handles a message from the client, returning the messages to send back.
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        // Requests have an id, notifications don't
+        let Some(id) = message.get("id").cloned() else {
+            return self.notify(method, params);
+        };
+        if method.is_empty() {
+            // A response to a request of the server, which sends none
+            return Vec::new();
+        }
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": SYNC_FULL,
+                    "definitionProvider": true,
+                    "referencesProvider": true,
+                    "renameProvider": true,
+                    "hoverProvider": true,
+                    "completionProvider": {},
+                },
+                "serverInfo": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Ok(Value::Null)
+            }
+            "textDocument/definition" => Ok(self
+                .at(params)
+                .and_then(|(uri, document, offset)| {
+                    let range = document.definition(offset)?;
+                    Some(json!({ "uri": uri, "range": range }))
+                })
+                .unwrap_or_default()),
+            "textDocument/references" => {
+                let include_definition = params["context"]["includeDeclaration"]
+                    .as_bool()
+                    .unwrap_or(true);
+                Ok(self
+                    .at(params)
+                    .map(|(uri, document, offset)| {
+                        document
+                            .references(offset, include_definition)
+                            .into_iter()
+                            .map(|range| json!({ "uri": uri, "range": range }))
+                            .collect()
+                    })
+                    .unwrap_or_default())
+            }
+            "textDocument/rename" => {
+                let new_name = params["newName"].as_str().unwrap_or_default();
+                match self.at(params) {
+                    Some((uri, document, offset)) => {
+                        document.rename(offset, new_name).map(|edits| match edits {
+                            Some(edits) => json!({ "changes": { uri: edits } }),
+                            None => Value::Null,
+                        })
+                    }
+                    None => Ok(Value::Null),
+                }
+            }
+            "textDocument/hover" => Ok(self
+                .at(params)
+                .and_then(|(_, document, offset)| document.hover(offset))
+                .unwrap_or_default()),
+            "textDocument/completion" => Ok(self
+                .at(params)
+                .map(|(_, document, _)| Value::from(document.completion()))
+                .unwrap_or_default()),
+            _ => {
+                return vec![error_response(
+                    id,
+                    METHOD_NOT_FOUND,
+                    &format!("Unsupported method: {method}"),
+                )]
+            }
+        };
+
+        match result {
+            Ok(result) => vec![response(id, result)],
+            Err(message) => vec![error_response(id, INVALID_PARAMS, &message)],
+        }
+    }
+
+    /// Handles a notification, returning the diagnostics of the document it changed.
+    fn notify(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string();
+
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents
+                    .insert(uri.clone(), Document::new(&uri, text.to_string()));
+            }
+            "textDocument/didChange" => {
+                // With full sync, the last change holds the whole text
+                let Some(text) = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .and_then(|change| change["text"].as_str())
+                else {
+                    return Vec::new();
+                };
+                match self.documents.get_mut(&uri) {
+                    Some(document) => document.update(text.to_string()),
+                    None => {
+                        self.documents
+                            .insert(uri.clone(), Document::new(&uri, text.to_string()));
+                    }
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                return vec![publish_diagnostics(&uri, Vec::new())];
+            }
+            _ => return Vec::new(),
+        }
+
+        let diagnostics = self.documents[&uri].diagnostics(&uri);
+        vec![publish_diagnostics(&uri, diagnostics)]
+    }
+
+    /// Returns the document and byte offset of a request's `textDocument` and `position`.
+    fn at<'a>(&'a self, params: &'a Value) -> Option<(&'a str, &'a Document, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let document = self.documents.get(uri)?;
+        let offset = document.offset(&params["position"])?;
+
+        Some((uri, document, offset))
+    }
+}
+
+/// Returns the notification that publishes the diagnostics of a document.
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    notification(
+        "textDocument/publishDiagnostics",
+        json!({ "uri": uri, "diagnostics": diagnostics }),
+    )
+}
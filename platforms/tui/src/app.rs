@@ -8,8 +8,8 @@ use ratatui::{
     Frame,
 };
 use tur::{
-    types::{DEFAULT_BLANK_SYMBOL, INPUT_BLANK_SYMBOL},
-    Program, ProgramLoader, ProgramManager, Step, TuringMachine,
+    types::{Mode, DEFAULT_BLANK_SYMBOL, INPUT_BLANK_SYMBOL},
+    Breakpoint, Halt, Pause, Program, ProgramLoader, ProgramManager, TuringMachine,
 };
 
 const BLOCK_PADDING: Padding = Padding::new(1, 1, 0, 0);
@@ -33,7 +33,7 @@ impl App {
         let program_content = ProgramManager::get_program_text_by_index(0)
             .unwrap()
             .to_string();
-        let machine = TuringMachine::new(program);
+        let machine = TuringMachine::new(program).with_history(true);
 
         Self {
             machine,
@@ -51,7 +51,11 @@ impl App {
     pub fn new_from_program_string(program_content: String) -> Result<Self, String> {
         let program: Program = ProgramLoader::load_program_from_string(&program_content)
             .map_err(|e| format!("Failed to load program: {}", e))?;
-        let machine = TuringMachine::new(program);
+        // Only the CLI follows every matching transition
+        if program.mode == Mode::Nondeterministic {
+            return Err("Nondeterministic programs can only be run with the CLI".to_string());
+        }
+        let machine = TuringMachine::new(program).with_history(true);
 
         Ok(Self {
             machine,
@@ -124,6 +128,15 @@ impl App {
         let mut lines = Vec::new();
         for line in self.program_content.lines() {
             let mut spans = Vec::new();
+
+            // Mark the definitions of states with a breakpoint
+            if let Some(state) = line.trim().strip_suffix(':') {
+                let breakpoint = Breakpoint::State(state.to_string());
+                if self.machine.breakpoints().contains(&breakpoint) {
+                    spans.push(Span::styled("● ", Style::default().fg(Color::Red)));
+                }
+            }
+
             let mut parts = line.split_whitespace();
             if let Some(first_word) = parts.next() {
                 if keywords.contains(&first_word) {
@@ -196,12 +209,13 @@ impl App {
 
     fn render_tapes(&self, f: &mut Frame, area: Rect) {
         let tapes = self.machine.tapes();
-        let head_positions = self.machine.heads();
         let tape_count = tapes.len();
 
         let mut text_lines = Vec::new();
 
-        for (tape_idx, (tape, &head_pos)) in tapes.iter().zip(head_positions.iter()).enumerate() {
+        for (tape_idx, tape) in tapes.iter().enumerate() {
+            let head_pos = tape.head();
+
             // Always show tape number for consistency (even for single tape)
             text_lines.push(Line::from(vec![Span::styled(
                 format!("Tape {}: ", tape_idx + 1),
@@ -210,9 +224,13 @@ impl App {
                     .add_modifier(Modifier::BOLD),
             )]));
 
+            // Show the stored cells, plus the head's cell if it's past them
+            let positions = tape.start().min(head_pos)..tape.end().max(head_pos + 1);
+
             // Create tape visualization
             let mut tape_spans = Vec::new();
-            for (i, &symbol) in tape.iter().enumerate() {
+            for pos in positions.clone() {
+                let symbol = tape.get(pos);
                 // Fix span render blank char incorrectly (it renders in a new line).
                 let symbol = if symbol == DEFAULT_BLANK_SYMBOL {
                     INPUT_BLANK_SYMBOL
@@ -220,7 +238,7 @@ impl App {
                     symbol
                 };
 
-                if i == head_pos {
+                if pos == head_pos {
                     // For head position, use brackets to make highlighting more visible
                     tape_spans.push(Span::styled(
                         format!(" {symbol} "),
@@ -237,16 +255,22 @@ impl App {
 
             text_lines.push(Line::from(tape_spans));
 
+            // Position ruler, labelling every fifth cell with its absolute position
+            let ruler: String = positions
+                .map(|pos| match pos.to_string() {
+                    label if pos % 5 == 0 && label.len() <= 3 => format!("{label:^3}"),
+                    _ => "   ".to_string(),
+                })
+                .collect();
+
+            text_lines.push(Line::from(Span::styled(
+                ruler,
+                Style::default().fg(Color::DarkGray),
+            )));
+
             // Head position indicator (consistent format)
-            let current_symbol = if head_pos < tape.len() {
-                tape[head_pos]
-            } else {
-                self.machine.blank()
-            };
-            let head_indicator = format!(
-                "Head at position: {} (symbol: '{}')",
-                head_pos, current_symbol
-            );
+            let head_indicator =
+                format!("Head at position: {} (symbol: '{}')", head_pos, tape.read());
 
             text_lines.push(Line::from(Span::styled(
                 head_indicator,
@@ -272,6 +296,8 @@ impl App {
 
         let (status_text, status_color) = if is_halted {
             ("HALTED", Color::Red)
+        } else if self.machine.is_step_limit_reached() {
+            ("LIMIT REACHED", Color::Magenta)
         } else if step_count == 0 {
             ("READY", Color::Blue)
         } else {
@@ -317,8 +343,11 @@ impl App {
         let help_text = vec![
             Line::from("Controls:"),
             Line::from("  Space - Step forward"),
+            Line::from("  b / Backspace - Step back"),
             Line::from("  r - Reset machine"),
-            Line::from("  p - Toggle auto-play"),
+            Line::from("  t - Toggle breakpoint on the current state"),
+            Line::from("  p - Toggle auto-play (pauses at breakpoints)"),
+            Line::from("  l - Toggle loop detection (remembers every configuration)"),
             Line::from(if self.program_loaded_from_source {
                 "  ← → - Program switching disabled (loaded from file/stdin)"
             } else {
@@ -350,9 +379,16 @@ impl App {
             .split(inner);
 
         let auto_play_status = if self.auto_play { "ON" } else { "OFF" };
+        let loop_detection_status = if self.machine.detects_loops() {
+            "ON"
+        } else {
+            "OFF"
+        };
         let status = Line::from(vec![
             Span::raw("Auto-play: "),
             Span::styled(auto_play_status, Style::default().fg(Color::Yellow)),
+            Span::raw(" | Loop detection: "),
+            Span::styled(loop_detection_status, Style::default().fg(Color::Yellow)),
             Span::raw(format!(" | {}", self.message)),
         ]);
 
@@ -366,17 +402,65 @@ impl App {
     }
 
     pub fn step_machine(&mut self) {
-        match self.machine.step() {
-            Step::Continue => {
+        match self.machine.step_with_breakpoints() {
+            None => {
                 self.message = format!("Step {} completed", self.machine.step_count());
             }
-            Step::Halt(_) => {
-                self.message = "Machine is halted. Press 'r' to reset.".to_string();
+            Some(Pause::Breakpoint(breakpoint)) => {
+                self.message = format!(
+                    "Breakpoint {} hit at step {}",
+                    breakpoint,
+                    self.machine.step_count()
+                );
+                self.auto_play = false;
+            }
+            Some(Pause::Halt(halt)) => {
+                self.message = match halt {
+                    Halt::Accept => "Machine accepted. Press 'r' to reset.".to_string(),
+                    Halt::Reject(rejection) => format!(
+                        "Machine rejected in state '{}'. Press 'r' to reset.",
+                        rejection.state
+                    ),
+                    Halt::StepLimit => format!(
+                        "Step limit of {} reached. Press 'r' to reset.",
+                        self.machine.max_steps()
+                    ),
+                    Halt::Loop {
+                        first_seen_step,
+                        period,
+                    } => format!(
+                        "Loop detected: step {} repeats step {first_seen_step} (period {period}). Press 'r' to reset.",
+                        self.machine.step_count()
+                    ),
+                    Halt::Err(err) => format!("Machine halted with error: {err}"),
+                    Halt::Ok => "Machine is halted. Press 'r' to reset.".to_string(),
+                };
                 self.auto_play = false;
             }
         }
     }
 
+    pub fn step_back_machine(&mut self) {
+        self.auto_play = false;
+        self.message = if self.machine.step_back() {
+            format!("Stepped back to step {}", self.machine.step_count())
+        } else {
+            "Already at the initial configuration".to_string()
+        };
+    }
+
+    pub fn toggle_breakpoint(&mut self) {
+        let state = self.machine.state().to_string();
+        self.message = if self
+            .machine
+            .toggle_breakpoint(Breakpoint::State(state.clone()))
+        {
+            format!("Breakpoint set on state '{state}'")
+        } else {
+            format!("Breakpoint removed from state '{state}'")
+        };
+    }
+
     pub fn reset_machine(&mut self) {
         self.machine.reset();
         self.message = "Machine reset".to_string();
@@ -396,6 +480,17 @@ impl App {
         );
     }
 
+    pub fn toggle_loop_detection(&mut self) {
+        self.message = format!(
+            "Loop detection {}",
+            if self.machine.toggle_loop_detection() {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+    }
+
     pub fn is_auto_playing(&self) -> bool {
         self.auto_play && !self.machine.is_halted()
     }
@@ -432,7 +527,10 @@ impl App {
                 .to_string();
         let tape_count = program.tapes.len();
         let program_name = program.name.clone();
-        self.machine = TuringMachine::new(program);
+        // Loop detection stays as the user toggled it for the previous program
+        self.machine = TuringMachine::new(program)
+            .with_history(true)
+            .with_loop_detection(self.machine.detects_loops());
         self.auto_play = false;
         self.scroll_offset = 0;
 
@@ -448,7 +546,7 @@ fn section<'a>(title: &'a str, content: Vec<Line<'a>>) -> Paragraph<'a> {
     Paragraph::new(content).block(block(title))
 }
 
-fn block(title: &str) -> Block {
+fn block(title: &str) -> Block<'_> {
     Block::default()
         .borders(Borders::ALL)
         .border_type(BorderType::Rounded)
@@ -140,7 +140,10 @@ fn handle_key_event(app: &mut App, key: KeyEvent) -> AppState {
             Action::Quit => return AppState::ShouldQuit,
             Action::Reset => app.reset_machine(),
             Action::Step => app.step_machine(),
+            Action::StepBack => app.step_back_machine(),
+            Action::ToggleBreakpoint => app.toggle_breakpoint(),
             Action::ToggleAutoPlay => app.toggle_auto_play(),
+            Action::ToggleLoopDetection => app.toggle_loop_detection(),
             Action::ToggleHelp => app.toggle_help(),
             Action::PreviousProgram => app.previous_program(),
             Action::NextProgram => app.next_program(),
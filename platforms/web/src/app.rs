@@ -4,19 +4,20 @@ use action::Action;
 use gloo_events::EventListener;
 
 use keymap::{Config, KeyMapConfig};
-use tur::types::Halt;
-use tur::{Direction, Program, ProgramManager, Step, Transition, TuringMachine};
+use tur::types::{Halt, Mode};
+use tur::{Breakpoint, Pause, Program, ProgramManager, Transition, TuringMachine};
 use wasm_bindgen::JsCast;
 use yew::prelude::*;
 
 pub enum Msg {
     PerformAction(Action),
     Step,
+    StepBack,
     Reset,
     ToggleAutoPlay,
     SelectProgram(usize),
     AutoStep,
-    LoadCustomProgram(Program),
+    LoadCustomProgram(Box<Program>),
     EditorError(String),
     UpdateEditorText(String),
     SetSpeed(u64),
@@ -39,7 +40,6 @@ pub struct App {
     _keyboard_listener: EventListener,
     keymap: Config<Action>,
     show_program_editor_help: bool,
-    tape_left_offsets: Vec<usize>,
 }
 
 impl App {
@@ -52,8 +52,7 @@ impl App {
     ) -> Self {
         let initial_state = program.initial_state.clone();
         let current_program_def = program.clone();
-        let machine = TuringMachine::new(program);
-        let num_tapes = machine.tapes().len();
+        let machine = TuringMachine::new(program).with_history(true);
 
         Self {
             machine,
@@ -71,7 +70,6 @@ impl App {
             _keyboard_listener: keyboard_listener,
             keymap: Action::keymap_config(),
             show_program_editor_help: false,
-            tape_left_offsets: vec![0; num_tapes],
         }
     }
 }
@@ -110,16 +108,27 @@ impl Component for App {
 
         // Check if there's a shared program in the URL
         if let Some(shared_program) = UrlSharing::extract_from_url() {
-            if let Ok(program) = tur::parser::parse(&shared_program.code) {
-                return App::new(
-                    program,
-                    shared_program.code,
-                    usize::MAX, // Custom program index
-                    format!("Loaded shared program: {}", shared_program.name),
-                    keyboard_listener,
-                );
-            } else if let Err(e) = tur::parser::parse(&shared_program.code) {
-                web_sys::console::warn_1(&format!("Failed to parse shared program: {}", e).into());
+            match tur::parser::parse(&shared_program.code) {
+                // Only the CLI follows every matching transition
+                Ok(program) if program.mode == Mode::Nondeterministic => {
+                    web_sys::console::warn_1(
+                        &"Nondeterministic shared programs can only be run with the CLI".into(),
+                    );
+                }
+                Ok(program) => {
+                    return App::new(
+                        program,
+                        shared_program.code,
+                        usize::MAX, // Custom program index
+                        format!("Loaded shared program: {}", shared_program.name),
+                        keyboard_listener,
+                    );
+                }
+                Err(e) => {
+                    web_sys::console::warn_1(
+                        &format!("Failed to parse shared program: {}", e).into(),
+                    );
+                }
             }
         }
 
@@ -139,12 +148,36 @@ impl Component for App {
                     Action::Step => {
                         ctx.link().send_message(Msg::Step);
                     }
+                    Action::StepBack => {
+                        ctx.link().send_message(Msg::StepBack);
+                    }
                     Action::Reset => {
                         ctx.link().send_message(Msg::Reset);
                     }
                     Action::ToggleAutoPlay => {
                         ctx.link().send_message(Msg::ToggleAutoPlay);
                     }
+                    Action::ToggleLoopDetection => {
+                        self.message = format!(
+                            "Loop detection {}",
+                            if self.machine.toggle_loop_detection() {
+                                "enabled"
+                            } else {
+                                "disabled"
+                            }
+                        );
+                    }
+                    Action::ToggleBreakpoint => {
+                        let state = self.machine.state().to_string();
+                        self.message = if self
+                            .machine
+                            .toggle_breakpoint(Breakpoint::State(state.clone()))
+                        {
+                            format!("Breakpoint set on state {state}")
+                        } else {
+                            format!("Breakpoint removed from state {state}")
+                        };
+                    }
                     Action::ToggleHelp => {
                         self.show_program_editor_help = !self.show_program_editor_help;
                     }
@@ -192,40 +225,59 @@ impl Component for App {
 
                 self.previous_state = self.machine.state().to_string();
                 self.last_transition = self.machine.transition().cloned();
-                let last_head_positions = self.machine.heads().to_vec();
-                let last_tape_lengths: Vec<usize> =
-                    self.machine.tapes().iter().map(|t| t.len()).collect();
 
-                let result = self.machine.step();
-
-                match result {
-                    Step::Continue => {
+                match self.machine.step_with_breakpoints() {
+                    None => {
                         self.message = format!("Step {} completed", self.machine.step_count());
                         self.machine_state = MachineState::Running;
                     }
-                    Step::Halt(reason) => {
+                    Some(Pause::Breakpoint(breakpoint)) => {
+                        self.message = format!(
+                            "Breakpoint {} hit at step {}",
+                            breakpoint,
+                            self.machine.step_count()
+                        );
+                        self.machine_state = MachineState::Running;
+                        self.auto_play = false;
+                    }
+                    Some(Pause::Halt(reason)) => {
+                        self.machine_state = match reason {
+                            Halt::StepLimit => MachineState::LimitReached,
+                            _ => MachineState::Halted,
+                        };
                         self.message = match reason {
                             Halt::Ok => "Machine halted".to_string(),
+                            Halt::Accept => "Machine accepted".to_string(),
+                            Halt::Reject(rejection) => {
+                                format!("Machine rejected in state {}", rejection.state)
+                            }
+                            Halt::StepLimit => format!(
+                                "Step limit of {} reached without halting",
+                                self.machine.max_steps()
+                            ),
+                            Halt::Loop {
+                                first_seen_step,
+                                period,
+                            } => format!(
+                                "Loop detected: step {} repeats step {first_seen_step} (period {period})",
+                                self.machine.step_count()
+                            ),
                             Halt::Err(err) => format!("Machine halted with error: {err}"),
                         };
-                        self.machine_state = MachineState::Halted;
                         self.auto_play = false;
                     }
                 }
-
-                // Update tape's left offset for animation purposes
-                let new_tape_lengths: Vec<usize> =
-                    self.machine.tapes().iter().map(|t| t.len()).collect();
-
-                if let Some(transition) = &self.last_transition {
-                    for i in 0..last_head_positions.len() {
-                        // Check if tape expanded to the left (length increased)
-                        if transition.directions[i] == Direction::Left
-                            && new_tape_lengths[i] > last_tape_lengths[i]
-                        {
-                            self.tape_left_offsets[i] += 1;
-                        }
-                    }
+                true
+            }
+            Msg::StepBack => {
+                self.auto_play = false;
+                if self.machine.step_back() {
+                    self.message = format!("Stepped back to step {}", self.machine.step_count());
+                    self.machine_state = MachineState::Running;
+                    self.last_transition = None;
+                    self.previous_state = self.machine.state().to_string();
+                } else {
+                    self.message = "Already at the initial configuration".to_string();
                 }
                 true
             }
@@ -234,7 +286,6 @@ impl Component for App {
                 self.message = "Machine reset".to_string();
                 self.auto_play = false;
                 self.last_transition = None;
-                self.tape_left_offsets = vec![0; self.machine.tapes().len()];
                 self.previous_state = self.machine.initial_state().to_string();
                 self.machine_state = MachineState::Running;
                 true
@@ -272,8 +323,9 @@ impl Component for App {
                     self.current_program_def = program.clone();
                     self.last_transition = None;
                     self.previous_state = program.initial_state.clone();
-                    self.machine = TuringMachine::new(program);
-                    self.tape_left_offsets = vec![0; self.machine.tapes().len()];
+                    self.machine = TuringMachine::new(program)
+                        .with_history(true)
+                        .with_loop_detection(self.machine.detects_loops());
                     self.message = "".to_string();
                     self.machine_state = MachineState::Running;
                     true
@@ -298,11 +350,12 @@ impl Component for App {
                 self.auto_play = false;
                 self.is_program_ready = true;
                 self.current_program = usize::MAX; // Indicate custom program
-                self.current_program_def = program.clone();
+                self.current_program_def = (*program).clone();
                 self.last_transition = None;
                 self.previous_state = program.initial_state.clone();
-                self.machine = TuringMachine::new(program);
-                self.tape_left_offsets = vec![0; self.machine.tapes().len()];
+                self.machine = TuringMachine::new(*program)
+                    .with_history(true)
+                    .with_loop_detection(self.machine.detects_loops());
                 self.message = "".to_string();
                 self.machine_state = MachineState::Running;
                 true
@@ -361,7 +414,7 @@ impl Component for App {
                                 <ProgramEditor
                                     program_text={self.editor_text.clone()}
                                     is_ready={self.is_program_ready}
-                                    on_program_submit={link.callback(Msg::LoadCustomProgram)}
+                                    on_program_submit={link.callback(|program| Msg::LoadCustomProgram(Box::new(program)))}
                                     on_error={link.callback(Msg::EditorError)}
                                     on_text_change={link.callback(Msg::UpdateEditorText)}
                                     current_program={self.current_program}
@@ -386,7 +439,6 @@ impl Component for App {
                                 <div class="card-body">
                                     <TapeView
                                         tapes={self.machine.tapes().to_vec()}
-                                        head_positions={self.machine.heads().to_vec()}
                                         auto_play={self.auto_play}
                                         machine_state={self.machine_state.clone()}
                                         is_program_ready={self.is_program_ready}
@@ -395,11 +447,12 @@ impl Component for App {
                                         step_count={self.machine.step_count()}
                                         current_symbols={self.machine.symbols()}
                                         on_step={link.callback(|_| Msg::Step)}
+                                        on_step_back={link.callback(|_| Msg::StepBack)}
+                                        can_step_back={self.machine.can_step_back()}
                                         on_reset={link.callback(|_| Msg::Reset)}
                                         on_toggle_auto={link.callback(|_| Msg::ToggleAutoPlay)}
                                         speed={self.speed}
                                         on_speed_change={link.callback(|speed: u64| Msg::SetSpeed(speed))}
-                                        tape_left_offsets={self.tape_left_offsets.clone()}
                                         message={self.message.clone()}
                                     />
                                 </div>
@@ -1,5 +1,5 @@
 use serde_json::json;
-use tur::{Program, Transition};
+use tur::{coverage, head_analysis, Program, Transition};
 use yew::prelude::*;
 
 #[derive(Properties, Clone)]
@@ -117,8 +117,22 @@ impl GraphView {
             all_states.insert("start".to_string());
         }
 
+        // States missing a transition for some symbols, or looping forever on blank, are
+        // highlighted
+        let coverage = coverage(&props.program);
+        let heads = head_analysis(&props.program);
+
         for state in &all_states {
-            let classes = String::new();
+            let mut classes = String::new();
+            if coverage
+                .state(state)
+                .is_some_and(|coverage| !coverage.is_complete())
+            {
+                classes.push_str("incomplete");
+            }
+            if heads.state(state).is_some_and(|heads| heads.loops_on_blank) {
+                classes.push_str(" blank-loop");
+            }
             // Initial classes, will be updated by update_node_styles
             elements.push(json!({
                 "data": {
@@ -194,6 +208,8 @@ impl GraphView {
                                 {{ selector: '.current', style: {{ 'background-color': gt.activeNodeColor }} }},
                                 {{ selector: '.previous', style: {{ 'background-color': gt.defaultNodeColor }} }},
                                 {{ selector: '.halt', style: {{ 'background-color': gt.defaultNodeColor }} }},
+                                {{ selector: '.incomplete', style: {{ 'border-width': '3px', 'border-style': 'dashed', 'border-color': gt.incompleteNodeColor }} }},
+                                {{ selector: '.blank-loop', style: {{ 'border-width': '3px', 'border-style': 'double', 'border-color': gt.blankLoopNodeColor }} }},
                                 {{ selector: 'edge', style: {{ 'width': 2, 'line-color': gt.edgeColor, 'target-arrow-color': gt.edgeColor, 'target-arrow-shape': 'triangle', 'curve-style': 'bezier', 'font-family': '"Fira Code", monospace', 'font-size': '12px', 'color': '#444', 'text-background-color': '#F5F7FA', 'text-background-opacity': 0.8 }} }},
                                 {{ selector: 'edge[label]', style: {{ 'label': 'data(label)', 'text-wrap': 'wrap', 'text-max-width': '120px' }} }}
                             ],
@@ -233,8 +249,7 @@ impl GraphView {
                 const currentNode = window.graphCy.getElementById('{}');
 
                 allNodes.stop(true, true).style({{
-                    'background-color': gt.defaultNodeColor,
-                    'border-width': '0px'
+                    'background-color': gt.defaultNodeColor
                 }});
                 allNodes.removeClass('current previous');
 
@@ -8,6 +8,7 @@ mod tape_view;
 pub enum MachineState {
     Running,
     Halted,
+    LimitReached,
 }
 
 pub use graph_view::GraphView;
@@ -1,5 +1,5 @@
 use crate::components::ProgramSelector;
-use tur::{parser::parse, Program, TuringMachineError, MAX_PROGRAM_SIZE};
+use tur::{parser::parse, types::Mode, Program, TuringMachineError, MAX_PROGRAM_SIZE};
 use web_sys::HtmlTextAreaElement;
 use yew::prelude::*;
 
@@ -120,6 +120,15 @@ Please reduce the program size.",
                     ctx.props().on_error.emit("Program is empty.".to_string());
                 } else {
                     match parse(&self.program_text) {
+                        // Only the CLI follows every matching transition
+                        Ok(program) if program.mode == Mode::Nondeterministic => {
+                            let error_msg =
+                                "Nondeterministic programs can only be run with the CLI."
+                                    .to_string();
+                            self.parse_error = Some(error_msg.clone());
+                            self.is_valid = false;
+                            ctx.props().on_error.emit(error_msg);
+                        }
                         Ok(program) => {
                             self.parse_error = None;
                             self.is_valid = true;
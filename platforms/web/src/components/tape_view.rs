@@ -1,10 +1,10 @@
 use crate::components::MachineState;
+use tur::Tape;
 use yew::{function_component, html, Callback, Event, Html, Properties, TargetCast};
 
 #[derive(Properties, PartialEq)]
 pub struct TapeViewProps {
-    pub tapes: Vec<Vec<char>>,
-    pub head_positions: Vec<usize>,
+    pub tapes: Vec<Tape>,
     pub auto_play: bool,
     pub machine_state: MachineState,
     pub is_program_ready: bool,
@@ -13,18 +13,19 @@ pub struct TapeViewProps {
     pub step_count: usize,
     pub current_symbols: Vec<char>,
     pub on_step: Callback<()>,
+    pub on_step_back: Callback<()>,
+    pub can_step_back: bool,
     pub on_reset: Callback<()>,
     pub on_toggle_auto: Callback<()>,
     pub speed: u64,
     pub on_speed_change: Callback<u64>,
-    pub tape_left_offsets: Vec<usize>,
     pub message: String,
 }
 
 #[function_component(TapeView)]
 pub fn tape_view(props: &TapeViewProps) -> Html {
     let cell_width = 42; // Width of each cell (no gap)
-    let padding_cells = 15; // Number of blank cells to show on each side for infinite tape effect
+    let padding_cells: isize = 15; // Number of blank cells to show on each side for infinite tape effect
 
     let on_speed_change = props.on_speed_change.clone();
     let is_machine_running = props.machine_state == MachineState::Running;
@@ -34,6 +35,13 @@ pub fn tape_view(props: &TapeViewProps) -> Html {
             <div class="tape-header">
                 <h3 class="card-title">{"Tapes"}</h3>
                 <div class="tape-controls">
+                    <button
+                        class="btn btn-secondary"
+                        onclick={props.on_step_back.reform(|_| ())}
+                        disabled={!props.can_step_back || !props.is_program_ready}
+                    >
+                        {"Back"}
+                    </button>
                     <button
                         class="btn btn-primary"
                         onclick={props.on_step.reform(|_| ())}
@@ -72,22 +80,19 @@ pub fn tape_view(props: &TapeViewProps) -> Html {
             </div>
             <div class="tapes-container">
                 {props.tapes.iter().enumerate().map(|(tape_index, tape)| {
-                    let head_position = props.head_positions.get(tape_index).cloned().unwrap_or(0);
-                    let left_offset = *props.tape_left_offsets.get(tape_index).unwrap_or(&0);
-
-                    // Add padding cells on the left
-                    let mut visible_tape = vec![props.blank_symbol; padding_cells - left_offset];
-
-                    // Add the actual tape content
-                    for &symbol in tape {
-                        visible_tape.push(symbol);
-                    }
+                    let head_position = tape.head();
 
-                    // Add padding cells on the right
-                    visible_tape.extend(std::iter::repeat_n(props.blank_symbol, padding_cells));
+                    // Cells are laid out by absolute position, starting `padding_cells` left of
+                    // the origin (or of the leftmost visited cell). The first cell stays put while
+                    // the tape grows, so the head slides smoothly in both directions.
+                    let first_position = tape.start().min(-padding_cells);
+                    let last_position = tape.end().max(head_position + 1) + padding_cells;
+                    let visible_tape: Vec<(isize, char)> = (first_position..last_position)
+                        .map(|position| (position, tape.get(position)))
+                        .collect();
 
                     // Calculate the transform to center the active cell under the head pointer
-                    let active_cell_index = padding_cells + head_position - left_offset;
+                    let active_cell_index = head_position - first_position;
                     let cell_offset = active_cell_index as i32 * cell_width;
 
                     // js_sys::eval(&format!("console.log({active_cell_index}, {cell_offset}, {left_offset})")).unwrap();
@@ -108,9 +113,9 @@ pub fn tape_view(props: &TapeViewProps) -> Html {
                             }}
                             <div class="tape-machine">
                                 <div class="tape-container" style={transform_style}>
-                                    {visible_tape.iter().enumerate().map(|(i, &symbol)| {
+                                    {visible_tape.iter().map(|&(position, symbol)| {
                                         // Check if this cell is under the head
-                                        let is_under_head = i == active_cell_index;
+                                        let is_under_head = position == head_position;
 
                                         let class = if is_under_head {
                                             "tape-cell under-head"
@@ -119,8 +124,13 @@ pub fn tape_view(props: &TapeViewProps) -> Html {
                                         };
 
                                         html! {
-                                            <div key={format!("{tape_index}_{i}_{symbol}_{i}")} class={class}>
+                                            <div key={format!("{tape_index}_{position}_{symbol}")} class={class}>
                                                 {symbol}
+                                                {if position % 5 == 0 {
+                                                    html! { <span class="tape-cell-position">{position}</span> }
+                                                } else {
+                                                    html! {}
+                                                }}
                                             </div>
                                         }
                                     }).collect::<Html>()}
@@ -154,12 +164,13 @@ pub fn tape_view(props: &TapeViewProps) -> Html {
                 <div class="state-item">
                     <span class="label">{"Status"}</span>
                     <span class={match props.machine_state {
-                        MachineState::Halted => "value status halted",
+                        MachineState::Halted | MachineState::LimitReached => "value status halted",
                         MachineState::Running if props.auto_play => "value status running",
                         MachineState::Running => "value status ready",
                     }}>
                         {match props.machine_state {
                             MachineState::Halted => "HALTED",
+                            MachineState::LimitReached => "LIMIT REACHED",
                             MachineState::Running if props.auto_play => "RUNNING",
                             MachineState::Running => "READY",
                         }}
@@ -10,6 +10,8 @@ window.addEventListener("load", () => {
     activeNodeColor: "#fdcf2b",
     edgeColor: "#ccc",
     edgeHighlightColor: "#ffaf80",
+    incompleteNodeColor: "#f87272",
+    blankLoopNodeColor: "#f59e0b",
     nodeTextColor: "white",
     edgeTextBackgroundColor: "white",
   };
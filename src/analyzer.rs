@@ -1,9 +1,16 @@
 //! This module provides functions for analyzing Turing Machine programs to detect common errors
 //! and inconsistencies before execution. This includes checks for valid head positions, defined
 //! states, reachable states, and handled tape symbols.
-
-use crate::types::{Program, TuringMachineError};
+//!
+//! `analyze` stops at the first error, while `diagnose` returns every finding as a `Diagnostic`
+//! with a severity, a code, the states and symbols involved and where it is in the source.
+
+use crate::heads::head_analysis;
+use crate::syntax::Location;
+use crate::types::{Mode, Program, Transition, TuringMachineError, WILDCARD_SYMBOL};
+use serde::Serialize;
 use std::collections::HashSet;
+use std::fmt;
 
 /// Represents various errors that can be found during the analysis of a Turing Machine program.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -21,6 +28,16 @@ pub enum AnalysisError {
     UnreachableStates(Vec<String>),
     /// Indicates that the initial tape contains symbols for which no transitions are defined.
     InvalidTapeSymbols(Vec<char>),
+    /// Indicates transitions that can never be taken, because an earlier transition of the same
+    /// state matches every symbol they read.
+    ShadowedTransitions(Vec<String>),
+    /// Indicates transitions that match some of the same symbols as an earlier transition of
+    /// the same state, so the state isn't fully deterministic. Only checked on request.
+    OverlappingTransitions(Vec<String>),
+    /// Indicates states that loop forever once they read a blank past the written part of the
+    /// tapes, e.g. a right-sweep without a rule for the end of the input. Only checked on
+    /// request.
+    BlankLoops(Vec<String>),
     /// Indicates structural problems with the program (empty tapes, mismatched head positions, etc.).
     StructuralError(String),
 }
@@ -50,11 +67,220 @@ impl From<AnalysisError> for TuringMachineError {
                     symbols
                 ))
             }
+            AnalysisError::ShadowedTransitions(transitions) => {
+                TuringMachineError::ValidationError(format!(
+                    "Transitions shadowed by an earlier transition of the same state: {:?}",
+                    transitions
+                ))
+            }
+            AnalysisError::OverlappingTransitions(transitions) => {
+                TuringMachineError::ValidationError(format!(
+                    "Transitions overlapping an earlier transition of the same state: {:?}",
+                    transitions
+                ))
+            }
+            AnalysisError::BlankLoops(states) => TuringMachineError::ValidationError(format!(
+                "States looping forever on blank: {:?}",
+                states
+            )),
             AnalysisError::StructuralError(msg) => TuringMachineError::ValidationError(msg),
         }
     }
 }
 
+/// How serious a `Diagnostic` is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The program is rejected.
+    Error,
+    /// The program runs, but probably not as intended.
+    Warning,
+    /// The program runs, but could be written better.
+    Hint,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Hint => "hint",
+        })
+    }
+}
+
+/// The kind of finding a `Diagnostic` reports, one per check of the analyzer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiagnosticCode {
+    /// The tapes, heads or transitions are inconsistent, or a state is both accepting and
+    /// rejecting.
+    Structure,
+    /// A head starts outside of its tape.
+    InvalidHead,
+    /// The initial state has no rules.
+    InvalidStartState,
+    /// A stop state is never entered by any transition.
+    UnenteredStopState,
+    /// A transition goes to a state that isn't defined.
+    UndefinedNextState,
+    /// A state can't be reached from the initial state.
+    UnreachableState,
+    /// A transition can never be taken, because an earlier one matches every symbol it reads.
+    ShadowedTransition,
+    /// A transition matches some of the same symbols as an earlier one, which is taken for
+    /// them.
+    OverlappingTransition,
+    /// A state loops forever once it reads a blank past the written part of the tapes.
+    BlankLoop,
+    /// A symbol of an initial tape isn't read by any transition.
+    UnhandledTapeSymbol,
+}
+
+impl DiagnosticCode {
+    /// Returns the code as written in diagnostics, e.g. `unreachable-state`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DiagnosticCode::Structure => "structure",
+            DiagnosticCode::InvalidHead => "invalid-head",
+            DiagnosticCode::InvalidStartState => "invalid-start-state",
+            DiagnosticCode::UnenteredStopState => "unentered-stop-state",
+            DiagnosticCode::UndefinedNextState => "undefined-next-state",
+            DiagnosticCode::UnreachableState => "unreachable-state",
+            DiagnosticCode::ShadowedTransition => "shadowed-transition",
+            DiagnosticCode::OverlappingTransition => "overlapping-transition",
+            DiagnosticCode::BlankLoop => "blank-loop",
+            DiagnosticCode::UnhandledTapeSymbol => "unhandled-tape-symbol",
+        }
+    }
+}
+
+impl fmt::Display for DiagnosticCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A finding of the analyzer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Diagnostic {
+    /// How serious the finding is.
+    pub severity: Severity,
+    /// The kind of finding.
+    pub code: DiagnosticCode,
+    /// A description of the finding.
+    pub message: String,
+    /// The states involved, e.g. an unreachable state, or the state of a transition and the
+    /// undefined state it goes to.
+    pub states: Vec<String>,
+    /// The symbols involved, e.g. an unhandled tape symbol.
+    pub symbols: Vec<char>,
+    /// Where the finding is in the source, if the program was parsed from source: the state
+    /// or the action of the transition it's about.
+    pub location: Option<Location>,
+    /// Other places in the source involved in the finding, e.g. the earlier transition that
+    /// shadows a transition.
+    pub related: Vec<Location>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.code, self.message)?;
+        if let Some(location) = self.location {
+            write!(f, " at {}", location)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Every finding of analyzing a program, in the order the checks run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Diagnostics {
+    /// The findings.
+    pub items: Vec<Diagnostic>,
+}
+
+impl Diagnostics {
+    /// Returns the findings with the given severity.
+    pub fn with_severity(&self, severity: Severity) -> impl Iterator<Item = &Diagnostic> {
+        self.items
+            .iter()
+            .filter(move |diagnostic| diagnostic.severity == severity)
+    }
+
+    /// Checks if any finding is an error, which rejects the program.
+    pub fn has_errors(&self) -> bool {
+        self.with_severity(Severity::Error).next().is_some()
+    }
+
+    /// Checks if there are no findings.
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Returns an iterator over the findings.
+    pub fn iter(&self) -> std::slice::Iter<'_, Diagnostic> {
+        self.items.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Diagnostics {
+    type Item = &'a Diagnostic;
+    type IntoIter = std::slice::Iter<'a, Diagnostic>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.items.iter()
+    }
+}
+
+/// Options for analyzing a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AnalysisOptions {
+    /// The severity of unreachable states. They are errors by default; as warnings, programs
+    /// with states that aren't used yet still parse and run.
+    pub unreachable_states: Severity,
+    /// The severity of transitions that can never be taken, because an earlier transition of
+    /// the same state matches every symbol they read. They are warnings by default, since the
+    /// program still runs, taking the earlier transition.
+    pub shadowed_transitions: Severity,
+    /// The severity of transitions that overlap an earlier transition of the same state, or
+    /// `None` to not check them, which is the default. Overlaps are a common way to write
+    /// a fallback, e.g. a wildcard after literal symbols, so they are only checked on request,
+    /// to make sure every state's rules are fully deterministic.
+    pub overlapping_transitions: Option<Severity>,
+    /// The severity of states that loop forever once they read a blank, or `None` to not check
+    /// them, which is the default. The check runs the head analysis of `head_analysis`.
+    pub blank_loops: Option<Severity>,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        Self {
+            unreachable_states: Severity::Error,
+            shadowed_transitions: Severity::Warning,
+            overlapping_transitions: None,
+            blank_loops: None,
+        }
+    }
+}
+
+impl AnalysisOptions {
+    /// Returns the severity of an analysis error under these options.
+    fn severity(&self, error: &AnalysisError) -> Severity {
+        match error {
+            AnalysisError::UnreachableStates(_) => self.unreachable_states,
+            AnalysisError::ShadowedTransitions(_) => self.shadowed_transitions,
+            AnalysisError::OverlappingTransitions(_) => {
+                self.overlapping_transitions.unwrap_or(Severity::Error)
+            }
+            AnalysisError::BlankLoops(_) => self.blank_loops.unwrap_or(Severity::Error),
+            _ => Severity::Error,
+        }
+    }
+}
+
 /// Analyzes a given Turing Machine `Program` for structural and logical errors.
 ///
 /// This function orchestrates a comprehensive series of checks, performing both
@@ -70,25 +296,236 @@ impl From<AnalysisError> for TuringMachineError {
 /// * `Ok(())` if no errors are found.
 /// * `Err(TuringMachineError::ValidationError)` if any validation rule is violated.
 pub fn analyze(program: &Program) -> Result<(), TuringMachineError> {
-    let errors = [
+    analyze_with(program, &AnalysisOptions::default()).map(|_| ())
+}
+
+/// Runs every check of `analyze` on a `Program`, returning all the errors found rather than
+/// only the first.
+///
+/// The errors are in the order the checks run, with at most one error per check.
+pub fn analysis_errors(program: &Program) -> Vec<AnalysisError> {
+    [
         check_structure,
         check_head,
         check_valid_start_state,
+        check_valid_stop_states,
+        check_accept_reject_states,
+        check_undefined_next_states,
         check_unreachable_states,
+        check_shadowed_transitions,
         check_tape_symbols,
     ]
     .iter()
     .filter_map(|f| f(program).err())
-    .collect::<Vec<_>>();
+    .collect()
+}
+
+/// Analyzes a `Program` like `analyze`, with the severities of `options`.
+///
+/// # Returns
+///
+/// * `Ok(Diagnostics)` with every finding, none of them errors, if the program is accepted.
+/// * `Err(TuringMachineError::ValidationError)` for the first check that finds errors.
+pub fn analyze_with(
+    program: &Program,
+    options: &AnalysisOptions,
+) -> Result<Diagnostics, TuringMachineError> {
+    let errors = errors_with(program, options);
+
+    if let Some(error) = errors
+        .iter()
+        .find(|error| options.severity(error) == Severity::Error)
+    {
+        return Err(error.clone().into());
+    }
+
+    Ok(diagnostics(program, options, errors))
+}
+
+/// Analyzes a `Program`, returning every finding with its severity under `options`.
+///
+/// Unlike `analyze`, this doesn't stop at the first error, and reports each state, transition
+/// or symbol a check finds as a separate `Diagnostic`, located in the program's source map.
+pub fn diagnose(program: &Program, options: &AnalysisOptions) -> Diagnostics {
+    diagnostics(program, options, errors_with(program, options))
+}
+
+/// Returns the errors of `analysis_errors`, and of the checks `options` turn on.
+fn errors_with(program: &Program, options: &AnalysisOptions) -> Vec<AnalysisError> {
+    let mut errors = analysis_errors(program);
+
+    if options.overlapping_transitions.is_some() {
+        errors.extend(check_overlapping_transitions(program).err());
+    }
+    if options.blank_loops.is_some() {
+        errors.extend(check_blank_loops(program).err());
+    }
+
+    errors
+}
+
+/// Returns the diagnostics of the errors found by analyzing `program`.
+fn diagnostics(
+    program: &Program,
+    options: &AnalysisOptions,
+    errors: Vec<AnalysisError>,
+) -> Diagnostics {
+    let source_map = program.source_map.as_ref();
+    let state_location = |state: &str| source_map.and_then(|map| map.states.get(state).copied());
+    let transition_location =
+        |state: &str, i: usize| source_map.and_then(|map| map.transition(state, i));
+
+    let mut items = Vec::new();
+    for error in errors {
+        let severity = options.severity(&error);
+        let diagnostic =
+            |code, message: String, states: Vec<String>, symbols, location| Diagnostic {
+                severity,
+                code,
+                message,
+                states,
+                symbols,
+                location,
+                related: Vec::new(),
+            };
 
-    if !errors.is_empty() {
-        // Return the first error
-        if let Some(first_error) = errors.first() {
-            return Err((*first_error).clone().into());
+        match error {
+            AnalysisError::StructuralError(message) => {
+                items.push(diagnostic(
+                    DiagnosticCode::Structure,
+                    message,
+                    vec![],
+                    vec![],
+                    None,
+                ));
+            }
+            AnalysisError::InvalidHead(head) => items.push(diagnostic(
+                DiagnosticCode::InvalidHead,
+                format!("Head position {head} is outside of the tape"),
+                vec![],
+                vec![],
+                None,
+            )),
+            AnalysisError::InvalidStartState(state) => items.push(diagnostic(
+                DiagnosticCode::InvalidStartState,
+                format!("Start state {state} has no rules"),
+                vec![state],
+                vec![],
+                None,
+            )),
+            AnalysisError::StopStatesNotFound(states) => {
+                items.extend(states.into_iter().map(|state| {
+                    diagnostic(
+                        DiagnosticCode::UnenteredStopState,
+                        format!("Stop state {state} is never entered"),
+                        vec![state.clone()],
+                        vec![],
+                        state_location(&state),
+                    )
+                }));
+            }
+            AnalysisError::UndefinedNextStates(_) => {
+                items.extend(undefined_next_states(program).into_iter().map(
+                    |(state, i, transition)| {
+                        diagnostic(
+                            DiagnosticCode::UndefinedNextState,
+                            format!("Undefined next state {}", transition.next_state),
+                            vec![state.clone(), transition.next_state.clone()],
+                            transition.read.clone(),
+                            transition_location(state, i),
+                        )
+                    },
+                ));
+            }
+            AnalysisError::UnreachableStates(states) => {
+                items.extend(states.into_iter().map(|state| {
+                    diagnostic(
+                        DiagnosticCode::UnreachableState,
+                        format!("Unreachable state {state}"),
+                        vec![state.clone()],
+                        vec![],
+                        state_location(&state),
+                    )
+                }));
+            }
+            AnalysisError::ShadowedTransitions(_) => {
+                items.extend(
+                    shadowed_transitions(program)
+                        .into_iter()
+                        .map(|(state, j, i)| {
+                            let message = shadowed_message(program, state, j, i);
+                            Diagnostic {
+                                related: transition_location(state, i).into_iter().collect(),
+                                ..diagnostic(
+                                    DiagnosticCode::ShadowedTransition,
+                                    message,
+                                    vec![state.clone()],
+                                    program.rules[state][j].read.clone(),
+                                    transition_location(state, j),
+                                )
+                            }
+                        }),
+                );
+            }
+            AnalysisError::OverlappingTransitions(_) => {
+                items.extend(overlapping_transitions(program).into_iter().map(
+                    |(state, j, i, symbols)| Diagnostic {
+                        related: transition_location(state, i).into_iter().collect(),
+                        ..diagnostic(
+                            DiagnosticCode::OverlappingTransition,
+                            format!("Transition {state}[{j}] overlaps {state}[{i}]"),
+                            vec![state.clone()],
+                            symbols,
+                            transition_location(state, j),
+                        )
+                    },
+                ));
+            }
+            AnalysisError::BlankLoops(states) => {
+                items.extend(states.into_iter().map(|state| {
+                    diagnostic(
+                        DiagnosticCode::BlankLoop,
+                        format!("State {state} loops forever once it reads a blank"),
+                        vec![state.clone()],
+                        vec![program.blank],
+                        state_location(&state),
+                    )
+                }));
+            }
+            AnalysisError::InvalidTapeSymbols(symbols) => {
+                items.extend(symbols.into_iter().map(|symbol| {
+                    diagnostic(
+                        DiagnosticCode::UnhandledTapeSymbol,
+                        format!("Tape symbol '{symbol}' isn't read by any transition"),
+                        vec![],
+                        vec![symbol],
+                        None,
+                    )
+                }));
+            }
         }
     }
 
-    Ok(())
+    Diagnostics { items }
+}
+
+/// Returns the message of a transition shadowed by an earlier one, which tells duplicates apart
+/// from transitions shadowed by a wildcard.
+fn shadowed_message(program: &Program, state: &str, later: usize, earlier: usize) -> String {
+    let transitions = &program.rules[state];
+    let (earlier_read, later_read) = (&transitions[earlier].read, &transitions[later].read);
+
+    if earlier_read == later_read {
+        format!("Transition {state}[{later}] duplicates {state}[{earlier}]")
+    } else if !earlier_read.contains(&WILDCARD_SYMBOL) {
+        // Without wildcards, the reads only differ by `_` and the blank symbol
+        format!(
+            "Transition {state}[{later}] duplicates {state}[{earlier}]: `_` is the blank symbol '{}'",
+            program.blank
+        )
+    } else {
+        format!("Transition {state}[{later}] is shadowed by {state}[{earlier}]")
+    }
 }
 
 /// Checks basic structural requirements of the program.
@@ -187,11 +624,12 @@ fn check_valid_start_state(program: &Program) -> Result<(), AnalysisError> {
     Ok(())
 }
 
-/// Checks whether states that have no outgoing transitions (potential stop states)
-/// are referenced as `next_state` in any other transition.
+/// Checks whether stop states, both the ones declared in the `states:` section and the
+/// ones that have no outgoing transitions, are referenced as `next_state` in any other
+/// transition.
 ///
 /// This helps identify "dead-end" states that are not part of the machine's
-/// intended halting mechanism.
+/// intended halting mechanism. The initial state is always reached, so it is never flagged.
 ///
 /// # Arguments
 ///
@@ -201,14 +639,15 @@ fn check_valid_start_state(program: &Program) -> Result<(), AnalysisError> {
 ///
 /// * `Ok(())` if all stop states are properly referenced or if there are no unreferenced stop states.
 /// * `Err(AnalysisError::StopStatesNotFound)` if stop states are found that are not referenced.
-#[allow(dead_code)]
 fn check_valid_stop_states(program: &Program) -> Result<(), AnalysisError> {
-    // Collect all states that have no outgoing transitions (potential stop states)
+    // Collect all declared stop states and states that have no outgoing transitions
     let stop_states: HashSet<String> = program
         .rules
         .iter()
         .filter(|(_, transitions)| transitions.is_empty())
         .map(|(state, _)| state.clone())
+        .chain(program.declared_stop_states().cloned())
+        .filter(|state| *state != program.initial_state)
         .collect();
 
     // Collect all next states referenced in transitions
@@ -231,10 +670,36 @@ fn check_valid_stop_states(program: &Program) -> Result<(), AnalysisError> {
     Ok(())
 }
 
+/// Checks that no state is declared as both an accept and a reject state.
+///
+/// # Arguments
+///
+/// * `program` - A reference to the `Program` to check.
+///
+/// # Returns
+///
+/// * `Ok(())` if accept and reject states are disjoint.
+/// * `Err(AnalysisError::StructuralError)` if a state is declared as both.
+fn check_accept_reject_states(program: &Program) -> Result<(), AnalysisError> {
+    if let Some(state) = program
+        .accept_states
+        .iter()
+        .find(|state| program.is_reject_state(state))
+    {
+        return Err(AnalysisError::StructuralError(format!(
+            "State '{}' is declared as both accept and reject",
+            state
+        )));
+    }
+
+    Ok(())
+}
+
 /// Checks that all `next_state` references within transitions point to states that are
 /// actually defined as keys in the program's rules.
 ///
-/// The special "halt" state is implicitly defined and does not need to be present in the rules.
+/// The special "halt" state and any declared stop states are implicitly defined and do not
+/// need to be present in the rules.
 ///
 /// # Arguments
 ///
@@ -242,21 +707,13 @@ fn check_valid_stop_states(program: &Program) -> Result<(), AnalysisError> {
 ///
 /// # Returns
 ///
-/// * `Ok(())` if all next states are defined or are the "halt" state.
+/// * `Ok(())` if all next states are defined, declared as stop states, or are the "halt" state.
 /// * `Err(AnalysisError::UndefinedNextStates)` if transitions reference undefined states.
-#[allow(dead_code)]
 fn check_undefined_next_states(program: &Program) -> Result<(), AnalysisError> {
-    let defined_states: HashSet<String> = program.rules.keys().cloned().collect();
-
-    let mut undefined_transitions = Vec::new();
-    for (state, transitions) in &program.rules {
-        for (i, transition) in transitions.iter().enumerate() {
-            if !defined_states.contains(&transition.next_state) && transition.next_state != "halt" {
-                undefined_transitions
-                    .push(format!("{}[{}] -> {}", state, i, transition.next_state));
-            }
-        }
-    }
+    let undefined_transitions: Vec<String> = undefined_next_states(program)
+        .into_iter()
+        .map(|(state, i, transition)| format!("{}[{}] -> {}", state, i, transition.next_state))
+        .collect();
 
     if !undefined_transitions.is_empty() {
         return Err(AnalysisError::UndefinedNextStates(undefined_transitions));
@@ -265,6 +722,33 @@ fn check_undefined_next_states(program: &Program) -> Result<(), AnalysisError> {
     Ok(())
 }
 
+/// Returns the transitions whose next state isn't defined, with their state and index, sorted
+/// by state.
+fn undefined_next_states(program: &Program) -> Vec<(&String, usize, &Transition)> {
+    let defined_states: HashSet<&String> = program
+        .rules
+        .keys()
+        .chain(program.declared_stop_states())
+        .collect();
+
+    // Sort the states to make the output deterministic
+    let mut states: Vec<_> = program.rules.iter().collect();
+    states.sort_by_key(|(state, _)| *state);
+
+    states
+        .into_iter()
+        .flat_map(|(state, transitions)| {
+            transitions
+                .iter()
+                .enumerate()
+                .map(move |(i, transition)| (state, i, transition))
+        })
+        .filter(|(_, _, transition)| {
+            !defined_states.contains(&transition.next_state) && transition.next_state != "halt"
+        })
+        .collect()
+}
+
 /// Checks for unreachable states by performing a breadth-first search (BFS) traversal
 /// starting from the initial state.
 ///
@@ -312,6 +796,165 @@ fn check_unreachable_states(program: &Program) -> Result<(), AnalysisError> {
     Ok(())
 }
 
+/// Checks for transitions that are shadowed by an earlier transition of the same state, which
+/// makes them dead code since the first matching transition is always taken.
+///
+/// A transition is shadowed when an earlier one matches every symbol it reads, e.g. a literal
+/// read after a wildcard, or a second rule for the same symbols. `_` and the program's blank
+/// symbol are treated as the same symbol. Nondeterministic programs are exempt, since they
+/// follow every matching transition.
+///
+/// # Arguments
+///
+/// * `program` - A reference to the `Program` to check.
+///
+/// # Returns
+///
+/// * `Ok(())` if every transition can be taken.
+/// * `Err(AnalysisError::ShadowedTransitions)` if shadowed transitions are found.
+fn check_shadowed_transitions(program: &Program) -> Result<(), AnalysisError> {
+    let shadowed: Vec<String> = shadowed_transitions(program)
+        .into_iter()
+        .map(|(state, j, i)| format!("{}[{}] by {}[{}]", state, j, state, i))
+        .collect();
+
+    if !shadowed.is_empty() {
+        return Err(AnalysisError::ShadowedTransitions(shadowed));
+    }
+
+    Ok(())
+}
+
+/// Returns the shadowed transitions, as their state, their index and the index of the earlier
+/// transition that shadows them, sorted by state.
+fn shadowed_transitions(program: &Program) -> Vec<(&String, usize, usize)> {
+    if program.mode == Mode::Nondeterministic {
+        return Vec::new();
+    }
+
+    let mut shadowed = Vec::new();
+
+    // Sort the states to make the output deterministic
+    let mut states: Vec<_> = program.rules.iter().collect();
+    states.sort_by_key(|(state, _)| *state);
+
+    for (state, transitions) in states {
+        for (j, later) in transitions.iter().enumerate() {
+            if let Some(i) = transitions[..j]
+                .iter()
+                .position(|earlier| earlier.covers(later, program.blank))
+            {
+                shadowed.push((state, j, i));
+            }
+        }
+    }
+
+    shadowed
+}
+
+/// Checks for transitions that match some of the same symbols as an earlier transition of the
+/// same state, e.g. `[a, *]` after `[*, b]`, which both match `[a, b]`. The earlier transition
+/// is always taken for those symbols, so a state without overlaps is fully deterministic.
+///
+/// Shadowed transitions, which overlap an earlier transition for every symbol they read, are
+/// left to `check_shadowed_transitions`. Nondeterministic programs are exempt, since they
+/// follow every matching transition.
+///
+/// # Arguments
+///
+/// * `program` - A reference to the `Program` to check.
+///
+/// # Returns
+///
+/// * `Ok(())` if no transitions overlap.
+/// * `Err(AnalysisError::OverlappingTransitions)` if overlapping transitions are found.
+fn check_overlapping_transitions(program: &Program) -> Result<(), AnalysisError> {
+    let overlapping: Vec<String> = overlapping_transitions(program)
+        .into_iter()
+        .map(|(state, j, i, _)| format!("{}[{}] overlaps {}[{}]", state, j, state, i))
+        .collect();
+
+    if !overlapping.is_empty() {
+        return Err(AnalysisError::OverlappingTransitions(overlapping));
+    }
+
+    Ok(())
+}
+
+/// Checks for states that loop forever once they read a blank past the written part of the
+/// tapes, as found by `head_analysis`. Programs with structural errors are skipped, since the
+/// analysis needs every transition to have one symbol and direction per tape.
+///
+/// # Arguments
+///
+/// * `program` - A reference to the `Program` to check.
+///
+/// # Returns
+///
+/// * `Ok(())` if no state loops on blank.
+/// * `Err(AnalysisError::BlankLoops)` with the looping states, in the order they are reached.
+fn check_blank_loops(program: &Program) -> Result<(), AnalysisError> {
+    if check_structure(program).is_err() {
+        return Ok(());
+    }
+
+    let states: Vec<String> = head_analysis(program)
+        .blank_loops()
+        .map(|state| state.state.clone())
+        .collect();
+
+    if !states.is_empty() {
+        return Err(AnalysisError::BlankLoops(states));
+    }
+
+    Ok(())
+}
+
+/// Returns the transitions that overlap an earlier transition without being shadowed, as their
+/// state, their index, the index of the first earlier transition they overlap and symbols both
+/// match, where `*` is any symbol. Sorted by state.
+fn overlapping_transitions(program: &Program) -> Vec<(&String, usize, usize, Vec<char>)> {
+    if program.mode == Mode::Nondeterministic {
+        return Vec::new();
+    }
+
+    let mut overlapping = Vec::new();
+
+    // Sort the states to make the output deterministic
+    let mut states: Vec<_> = program.rules.iter().collect();
+    states.sort_by_key(|(state, _)| *state);
+
+    for (state, transitions) in states {
+        for (j, later) in transitions.iter().enumerate() {
+            let earlier = &transitions[..j];
+            if earlier.iter().any(|t| t.covers(later, program.blank)) {
+                continue;
+            }
+
+            if let Some(i) = earlier
+                .iter()
+                .position(|t| t.overlaps(later, program.blank))
+            {
+                let symbols = transitions[i]
+                    .read
+                    .iter()
+                    .zip(&later.read)
+                    .map(|(&earlier, &later)| {
+                        if earlier == WILDCARD_SYMBOL {
+                            later
+                        } else {
+                            earlier
+                        }
+                    })
+                    .collect();
+                overlapping.push((state, j, i, symbols));
+            }
+        }
+    }
+
+    overlapping
+}
+
 /// Checks that all symbols present in the initial tape(s) have corresponding transitions defined
 /// in the program's rules for the states they might be encountered in.
 ///
@@ -345,6 +988,10 @@ fn check_tape_symbols(program: &Program) -> Result<(), AnalysisError> {
             // For multi-tape, each symbol in the 'read' vector is a handled symbol.
             // For single-tape, transition.read will have length 1.
             for &symbol_in_read_vec in &transition.read {
+                // A wildcard handles every symbol
+                if symbol_in_read_vec == WILDCARD_SYMBOL {
+                    return Ok(());
+                }
                 handled_symbols.insert(symbol_in_read_vec);
             }
         }
@@ -368,7 +1015,7 @@ fn check_tape_symbols(program: &Program) -> Result<(), AnalysisError> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::types::{Direction, Mode, Transition};
+    use crate::types::{Direction, Transition, INPUT_BLANK_SYMBOL};
     use std::collections::HashMap;
 
     fn create_test_program(
@@ -380,10 +1027,14 @@ mod tests {
             name: "Test Program".to_string(),
             mode: Mode::Normal,
             initial_state: initial_state.to_string(),
+            stop_states: Vec::new(),
+            accept_states: Vec::new(),
+            reject_states: Vec::new(),
             tapes: vec![initial_tape.to_string()],
             heads: vec![0],
             blank: '-',
             rules,
+            source_map: None,
         }
     }
 
@@ -530,6 +1181,52 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[test]
+    fn test_undefined_next_states_with_declared_stop_state() {
+        let mut rules = HashMap::new();
+
+        rules.insert(
+            "start".to_string(),
+            vec![create_single_tape_transition(
+                'a',
+                'b',
+                Direction::Right,
+                "done",
+            )],
+        );
+
+        let mut program = create_test_program("start", "a", rules);
+        assert!(check_undefined_next_states(&program).is_err());
+
+        // Declared stop states don't need to be defined in the rules
+        program.stop_states = vec!["done".to_string()];
+        assert!(check_undefined_next_states(&program).is_ok());
+        assert!(analyze(&program).is_ok());
+    }
+
+    #[test]
+    fn test_declared_stop_states_not_found() {
+        let mut rules = HashMap::new();
+
+        rules.insert(
+            "start".to_string(),
+            vec![create_single_tape_transition(
+                'a',
+                'b',
+                Direction::Right,
+                "halt",
+            )],
+        );
+
+        let mut program = create_test_program("start", "a", rules);
+        program.stop_states = vec!["done".to_string()];
+
+        assert_eq!(
+            check_valid_stop_states(&program),
+            Err(AnalysisError::StopStatesNotFound(vec!["done".to_string()]))
+        );
+    }
+
     #[test]
     fn test_unreachable_states() {
         let mut rules = HashMap::new();
@@ -606,6 +1303,227 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_wildcard_handles_tape_symbols() {
+        let mut rules = HashMap::new();
+        rules.insert(
+            "start".to_string(),
+            vec![create_single_tape_transition(
+                WILDCARD_SYMBOL,
+                'b',
+                Direction::Right,
+                "halt",
+            )],
+        );
+
+        let program = create_test_program("start", "ac", rules);
+        assert!(check_tape_symbols(&program).is_ok());
+    }
+
+    #[test]
+    fn test_shadowed_transitions() {
+        let mut rules = HashMap::new();
+        rules.insert(
+            "start".to_string(),
+            vec![
+                create_single_tape_transition('a', 'b', Direction::Right, "start"),
+                // `_` is the blank symbol `-`, so the next rule is shadowed
+                create_single_tape_transition('_', 'b', Direction::Right, "start"),
+                create_single_tape_transition('-', 'b', Direction::Right, "start"),
+                create_single_tape_transition(WILDCARD_SYMBOL, 'b', Direction::Right, "start"),
+                create_single_tape_transition('c', 'b', Direction::Right, "start"),
+            ],
+        );
+
+        let mut program = create_test_program("start", "a", rules);
+        assert_eq!(
+            check_shadowed_transitions(&program),
+            Err(AnalysisError::ShadowedTransitions(vec![
+                "start[2] by start[1]".to_string(),
+                "start[4] by start[3]".to_string(),
+            ]))
+        );
+
+        // Shadowed transitions are warnings by default, the earlier transition is taken
+        let diagnostics = analyze_with(&program, &AnalysisOptions::default()).unwrap();
+        assert_eq!(diagnostics.items.len(), 2);
+        assert!(diagnostics
+            .items
+            .iter()
+            .all(|d| d.severity == Severity::Warning));
+
+        let options = AnalysisOptions {
+            shadowed_transitions: Severity::Error,
+            ..AnalysisOptions::default()
+        };
+        assert!(analyze_with(&program, &options).is_err());
+
+        // Nondeterministic programs follow every matching transition
+        program.mode = Mode::Nondeterministic;
+        assert!(check_shadowed_transitions(&program).is_ok());
+    }
+
+    #[test]
+    fn test_shadowed_transition_diagnostics() {
+        let program = crate::parser::parse_unchecked(
+            r#"
+name: Shadowed
+blank: x
+tape: a
+rules:
+  start:
+    a, R, start
+    a, R, start
+    _, R, start
+    x, R, start
+    *, R, halt
+    c, R, halt
+"#,
+        )
+        .unwrap();
+
+        let diagnostics = diagnose(&program, &AnalysisOptions::default());
+        let shadowed: Vec<(&str, Option<usize>, Vec<usize>)> = diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.code == DiagnosticCode::ShadowedTransition)
+            .map(|diagnostic| {
+                (
+                    diagnostic.message.as_str(),
+                    diagnostic.location.map(|location| location.line),
+                    diagnostic
+                        .related
+                        .iter()
+                        .map(|location| location.line)
+                        .collect(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            shadowed,
+            vec![
+                ("Transition start[1] duplicates start[0]", Some(8), vec![7]),
+                (
+                    "Transition start[3] duplicates start[2]: `_` is the blank symbol 'x'",
+                    Some(10),
+                    vec![9]
+                ),
+                (
+                    "Transition start[5] is shadowed by start[4]",
+                    Some(12),
+                    vec![11]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_overlapping_transitions() {
+        let mut rules = HashMap::new();
+        rules.insert(
+            "start".to_string(),
+            vec![
+                Transition {
+                    read: vec!['a', WILDCARD_SYMBOL],
+                    write: vec!['a', 'b'],
+                    directions: vec![Direction::Right, Direction::Right],
+                    next_state: "start".to_string(),
+                },
+                Transition {
+                    read: vec![WILDCARD_SYMBOL, 'b'],
+                    write: vec!['a', 'b'],
+                    directions: vec![Direction::Right, Direction::Right],
+                    next_state: "halt".to_string(),
+                },
+                // Disjoint from both
+                Transition {
+                    read: vec!['b', 'a'],
+                    write: vec!['a', 'b'],
+                    directions: vec![Direction::Right, Direction::Right],
+                    next_state: "halt".to_string(),
+                },
+            ],
+        );
+
+        let mut program = create_test_program("start", "a", rules);
+        program.tapes.push("b".to_string());
+        program.heads.push(0);
+
+        assert_eq!(
+            check_overlapping_transitions(&program),
+            Err(AnalysisError::OverlappingTransitions(vec![
+                "start[1] overlaps start[0]".to_string()
+            ]))
+        );
+
+        // Overlaps are only checked on request
+        assert!(analyze(&program).is_ok());
+        assert!(diagnose(&program, &AnalysisOptions::default()).is_empty());
+
+        let options = AnalysisOptions {
+            overlapping_transitions: Some(Severity::Warning),
+            ..AnalysisOptions::default()
+        };
+        let diagnostics = analyze_with(&program, &options).unwrap();
+        assert_eq!(diagnostics.items.len(), 1);
+        assert_eq!(
+            diagnostics.items[0].code,
+            DiagnosticCode::OverlappingTransition
+        );
+        assert_eq!(diagnostics.items[0].symbols, vec!['a', 'b']);
+
+        let options = AnalysisOptions {
+            overlapping_transitions: Some(Severity::Error),
+            ..AnalysisOptions::default()
+        };
+        assert!(analyze_with(&program, &options).is_err());
+
+        // Nondeterministic programs follow every matching transition
+        program.mode = Mode::Nondeterministic;
+        assert!(check_overlapping_transitions(&program).is_ok());
+    }
+
+    #[test]
+    fn test_blank_loops() {
+        let mut rules = HashMap::new();
+        rules.insert(
+            "start".to_string(),
+            vec![
+                Transition {
+                    read: vec!['a'],
+                    write: vec!['a'],
+                    directions: vec![Direction::Right],
+                    next_state: "start".to_string(),
+                },
+                // Sweeps right forever, since it has no rule to stop at the end of the input
+                Transition {
+                    read: vec![INPUT_BLANK_SYMBOL],
+                    write: vec!['b'],
+                    directions: vec![Direction::Right],
+                    next_state: "start".to_string(),
+                },
+            ],
+        );
+
+        let program = create_test_program("start", "aa", rules);
+        assert_eq!(
+            check_blank_loops(&program),
+            Err(AnalysisError::BlankLoops(vec!["start".to_string()]))
+        );
+
+        // Blank loops are only checked on request
+        assert!(analyze(&program).is_ok());
+
+        let options = AnalysisOptions {
+            blank_loops: Some(Severity::Warning),
+            ..AnalysisOptions::default()
+        };
+        let diagnostics = analyze_with(&program, &options).unwrap();
+        assert_eq!(diagnostics.items.len(), 1);
+        assert_eq!(diagnostics.items[0].code, DiagnosticCode::BlankLoop);
+        assert_eq!(diagnostics.items[0].states, vec!["start"]);
+    }
+
     #[test]
     fn test_multi_tape_symbols() {
         let mut rules = HashMap::new();
@@ -627,10 +1545,14 @@ mod tests {
             name: "Valid Multi-Tape".to_string(),
             mode: Mode::Normal,
             initial_state: "start".to_string(),
+            stop_states: Vec::new(),
+            accept_states: Vec::new(),
+            reject_states: Vec::new(),
             tapes: vec!["a".to_string(), "x".to_string()],
             heads: vec![0, 0],
             blank: '-',
             rules: rules.clone(),
+            source_map: None,
         };
         assert!(check_tape_symbols(&program_valid).is_ok());
 
@@ -639,10 +1561,14 @@ mod tests {
             name: "Invalid Multi-Tape".to_string(),
             mode: Mode::Normal,
             initial_state: "start".to_string(),
+            stop_states: Vec::new(),
+            accept_states: Vec::new(),
+            reject_states: Vec::new(),
             tapes: vec!["a".to_string(), "z".to_string()],
             heads: vec![0, 0],
             blank: '-',
             rules,
+            source_map: None,
         };
         let result = check_tape_symbols(&program_invalid);
         assert!(result.is_err());
@@ -801,4 +1727,126 @@ mod tests {
             panic!("Expected ValidationError");
         }
     }
+
+    #[test]
+    fn test_analysis_errors() {
+        let mut rules = HashMap::new();
+        rules.insert(
+            "start".to_string(),
+            vec![create_single_tape_transition(
+                'a',
+                'b',
+                Direction::Right,
+                "missing",
+            )],
+        );
+        rules.insert(
+            "other".to_string(),
+            vec![create_single_tape_transition(
+                'a',
+                'a',
+                Direction::Right,
+                "halt",
+            )],
+        );
+
+        let program = create_test_program("start", "a", rules);
+
+        assert_eq!(
+            analysis_errors(&program),
+            vec![
+                AnalysisError::UndefinedNextStates(vec!["start[0] -> missing".to_string()]),
+                AnalysisError::UnreachableStates(vec!["other".to_string()]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diagnose() {
+        let program = crate::parser::parse_unchecked(
+            r#"
+name: Diagnostics
+tape: a, c
+rules:
+  start:
+    a, R, start
+    a, R, missing
+  orphan:
+    a, R, halt
+"#,
+        )
+        .unwrap();
+
+        let diagnostics = diagnose(&program, &AnalysisOptions::default());
+        let codes: Vec<(Severity, DiagnosticCode)> = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.severity, diagnostic.code))
+            .collect();
+        assert_eq!(
+            codes,
+            vec![
+                (Severity::Error, DiagnosticCode::UndefinedNextState),
+                (Severity::Error, DiagnosticCode::UnreachableState),
+                (Severity::Warning, DiagnosticCode::ShadowedTransition),
+                (Severity::Error, DiagnosticCode::UnhandledTapeSymbol),
+            ]
+        );
+
+        let undefined = &diagnostics.items[0];
+        assert_eq!(undefined.states, vec!["start", "missing"]);
+        assert_eq!(undefined.symbols, vec!['a']);
+        assert_eq!(
+            undefined.to_string(),
+            "error[undefined-next-state]: Undefined next state missing at line 7, column 5"
+        );
+        assert_eq!(diagnostics.items[3].symbols, vec!['c']);
+
+        // Unreachable states can be warnings
+        let options = AnalysisOptions {
+            unreachable_states: Severity::Warning,
+            ..AnalysisOptions::default()
+        };
+        let diagnostics = diagnose(&program, &options);
+        let warnings: Vec<&Diagnostic> = diagnostics.with_severity(Severity::Warning).collect();
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].states, vec!["orphan"]);
+        assert_eq!(warnings[0].location.map(|l| l.line), Some(8));
+        assert!(diagnostics.has_errors());
+    }
+
+    #[test]
+    fn test_analyze_with_warnings() {
+        let mut rules = HashMap::new();
+        rules.insert(
+            "start".to_string(),
+            vec![create_single_tape_transition(
+                'a',
+                'a',
+                Direction::Right,
+                "halt",
+            )],
+        );
+        rules.insert(
+            "later".to_string(),
+            vec![create_single_tape_transition(
+                'a',
+                'a',
+                Direction::Right,
+                "halt",
+            )],
+        );
+
+        let program = create_test_program("start", "a", rules);
+        assert!(analyze(&program).is_err());
+
+        let options = AnalysisOptions {
+            unreachable_states: Severity::Warning,
+            ..AnalysisOptions::default()
+        };
+        let diagnostics = analyze_with(&program, &options).unwrap();
+        assert!(!diagnostics.has_errors());
+        assert_eq!(diagnostics.items.len(), 1);
+        assert_eq!(diagnostics.items[0].code, DiagnosticCode::UnreachableState);
+        assert_eq!(diagnostics.items[0].location, None);
+    }
 }
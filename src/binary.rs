@@ -0,0 +1,470 @@
+//! This module reduces single-tape Turing Machine programs over any alphabet to equivalent
+//! programs over the binary alphabet `{0, 1}` and the blank symbol.
+//!
+//! Each symbol of the original tape is stored as a fixed-width block of bits, and the blank
+//! symbol as a block of blanks, so that the unvisited parts of the tape decode to blanks. Every
+//! step of the original program is simulated by reading a block from left to right, then
+//! writing the new block from right to left and moving the head to the start of the next block.
+
+use crate::compile::{build_rules, finish_program, halts_in, tape_symbol, StateBuilder};
+use crate::tape::Tape;
+use crate::types::{resolve, Direction, Mode, Program, TuringMachineError, WILDCARD_SYMBOL};
+use std::collections::HashSet;
+
+/// A binary program reduced from a program over a larger alphabet, with the helpers to convert
+/// tapes between the two.
+#[derive(Debug, Clone)]
+pub struct BinaryProgram {
+    /// The reduced binary program.
+    pub program: Program,
+    /// The non-blank symbols of the original program, in sorted order. Each is encoded as the
+    /// binary representation of its index.
+    symbols: Vec<char>,
+    width: usize,
+    blank: char,
+}
+
+impl BinaryProgram {
+    /// Returns the number of cells used for each symbol of the original program.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// Encodes the content of a tape of the original program into blocks of bits.
+    ///
+    /// Returns an error if a symbol isn't used by the original program.
+    pub fn encode_tape(&self, tape: &str) -> Result<String, TuringMachineError> {
+        tape.chars()
+            .map(|symbol| {
+                let symbol = resolve(symbol, self.blank);
+                self.encode(symbol).ok_or_else(|| {
+                    TuringMachineError::ValidationError(format!(
+                        "Symbol '{symbol}' isn't used by the program"
+                    ))
+                })
+            })
+            .collect()
+    }
+
+    /// Decodes a tape of the binary program into a tape of the original program, with its
+    /// symbols and head at the same positions as on the original tape.
+    pub fn decode_tape(&self, tape: &Tape) -> Tape {
+        let width = self.width as isize;
+        let start = tape.start().div_euclid(width);
+        let end = (tape.end() - 1).div_euclid(width);
+
+        let mut decoded = Tape::new(Vec::new(), start, self.blank);
+        for block in start..=end {
+            let bits: String = (0..width)
+                .map(|offset| tape.get(block * width + offset))
+                .collect();
+            decoded.write(self.decode(&bits));
+            decoded.move_head(Direction::Right);
+        }
+
+        while decoded.head() > tape.head().div_euclid(width) {
+            decoded.move_head(Direction::Left);
+        }
+        decoded
+    }
+
+    /// Returns the block of cells for a symbol, or `None` if it isn't used by the program.
+    fn encode(&self, symbol: char) -> Option<String> {
+        if symbol == self.blank {
+            return Some(self.blank.to_string().repeat(self.width));
+        }
+
+        let i = self.symbols.binary_search(&symbol).ok()?;
+        Some(format!("{i:0width$b}", width = self.width))
+    }
+
+    /// Returns the symbol for a block of cells. Blocks that don't hold a symbol decode to blank.
+    fn decode(&self, bits: &str) -> char {
+        usize::from_str_radix(bits, 2)
+            .ok()
+            .and_then(|i| self.symbols.get(i))
+            .copied()
+            .unwrap_or(self.blank)
+    }
+}
+
+/// A state of the binary program.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Node {
+    /// Reading the block under the head in `state`, having read the cells in `read`.
+    Read { state: String, read: String },
+    /// Writing `block` from right to left, at `offset` in the block, then moving the head.
+    Write {
+        state: String,
+        block: String,
+        direction: Direction,
+        offset: usize,
+    },
+    /// Moving the head `cells` more cells to the start of the next block.
+    Skip {
+        state: String,
+        direction: Direction,
+        cells: usize,
+    },
+    /// A state the original program halts in.
+    Halt(String),
+}
+
+/// Reduces a deterministic single-tape program to a program over `0`, `1` and the blank symbol
+/// that computes the same result.
+///
+/// The binary program starts on the original tape encoded by [`BinaryProgram::encode_tape`],
+/// and halts when the original program does, with the tape decoded by
+/// [`BinaryProgram::decode_tape`] holding the same symbols. It halts in the same state when the
+/// original program enters a state without transitions or a stop, accept or reject state; when
+/// no transition matches, it halts in the state that read the block.
+///
+/// # Errors
+///
+/// Returns `TuringMachineError::ValidationError` for multi-tape or nondeterministic programs,
+/// and programs whose blank symbol is `0` or `1`.
+pub fn to_binary(program: &Program) -> Result<BinaryProgram, TuringMachineError> {
+    if !program.is_single_tape() {
+        return Err(TuringMachineError::ValidationError(
+            "Only single-tape programs can be reduced to binary".to_string(),
+        ));
+    }
+    if program.mode == Mode::Nondeterministic {
+        return Err(TuringMachineError::ValidationError(
+            "Nondeterministic programs can't be reduced to binary".to_string(),
+        ));
+    }
+    if program.blank == '0' || program.blank == '1' {
+        return Err(TuringMachineError::ValidationError(format!(
+            "The blank symbol '{}' is a binary digit",
+            program.blank
+        )));
+    }
+
+    // Every non-blank symbol that can be on the tape: the input and anything read or written
+    let symbols: HashSet<char> = program
+        .initial_tape()
+        .chars()
+        .chain(program.rules.values().flatten().flat_map(|transition| {
+            transition
+                .read
+                .iter()
+                .chain(&transition.write)
+                .map(|&symbol| resolve(symbol, program.blank))
+        }))
+        .filter(|&symbol| symbol != WILDCARD_SYMBOL && symbol != program.blank)
+        .collect();
+    let mut symbols: Vec<char> = symbols.into_iter().collect();
+    symbols.sort();
+
+    // Enough bits to number every symbol, and at least one
+    let width = (usize::BITS - symbols.len().saturating_sub(1).leading_zeros()).max(1) as usize;
+
+    let mut binary = BinaryProgram {
+        program: program.clone(),
+        symbols,
+        width,
+        blank: program.blank,
+    };
+    let tape = binary.encode_tape(&program.initial_tape())?;
+
+    let reducer = Reducer {
+        program,
+        binary: &binary,
+    };
+    let initial = reducer.start(&program.initial_state);
+    let rules = build_rules(&reducer, &initial, program.blank);
+
+    binary.program = finish_program(Program {
+        name: format!("{} (binary)", program.name),
+        mode: program.mode.clone(),
+        initial_state: reducer.name(&initial),
+        stop_states: program.stop_states.clone(),
+        accept_states: program.accept_states.clone(),
+        reject_states: program.reject_states.clone(),
+        tapes: vec![tape],
+        heads: vec![program.head_position() * width],
+        blank: program.blank,
+        rules,
+        source_map: None,
+    })?;
+
+    Ok(binary)
+}
+
+/// Builds the states of a binary program.
+struct Reducer<'a> {
+    program: &'a Program,
+    binary: &'a BinaryProgram,
+}
+
+impl StateBuilder for Reducer<'_> {
+    type Node = Node;
+
+    fn transitions(&self, node: &Node) -> Vec<(char, char, Direction, Node)> {
+        let width = self.binary.width;
+        let blank = self.program.blank;
+
+        match node {
+            Node::Read { state, read } => {
+                // A block holds either bits or blanks
+                let cells = match read.chars().next() {
+                    None => vec!['0', '1', blank],
+                    Some(cell) if cell == blank => vec![blank],
+                    Some(_) => vec!['0', '1'],
+                };
+
+                cells
+                    .into_iter()
+                    .filter_map(|cell| {
+                        let read = format!("{read}{cell}");
+                        if read.chars().count() < width {
+                            let next = Node::Read {
+                                state: state.clone(),
+                                read,
+                            };
+                            return Some((cell, cell, Direction::Right, next));
+                        }
+
+                        // The whole block is read, so start writing from its last cell
+                        let (block, direction, next_state) = self.apply(state, &read)?;
+                        let node = Node::Write {
+                            state: next_state,
+                            block,
+                            direction,
+                            offset: width - 1,
+                        };
+                        let (write, direction, next) = self.write(&node);
+                        Some((cell, write, direction, next))
+                    })
+                    .collect()
+            }
+            Node::Write { .. } => {
+                let (write, direction, next) = self.write(node);
+                vec![(WILDCARD_SYMBOL, write, direction, next)]
+            }
+            Node::Skip {
+                state,
+                direction,
+                cells,
+            } => {
+                let next = self.skip(state, *direction, cells - 1);
+                vec![(WILDCARD_SYMBOL, WILDCARD_SYMBOL, *direction, next)]
+            }
+            Node::Halt(_) => Vec::new(),
+        }
+    }
+
+    /// Returns the name of a state, e.g. `start:read:01` for the state reading the block of
+    /// state `start` whose first two cells are `0` and `1`.
+    fn name(&self, node: &Node) -> String {
+        let blank = self.program.blank;
+        let cells =
+            |cells: &str| -> String { cells.chars().map(|c| tape_symbol(c, blank)).collect() };
+        let direction = |direction: &Direction| match direction {
+            Direction::Left => "L",
+            Direction::Right => "R",
+            Direction::Stay => "S",
+        };
+
+        match node {
+            Node::Read { state, read } => format!("{state}:read:{}", cells(read)),
+            Node::Write {
+                state,
+                block,
+                direction: d,
+                offset,
+            } => format!("{state}:write:{}:{}:{offset}", cells(block), direction(d)),
+            Node::Skip {
+                state,
+                direction: d,
+                cells,
+            } => format!("{state}:skip:{}:{cells}", direction(d)),
+            Node::Halt(state) => state.clone(),
+        }
+    }
+}
+
+impl Reducer<'_> {
+    /// Returns the block to write, the head direction and the next state of the transition of
+    /// `state` matching the symbol in the block `read`, if any.
+    fn apply(&self, state: &str, read: &str) -> Option<(String, Direction, String)> {
+        let symbol = self.binary.decode(read);
+        let transition = self.program.rules[state]
+            .iter()
+            .find(|transition| transition.matches(&[symbol], self.program.blank))?;
+
+        let write = match resolve(transition.write[0], self.program.blank) {
+            WILDCARD_SYMBOL => symbol,
+            write => write,
+        };
+        let block = self.binary.encode(write).unwrap();
+
+        Some((
+            block,
+            transition.directions[0],
+            transition.next_state.clone(),
+        ))
+    }
+
+    /// Returns the cell to write for a `Node::Write`, the direction and the next state.
+    fn write(&self, node: &Node) -> (char, Direction, Node) {
+        let Node::Write {
+            state,
+            block,
+            direction,
+            offset,
+        } = node
+        else {
+            unreachable!("only write nodes write blocks");
+        };
+        let cell = block.chars().nth(*offset).unwrap();
+
+        if *offset > 0 {
+            let next = Node::Write {
+                state: state.clone(),
+                block: block.clone(),
+                direction: *direction,
+                offset: offset - 1,
+            };
+            return (cell, Direction::Left, next);
+        }
+
+        // At the start of the block, so move to the start of the next one
+        match direction {
+            Direction::Stay => (cell, Direction::Stay, self.start(state)),
+            _ => (
+                cell,
+                *direction,
+                self.skip(state, *direction, self.binary.width - 1),
+            ),
+        }
+    }
+
+    /// Returns the state moving the head `cells` more cells in `direction`, then starting the
+    /// next step in `state`.
+    fn skip(&self, state: &str, direction: Direction, cells: usize) -> Node {
+        if cells == 0 {
+            return self.start(state);
+        }
+
+        Node::Skip {
+            state: state.to_string(),
+            direction,
+            cells,
+        }
+    }
+
+    /// Returns the state that starts the next step in `state`.
+    fn start(&self, state: &str) -> Node {
+        if halts_in(self.program, state) {
+            Node::Halt(state.to_string())
+        } else {
+            Node::Read {
+                state: state.to_string(),
+                read: String::new(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    /// Runs a program and its binary program on the same input, and checks that they halt with
+    /// the same tape, and in the same state if it's one the program halts in.
+    fn assert_equivalent(program: &Program, input: &str) {
+        let binary = to_binary(program).unwrap();
+        crate::compile::tests::assert_equivalent(
+            program,
+            &[input],
+            &binary.program,
+            binary.encode_tape(input).unwrap(),
+            |tape| vec![binary.decode_tape(tape)],
+        );
+    }
+
+    #[test]
+    fn test_palindrome() {
+        let program = parse(include_str!("../examples/palindrome.tur")).unwrap();
+        let binary = to_binary(&program).unwrap();
+
+        // `X`, `Y`, `a` and `b` take two bits each
+        assert_eq!(binary.width(), 2);
+        assert_eq!(binary.encode_tape("ab_X").unwrap(), "1011  00");
+        assert!(binary
+            .program
+            .rules
+            .values()
+            .flatten()
+            .flat_map(|transition| transition.read.iter().chain(&transition.write))
+            .all(|symbol| ['0', '1', '_', '*'].contains(symbol)));
+
+        assert_equivalent(&program, "abba");
+        assert_equivalent(&program, "abab");
+        assert_equivalent(&program, "");
+    }
+
+    #[test]
+    fn test_moving_left_and_staying() {
+        let program = parse(include_str!("../examples/busy-beaver-3.tur")).unwrap();
+        assert_equivalent(&program, "");
+
+        let program = parse(
+            r#"
+name: Rewind
+head: 2
+tape: a, b, c
+rules:
+  start:
+    c -> C, L, start
+    b -> B, S, back
+  back:
+    B, L, back
+    * -> *, S, halt
+"#,
+        )
+        .unwrap();
+        assert_equivalent(&program, "abc");
+        assert_equivalent(&program, "bbc");
+    }
+
+    #[test]
+    fn test_undefined_transition_halts() {
+        let program = parse(include_str!("../examples/even-zeros-and-ones.tur")).unwrap();
+
+        assert_equivalent(&program, "0011");
+        assert_equivalent(&program, "0111");
+    }
+
+    #[test]
+    fn test_decode_tape() {
+        let program = parse(include_str!("../examples/palindrome.tur")).unwrap();
+        let binary = to_binary(&program).unwrap();
+
+        let tape = Tape::new(
+            binary.encode_tape("abXY").unwrap().chars(),
+            5,
+            program.blank,
+        );
+        let decoded = binary.decode_tape(&tape);
+        assert_eq!(decoded.trimmed(), (0, "abXY".chars().collect()));
+        assert_eq!(decoded.head(), 2);
+
+        let tape = Tape::new(binary.encode_tape("ab").unwrap().chars(), -1, program.blank);
+        assert_eq!(binary.decode_tape(&tape).head(), -1);
+        assert!(binary.encode_tape("c").is_err());
+    }
+
+    #[test]
+    fn test_unsupported_programs() {
+        let program = parse(include_str!("../examples/multi-tape-copy.tur")).unwrap();
+        assert!(to_binary(&program).is_err());
+
+        let mut program = parse(include_str!("../examples/busy-beaver-3.tur")).unwrap();
+        program.blank = '0';
+        assert!(to_binary(&program).is_err());
+    }
+}
@@ -0,0 +1,516 @@
+//! This module enumerates busy beaver candidates: every `n`-state, `k`-symbol Turing Machine in
+//! tree normal form, run from a blank tape to find the champions that run the longest and write
+//! the most non-blank symbols before halting.
+//!
+//! Tree normal form builds machines lazily. Every machine starts with no transitions and is
+//! run until it reads a state and symbol without one. That undefined transition is then either
+//! made the halting transition, which completes a halting machine, or defined in every possible
+//! way, each giving a child machine that is run in turn. States are numbered in order of first
+//! use and the first move is always to the right, so machines that only differ by renaming
+//! states or mirroring the tape are enumerated once.
+
+use crate::deciders::{decide, Decision};
+use crate::machine::TuringMachine;
+use crate::types::{
+    Direction, Halt, Mode, Program, Step, Transition, TuringMachineError, DEFAULT_BLANK_SYMBOL,
+    INPUT_BLANK_SYMBOL,
+};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::thread;
+
+/// The default maximum number of steps each candidate machine is run for.
+pub const MAX_BUSY_BEAVER_STEPS: usize = 1000;
+/// The name of the state entered by the halting transition.
+const HALT_STATE: &str = "halt";
+/// The maximum number of states, named `A` to `Z`.
+const MAX_STATES: usize = 26;
+/// The maximum number of symbols, written `_` and `1` to `9`.
+const MAX_SYMBOLS: usize = 10;
+
+/// A transition in a candidate machine's table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Entry {
+    /// Writes a symbol, moves and enters a state, all by index.
+    Move {
+        write: usize,
+        direction: Direction,
+        next: usize,
+    },
+    /// Writes `1`, moves right and halts.
+    Halt,
+}
+
+/// A candidate machine's transition table, indexed by state and then symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Table {
+    symbols: usize,
+    entries: Vec<Option<Entry>>,
+}
+
+impl Table {
+    fn new(states: usize, symbols: usize) -> Self {
+        Self {
+            symbols,
+            entries: vec![None; states * symbols],
+        }
+    }
+
+    /// Returns the number of states used so far, i.e. one past the highest state entered.
+    fn used_states(&self) -> usize {
+        self.entries
+            .iter()
+            .flatten()
+            .map(|entry| match entry {
+                Entry::Move { next, .. } => next + 1,
+                Entry::Halt => 1,
+            })
+            .fold(1, usize::max)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.entries.iter().all(Option::is_none)
+    }
+
+    /// Returns the number of transitions left undefined.
+    fn undefined(&self) -> usize {
+        self.entries.iter().filter(|entry| entry.is_none()).count()
+    }
+
+    /// Returns a copy of the table with the given transition defined.
+    fn with(&self, state: usize, symbol: usize, entry: Entry) -> Self {
+        let mut table = self.clone();
+        table.entries[state * self.symbols + symbol] = Some(entry);
+        table
+    }
+
+    /// Builds a runnable program from the table. Undefined transitions halt the machine.
+    fn to_program(&self) -> Program {
+        let mut rules: HashMap<String, Vec<Transition>> = HashMap::new();
+
+        for (i, entry) in self.entries.iter().enumerate() {
+            let Some(entry) = entry else { continue };
+            let (write, direction, next_state) = match *entry {
+                Entry::Move {
+                    write,
+                    direction,
+                    next,
+                } => (write, direction, state_name(next)),
+                Entry::Halt => (1, Direction::Right, HALT_STATE.to_string()),
+            };
+
+            rules
+                .entry(state_name(i / self.symbols))
+                .or_default()
+                .push(Transition {
+                    read: vec![symbol_char(i % self.symbols)],
+                    write: vec![symbol_char(write)],
+                    directions: vec![direction],
+                    next_state,
+                });
+        }
+
+        Program {
+            name: self.to_string(),
+            mode: Mode::Normal,
+            initial_state: state_name(0),
+            stop_states: Vec::new(),
+            accept_states: Vec::new(),
+            reject_states: Vec::new(),
+            tapes: vec![DEFAULT_BLANK_SYMBOL.to_string()],
+            heads: vec![0],
+            blank: DEFAULT_BLANK_SYMBOL,
+            rules,
+            source_map: None,
+        }
+    }
+}
+
+/// Formats the table in the standard compact notation, e.g. `1RB1LB_1LA1RZ`: one group per
+/// state, each listing the symbol written, the move and the next state for every symbol read.
+/// Symbols are written as digits, with `0` for blank; `Z` is the halting state and `---` an
+/// undefined transition.
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, entry) in self.entries.iter().enumerate() {
+            if i > 0 && i % self.symbols == 0 {
+                write!(f, "_")?;
+            }
+
+            match entry {
+                Some(Entry::Move {
+                    write,
+                    direction,
+                    next,
+                }) => {
+                    let direction = if *direction == Direction::Left {
+                        'L'
+                    } else {
+                        'R'
+                    };
+                    write!(f, "{write}{direction}{}", state_name(*next))?;
+                }
+                Some(Entry::Halt) => write!(f, "1RZ")?,
+                None => write!(f, "---")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the name of the state with the given index: `A`, `B`, `C`, ...
+fn state_name(state: usize) -> String {
+    char::from(b'A' + state as u8).to_string()
+}
+
+/// Returns the program symbol with the given index: `_` for blank, then `1` to `9`.
+fn symbol_char(symbol: usize) -> char {
+    if symbol == 0 {
+        INPUT_BLANK_SYMBOL
+    } else {
+        char::from_digit(symbol as u32, 10).unwrap()
+    }
+}
+
+/// A busy beaver champion.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Champion {
+    /// The machine, with its halting transition into the `halt` state.
+    pub program: Program,
+    /// The machine's transition table in standard compact notation, e.g. `1RB1LB_1LA1RZ`.
+    pub notation: String,
+    /// The number of steps run before halting, including the halting transition.
+    pub steps: usize,
+    /// The number of non-blank symbols on the tape when the machine halts.
+    pub ones: usize,
+}
+
+/// The results of enumerating busy beaver candidates.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    /// The number of machines enumerated.
+    pub machines: usize,
+    /// The number of machines that halted within the step budget.
+    pub halting: usize,
+    /// The number of machines proven never to halt by the deciders.
+    pub never_halting: usize,
+    /// The number of machines that neither halted nor were proven never to halt.
+    pub unknown: usize,
+    /// The halting machine that runs for the most steps.
+    pub max_steps: Option<Champion>,
+    /// The halting machine that writes the most non-blank symbols.
+    pub max_ones: Option<Champion>,
+}
+
+impl Summary {
+    /// Counts an enumerated machine, keeping it if it's a new champion.
+    fn record(&mut self, table: &Table, decision: &Decision, ones: usize) {
+        self.machines += 1;
+
+        let steps = match *decision {
+            Decision::Halts(steps) => steps,
+            Decision::NeverHalts(_) => return self.never_halting += 1,
+            Decision::Unknown => return self.unknown += 1,
+        };
+        self.halting += 1;
+
+        let champion = Champion {
+            program: table.to_program(),
+            notation: table.to_string(),
+            steps,
+            ones,
+        };
+        offer(&mut self.max_steps, champion.clone(), |c| c.steps);
+        offer(&mut self.max_ones, champion, |c| c.ones);
+    }
+
+    /// Merges the results of another enumeration into this one.
+    fn merge(&mut self, other: Summary) {
+        self.machines += other.machines;
+        self.halting += other.halting;
+        self.never_halting += other.never_halting;
+        self.unknown += other.unknown;
+
+        if let Some(champion) = other.max_steps {
+            offer(&mut self.max_steps, champion, |c| c.steps);
+        }
+        if let Some(champion) = other.max_ones {
+            offer(&mut self.max_ones, champion, |c| c.ones);
+        }
+    }
+}
+
+/// Keeps `candidate` if it beats the current champion by `key`. Ties go to the smallest
+/// notation, so the champions don't depend on the order machines are enumerated in.
+fn offer(champion: &mut Option<Champion>, candidate: Champion, key: fn(&Champion) -> usize) {
+    let better = champion.as_ref().is_none_or(|champion| {
+        (key(&candidate), Reverse(&candidate.notation))
+            > (key(champion), Reverse(&champion.notation))
+    });
+
+    if better {
+        *champion = Some(candidate);
+    }
+}
+
+/// Enumerates every `n`-state, `k`-symbol Turing Machine in tree normal form and runs it from a
+/// blank tape, using several worker threads.
+///
+/// Machines that don't halt within the step budget are passed to the deciders, which may prove
+/// that they never halt.
+pub struct Enumerator {
+    states: usize,
+    symbols: usize,
+    max_steps: usize,
+    workers: usize,
+}
+
+impl Enumerator {
+    /// Creates a new `Enumerator` for machines with the given number of states and symbols,
+    /// including the blank symbol.
+    ///
+    /// Returns an error unless there are 1 to 26 states and 2 to 10 symbols.
+    pub fn new(states: usize, symbols: usize) -> Result<Self, TuringMachineError> {
+        if !(1..=MAX_STATES).contains(&states) {
+            return Err(TuringMachineError::ValidationError(format!(
+                "Busy beaver machines must have 1 to {MAX_STATES} states, got {states}"
+            )));
+        }
+        if !(2..=MAX_SYMBOLS).contains(&symbols) {
+            return Err(TuringMachineError::ValidationError(format!(
+                "Busy beaver machines must have 2 to {MAX_SYMBOLS} symbols, got {symbols}"
+            )));
+        }
+
+        Ok(Self {
+            states,
+            symbols,
+            max_steps: MAX_BUSY_BEAVER_STEPS,
+            workers: thread::available_parallelism().map_or(1, |n| n.get()),
+        })
+    }
+
+    /// Sets the maximum number of steps each machine is run for.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Sets the number of worker threads. Defaults to the available parallelism.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Enumerates and runs every machine, returning the counts and champions.
+    pub fn run(&self) -> Summary {
+        let mut summary = Summary::default();
+
+        // Expand the top of the tree until there are enough subtrees to share among workers
+        let mut frontier = vec![Table::new(self.states, self.symbols)];
+        while !frontier.is_empty() && frontier.len() < self.workers * 16 {
+            let mut next = Vec::new();
+            for table in frontier {
+                self.expand(table, &mut next, &mut |table, decision, ones| {
+                    summary.record(table, decision, ones)
+                });
+            }
+            frontier = next;
+        }
+
+        let queue = Mutex::new(frontier);
+        thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.workers)
+                .map(|_| {
+                    scope.spawn(|| {
+                        let mut summary = Summary::default();
+                        while let Some(table) = queue.lock().unwrap().pop() {
+                            self.explore(table, &mut |table, decision, ones| {
+                                summary.record(table, decision, ones)
+                            });
+                        }
+                        summary
+                    })
+                })
+                .collect();
+
+            for worker in workers {
+                summary.merge(worker.join().unwrap());
+            }
+        });
+
+        summary
+    }
+
+    /// Enumerates and runs every machine on the current thread, calling `visit` with each
+    /// machine and its outcome.
+    pub fn for_each(&self, mut visit: impl FnMut(Program, Decision)) {
+        self.explore(
+            Table::new(self.states, self.symbols),
+            &mut |table, decision, _| visit(table.to_program(), decision.clone()),
+        );
+    }
+
+    /// Enumerates the subtree rooted at `table` depth-first.
+    fn explore(&self, table: Table, visit: &mut impl FnMut(&Table, &Decision, usize)) {
+        let mut stack = vec![table];
+        while let Some(table) = stack.pop() {
+            self.expand(table, &mut stack, visit);
+        }
+    }
+
+    /// Runs a partial machine until it halts, reaches the step budget or reads an undefined
+    /// transition. The first two complete a machine, passed to `visit` with its outcome and
+    /// the number of non-blank symbols written; the last adds its children to `children`.
+    fn expand(
+        &self,
+        table: Table,
+        children: &mut Vec<Table>,
+        visit: &mut impl FnMut(&Table, &Decision, usize),
+    ) {
+        let program = table.to_program();
+        let mut machine = TuringMachine::new(program.clone())
+            .with_max_steps(self.max_steps)
+            .with_history(false);
+
+        match machine.run() {
+            Step::Halt(Halt::Ok) => {}
+            Step::Halt(Halt::StepLimit) => {
+                return visit(&table, &decide(&program, self.max_steps), 0);
+            }
+            result => unreachable!("unexpected result for a busy beaver candidate: {result:?}"),
+        }
+
+        // The machine read an undefined transition: make it the halting transition...
+        let tape = &machine.tapes()[0];
+        let state = machine.state().as_bytes()[0] as usize - b'A' as usize;
+        let symbol = if tape.read() == tape.blank() {
+            0
+        } else {
+            tape.read().to_digit(10).unwrap() as usize
+        };
+
+        let ones = tape.symbols().filter(|&c| c != tape.blank()).count()
+            + usize::from(tape.read() == tape.blank());
+        visit(
+            &table.with(state, symbol, Entry::Halt),
+            &Decision::Halts(machine.step_count() + 1),
+            ones,
+        );
+
+        // ...or define it in every other way, as long as a transition is left to halt on
+        if table.undefined() <= 1 {
+            return;
+        }
+
+        let states = (table.used_states() + 1).min(self.states);
+        let directions: &[Direction] = if table.is_empty() {
+            &[Direction::Right]
+        } else {
+            &[Direction::Left, Direction::Right]
+        };
+
+        for write in 0..self.symbols {
+            for &direction in directions {
+                for next in 0..states {
+                    children.push(table.with(
+                        state,
+                        symbol,
+                        Entry::Move {
+                            write,
+                            direction,
+                            next,
+                        },
+                    ));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_busy_beaver_2() {
+        let summary = Enumerator::new(2, 2).unwrap().with_workers(2).run();
+
+        let champion = summary.max_steps.unwrap();
+        assert_eq!(champion.steps, 6);
+        assert_eq!(summary.max_ones.unwrap().ones, 4);
+        assert_eq!(summary.unknown, 0);
+        assert_eq!(
+            summary.machines,
+            summary.halting + summary.never_halting + summary.unknown
+        );
+
+        // The champion runs the same in a regular machine
+        let mut machine = TuringMachine::new(champion.program);
+        assert_eq!(machine.run(), Step::Halt(Halt::Ok));
+        assert_eq!(machine.step_count(), 6);
+        assert_eq!(machine.state(), HALT_STATE);
+    }
+
+    #[test]
+    fn test_busy_beaver_3() {
+        let summary = Enumerator::new(3, 2).unwrap().with_max_steps(100).run();
+
+        assert_eq!(summary.max_steps.unwrap().steps, 21);
+        assert_eq!(summary.max_ones.unwrap().ones, 6);
+        assert_eq!(summary.unknown, 27);
+    }
+
+    #[test]
+    fn test_workers_agree() {
+        let enumerator = Enumerator::new(2, 3).unwrap().with_max_steps(100);
+        let single = enumerator.with_workers(1).run();
+        let parallel = Enumerator::new(2, 3)
+            .unwrap()
+            .with_max_steps(100)
+            .with_workers(4)
+            .run();
+
+        assert_eq!(single, parallel);
+        assert_eq!(single.max_steps.unwrap().steps, 38);
+    }
+
+    #[test]
+    fn test_for_each() {
+        let mut machines = 0;
+        Enumerator::new(2, 2).unwrap().for_each(|program, _| {
+            assert_eq!(program.initial_state, "A");
+            machines += 1;
+        });
+
+        assert_eq!(
+            machines,
+            Enumerator::new(2, 2)
+                .unwrap()
+                .with_workers(1)
+                .run()
+                .machines
+        );
+    }
+
+    #[test]
+    fn test_notation() {
+        let table = Table::new(2, 2)
+            .with(
+                0,
+                0,
+                Entry::Move {
+                    write: 1,
+                    direction: Direction::Right,
+                    next: 1,
+                },
+            )
+            .with(1, 1, Entry::Halt);
+
+        assert_eq!(table.to_string(), "1RB---_---1RZ");
+        assert!(Enumerator::new(0, 2).is_err());
+    }
+}
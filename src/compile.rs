@@ -0,0 +1,168 @@
+//! This module holds what the program transformations share: building the states of a
+//! generated program breadth-first from its initial state, and finishing it by removing the
+//! states it can't reach.
+
+use crate::analyzer::analyze;
+use crate::types::{Direction, Program, Transition, TuringMachineError, INPUT_BLANK_SYMBOL};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::Hash;
+
+/// The states of a generated single-tape program, built on demand from their nodes.
+pub(crate) trait StateBuilder {
+    /// A state of the generated program, holding what it knows about the original program.
+    type Node: Clone + Eq + Hash;
+
+    /// Returns the transitions of a state, as the symbol read, the symbol written, the
+    /// direction and the next state.
+    fn transitions(&self, node: &Self::Node) -> Vec<(char, char, Direction, Self::Node)>;
+
+    /// Returns the name of a state.
+    fn name(&self, node: &Self::Node) -> String;
+}
+
+/// Builds the transitions of every state reachable from `initial`, writing `blank` as `_`.
+pub(crate) fn build_rules<B: StateBuilder>(
+    builder: &B,
+    initial: &B::Node,
+    blank: char,
+) -> HashMap<String, Vec<Transition>> {
+    let mut rules = HashMap::new();
+    let mut queue = VecDeque::from([initial.clone()]);
+    let mut seen = HashSet::from([initial.clone()]);
+
+    while let Some(node) = queue.pop_front() {
+        let transitions = builder
+            .transitions(&node)
+            .into_iter()
+            .map(|(read, write, direction, next)| {
+                let transition = Transition {
+                    read: vec![tape_symbol(read, blank)],
+                    write: vec![tape_symbol(write, blank)],
+                    directions: vec![direction],
+                    next_state: builder.name(&next),
+                };
+                if seen.insert(next.clone()) {
+                    queue.push_back(next);
+                }
+                transition
+            })
+            .collect();
+
+        rules.insert(builder.name(&node), transitions);
+    }
+
+    rules
+}
+
+/// Returns how a symbol is written in a transition, with `_` for blank.
+pub(crate) fn tape_symbol(symbol: char, blank: char) -> char {
+    if symbol == blank {
+        INPUT_BLANK_SYMBOL
+    } else {
+        symbol
+    }
+}
+
+/// Checks if a program halts as soon as it enters `state`.
+pub(crate) fn halts_in(program: &Program, state: &str) -> bool {
+    program.is_stop_state(state) || program.rules.get(state).is_none_or(Vec::is_empty)
+}
+
+/// Removes the states that can't be reached from the initial state, and checks the program.
+pub(crate) fn finish_program(mut program: Program) -> Result<Program, TuringMachineError> {
+    let mut reachable = HashSet::from([program.initial_state.clone()]);
+    let mut queue = vec![program.initial_state.clone()];
+
+    while let Some(state) = queue.pop() {
+        for transition in program.rules.get(&state).into_iter().flatten() {
+            if reachable.insert(transition.next_state.clone()) {
+                queue.push(transition.next_state.clone());
+            }
+        }
+    }
+
+    program.rules.retain(|state, _| reachable.contains(state));
+    for states in [
+        &mut program.stop_states,
+        &mut program.accept_states,
+        &mut program.reject_states,
+    ] {
+        states.retain(|state| reachable.contains(state));
+    }
+
+    analyze(&program)?;
+
+    Ok(program)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::machine::TuringMachine;
+    use crate::tape::Tape;
+    use crate::types::{Halt, Step};
+
+    /// Runs a program on `input`, and the program it was transformed into on `encoded`, and
+    /// checks that they halt with the same tapes once `decode`d, and in the same state if it's
+    /// one the program halts in. Returns the number of steps the transformed program took.
+    pub(crate) fn assert_equivalent(
+        program: &Program,
+        input: &[&str],
+        transformed: &Program,
+        encoded: String,
+        decode: impl Fn(&Tape) -> Vec<Tape>,
+    ) -> usize {
+        let input: Vec<String> = input.iter().map(|tape| tape.to_string()).collect();
+
+        let mut machine = TuringMachine::new(program.clone());
+        machine.set_tapes_content(&input).unwrap();
+        assert!(matches!(
+            machine.run(),
+            Step::Halt(Halt::Ok | Halt::Accept | Halt::Reject(_))
+        ));
+
+        let mut simulation = TuringMachine::new(transformed.clone()).with_max_steps(100_000);
+        simulation.set_tapes_content(&[encoded]).unwrap();
+        assert!(matches!(
+            simulation.run(),
+            Step::Halt(Halt::Ok | Halt::Accept | Halt::Reject(_))
+        ));
+
+        if halts_in(program, machine.state()) {
+            assert_eq!(simulation.state(), machine.state());
+        }
+        let decoded = decode(&simulation.tapes()[0]);
+        assert_eq!(decoded.len(), machine.tapes().len());
+        for (decoded, tape) in decoded.iter().zip(machine.tapes()) {
+            assert_eq!(decoded.trimmed(), tape.trimmed());
+            assert_eq!(decoded.head(), tape.head());
+        }
+
+        simulation.step_count()
+    }
+
+    #[test]
+    fn test_finish_program() {
+        let program = crate::parser::parse(
+            r#"
+name: Finish
+tape: a
+rules:
+  start:
+    a, R, halt
+  halt:
+"#,
+        )
+        .unwrap();
+
+        let mut unfinished = program.clone();
+        unfinished.rules.insert("orphan".to_string(), Vec::new());
+        unfinished.stop_states.push("orphan".to_string());
+
+        let finished = finish_program(unfinished).unwrap();
+        assert_eq!(finished.rules, program.rules);
+        assert!(!finished.stop_states.contains(&"orphan".to_string()));
+        assert!(halts_in(&finished, "halt"));
+        assert!(!halts_in(&finished, "start"));
+    }
+}
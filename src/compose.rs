@@ -0,0 +1,353 @@
+//! This module provides combinators that build new Turing Machine programs out of existing ones.
+//!
+//! The resulting programs are plain `Program`s that pass `analyze`, so they can be run, saved,
+//! visualized and encoded like hand-written programs.
+
+use crate::compile::{finish_program, halts_in};
+use crate::types::{Direction, Mode, Program, Transition, TuringMachineError, WILDCARD_SYMBOL};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// Builds a program that runs `a`, then starts `b` from the configuration `a` halts in.
+///
+/// The states of `a` and `b` are renamed to `a.STATE` and `b.STATE` to avoid clashes. Every
+/// transition into a state where `a` halts goes to the initial state of `b` instead. In normal
+/// mode, `a` also halts when no transition matches, so each of its states gets a final
+/// transition that moves on to `b` for any other symbols, taking one extra step.
+///
+/// The composed program keeps the tapes, heads and name of `a`, and the stop, accept and
+/// reject states of `b`.
+///
+/// # Errors
+///
+/// Returns `TuringMachineError::ValidationError` if the programs have different numbers of
+/// tapes, blank symbols or modes, or are nondeterministic.
+pub fn compose(a: &Program, b: &Program) -> Result<Program, TuringMachineError> {
+    check_compatible(a, b)?;
+    if a.tapes.len() != b.tapes.len() {
+        return Err(TuringMachineError::ValidationError(format!(
+            "Can't compose a program with {} tapes and one with {} tapes",
+            a.tapes.len(),
+            b.tapes.len()
+        )));
+    }
+
+    let tapes = a.tapes.len();
+    let b_start = format!("b.{}", b.initial_state);
+    let next = |state: &str| {
+        if halts_in(a, state) {
+            b_start.clone()
+        } else {
+            format!("a.{state}")
+        }
+    };
+
+    let mut rules = HashMap::new();
+    for (state, transitions) in &a.rules {
+        if halts_in(a, state) {
+            continue;
+        }
+
+        let mut transitions: Vec<Transition> = transitions
+            .iter()
+            .map(|transition| Transition {
+                next_state: next(&transition.next_state),
+                ..transition.clone()
+            })
+            .collect();
+        if a.mode == Mode::Normal {
+            add_catch_all(&mut transitions, tapes, &b_start, a.blank);
+        }
+
+        rules.insert(format!("a.{state}"), transitions);
+    }
+
+    let prefix = |state: &String| format!("b.{state}");
+    for (state, transitions) in &b.rules {
+        let transitions = transitions
+            .iter()
+            .map(|transition| Transition {
+                next_state: prefix(&transition.next_state),
+                ..transition.clone()
+            })
+            .collect();
+        rules.insert(prefix(state), transitions);
+    }
+
+    // States `b` halts in without defining them, such as `halt`
+    let undefined: Vec<String> = rules
+        .values()
+        .flatten()
+        .map(|transition| transition.next_state.clone())
+        .filter(|state| !rules.contains_key(state))
+        .collect();
+    for state in undefined {
+        rules.insert(state, Vec::new());
+    }
+
+    let initial_state = next(&a.initial_state);
+    let program = Program {
+        name: format!("{} then {}", a.name, b.name),
+        mode: a.mode.clone(),
+        initial_state,
+        stop_states: b.stop_states.iter().map(prefix).collect(),
+        accept_states: b.accept_states.iter().map(prefix).collect(),
+        reject_states: b.reject_states.iter().map(prefix).collect(),
+        tapes: a.tapes.clone(),
+        heads: a.heads.clone(),
+        blank: a.blank,
+        rules,
+        source_map: None,
+    };
+
+    finish_program(program)
+}
+
+/// Builds a two-tape program that runs two single-tape programs side by side, `a` on the first
+/// tape and `b` on the second.
+///
+/// Each state of the product is a pair of states named `a.STATE-b.STATE`, and each step takes a
+/// step of both programs. Once one of them halts, it stays put while the other one runs, and
+/// the product halts when both have halted. Stop, accept and reject states aren't kept, so the
+/// product never reports a verdict.
+///
+/// # Errors
+///
+/// Returns `TuringMachineError::ValidationError` if either program has more than one tape, or
+/// they have different blank symbols or modes, or are nondeterministic. It's also returned if
+/// two pairs of states would get the same name, e.g. `p-b.q` and `r`, and `p` and `q-b.r`.
+pub fn product(a: &Program, b: &Program) -> Result<Program, TuringMachineError> {
+    check_compatible(a, b)?;
+    if !a.is_single_tape() || !b.is_single_tape() {
+        return Err(TuringMachineError::ValidationError(
+            "Only single-tape programs can be run side by side".to_string(),
+        ));
+    }
+
+    let name = |(state_a, state_b): &(String, String)| format!("a.{state_a}-b.{state_b}");
+    let initial = (a.initial_state.clone(), b.initial_state.clone());
+
+    let mut rules = HashMap::new();
+    let mut queue = VecDeque::from([initial.clone()]);
+    let mut seen = HashSet::from([initial.clone()]);
+    // The pair each name was given to, as state names may contain `-` and `.` themselves
+    let mut names = HashMap::from([(name(&initial), initial.clone())]);
+
+    while let Some(pair) = queue.pop_front() {
+        let moves_a = moves(a, &pair.0);
+        let moves_b = moves(b, &pair.1);
+
+        let mut transitions = Vec::new();
+        for (transition_a, halted_a) in &moves_a {
+            for (transition_b, halted_b) in &moves_b {
+                // Neither program moves, so the product halts
+                if *halted_a && *halted_b {
+                    continue;
+                }
+
+                let next = (
+                    transition_a.next_state.clone(),
+                    transition_b.next_state.clone(),
+                );
+                transitions.push(Transition {
+                    read: [transition_a.read[0], transition_b.read[0]].to_vec(),
+                    write: [transition_a.write[0], transition_b.write[0]].to_vec(),
+                    directions: [transition_a.directions[0], transition_b.directions[0]].to_vec(),
+                    next_state: name(&next),
+                });
+
+                if seen.insert(next.clone()) {
+                    if let Some(other) = names.insert(name(&next), next.clone()) {
+                        return Err(TuringMachineError::ValidationError(format!(
+                            "States {:?} and {:?} would both be named {}",
+                            other,
+                            next,
+                            name(&next)
+                        )));
+                    }
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        rules.insert(name(&pair), transitions);
+    }
+
+    let program = Program {
+        name: format!("{} and {}", a.name, b.name),
+        mode: a.mode.clone(),
+        initial_state: name(&initial),
+        stop_states: Vec::new(),
+        accept_states: Vec::new(),
+        reject_states: Vec::new(),
+        tapes: vec![a.initial_tape(), b.initial_tape()],
+        heads: vec![a.head_position(), b.head_position()],
+        blank: a.blank,
+        rules,
+        source_map: None,
+    };
+
+    finish_program(program)
+}
+
+/// Checks that two programs can be combined.
+fn check_compatible(a: &Program, b: &Program) -> Result<(), TuringMachineError> {
+    if a.mode == Mode::Nondeterministic || b.mode == Mode::Nondeterministic {
+        return Err(TuringMachineError::ValidationError(
+            "Nondeterministic programs can't be combined".to_string(),
+        ));
+    }
+    if a.mode != b.mode {
+        return Err(TuringMachineError::ValidationError(format!(
+            "Can't combine a program in {:?} mode with one in {:?} mode",
+            a.mode, b.mode
+        )));
+    }
+    if a.blank != b.blank {
+        return Err(TuringMachineError::ValidationError(format!(
+            "Can't combine programs with different blank symbols ('{}' and '{}')",
+            a.blank, b.blank
+        )));
+    }
+
+    Ok(())
+}
+
+/// Returns the single-tape transitions a program can take in `state`, each with whether it's
+/// the program staying put because it halted.
+fn moves(program: &Program, state: &str) -> Vec<(Transition, bool)> {
+    if halts_in(program, state) {
+        let mut stay = Vec::new();
+        add_catch_all(&mut stay, 1, state, program.blank);
+        return vec![(stay.remove(0), true)];
+    }
+
+    let mut transitions = program.rules[state].clone();
+    let defined = transitions.len();
+    if program.mode == Mode::Normal {
+        add_catch_all(&mut transitions, 1, state, program.blank);
+    }
+
+    transitions
+        .into_iter()
+        .enumerate()
+        .map(|(i, transition)| (transition, i >= defined))
+        .collect()
+}
+
+/// Adds a transition reading any symbols, and moving to `next_state` without changing the tapes,
+/// unless an existing transition already matches any symbols.
+fn add_catch_all(transitions: &mut Vec<Transition>, tapes: usize, next_state: &str, blank: char) {
+    let catch_all = Transition {
+        read: vec![WILDCARD_SYMBOL; tapes],
+        write: vec![WILDCARD_SYMBOL; tapes],
+        directions: vec![Direction::Stay; tapes],
+        next_state: next_state.to_string(),
+    };
+
+    if !transitions
+        .iter()
+        .any(|transition| transition.covers(&catch_all, blank))
+    {
+        transitions.push(catch_all);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyzer::analyze;
+    use crate::machine::TuringMachine;
+    use crate::parser::parse;
+
+    fn run(program: Program, tapes: &[&str]) -> Vec<String> {
+        let mut machine = TuringMachine::new(program);
+        let tapes: Vec<String> = tapes.iter().map(|tape| tape.to_string()).collect();
+        machine.set_tapes_content(&tapes).unwrap();
+        machine.run();
+        machine
+            .tapes()
+            .iter()
+            .map(|tape| tape.to_string().trim().to_string())
+            .collect()
+    }
+
+    fn flip() -> Program {
+        parse(
+            r#"
+name: Flip
+tape: a, a
+rules:
+  start:
+    a -> b, R, start
+    _, L, done
+  done:
+"#,
+        )
+        .unwrap()
+    }
+
+    fn mark() -> Program {
+        parse(
+            r#"
+name: Mark
+tape: b
+rules:
+  start:
+    b, L, start
+    * -> X, S, halt
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_compose() {
+        let program = compose(&flip(), &mark()).unwrap();
+        assert_eq!(program.name, "Flip then Mark");
+        assert_eq!(program.initial_state, "a.start");
+        assert!(program.rules.contains_key("b.start"));
+        assert!(!program.rules.contains_key("a.done"));
+
+        // `Flip` halts on `c` without a transition, and `Mark` takes over from there
+        assert_eq!(run(program, &["aac"]), vec!["bbX"]);
+    }
+
+    #[test]
+    fn test_compose_checks_programs() {
+        let two_tapes = parse(include_str!("../examples/multi-tape-copy.tur")).unwrap();
+        assert!(compose(&flip(), &two_tapes).is_err());
+
+        let mut strict = mark();
+        strict.mode = Mode::Strict;
+        assert!(compose(&flip(), &strict).is_err());
+    }
+
+    #[test]
+    fn test_product() {
+        let program = product(&flip(), &mark()).unwrap();
+        assert_eq!(program.tapes, vec!["aa", "b"]);
+        assert_eq!(program.initial_state, "a.start-b.start");
+        assert!(analyze(&program).is_ok());
+
+        // Each program runs on its own tape, and the product halts once both have halted
+        assert_eq!(run(program, &["aac", "bb"]), vec!["bbc", "Xbb"]);
+    }
+
+    #[test]
+    fn test_product_name_collision() {
+        let a = parse("name: A\ntape: a\nrules:\n  p:\n    a, R, p-b.q\n  p-b.q:").unwrap();
+        let b = parse("name: B\ntape: a\nrules:\n  q-b.r:\n    a, R, r\n  r:").unwrap();
+
+        // (p, q-b.r) goes to (p-b.q, r), and both are named a.p-b.q-b.r
+        assert!(matches!(
+            product(&a, &b),
+            Err(TuringMachineError::ValidationError(message)) if message.contains("a.p-b.q-b.r")
+        ));
+    }
+
+    #[test]
+    fn test_product_of_multi_tape_programs() {
+        let two_tapes = parse(include_str!("../examples/multi-tape-copy.tur")).unwrap();
+        assert!(product(&flip(), &two_tapes).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+//! This module provides a coverage analysis of a program's transitions: for each state reachable
+//! from the initial state, the combinations of symbols it has no transition for.
+//!
+//! A missing transition halts the machine in `Mode::Normal` (and ends the branch in
+//! `Mode::Nondeterministic`), and is an error in `Mode::Strict`. Halting on a missing transition
+//! is often intended, but a forgotten rule is a common bug, so the analysis lists every gap.
+
+use crate::types::{resolve, Mode, Program, WILDCARD_SYMBOL};
+use std::collections::{BTreeSet, HashSet, VecDeque};
+
+/// What the machine does when it reads symbols its state has no transition for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The machine halts, as in `Mode::Normal` and `Mode::Nondeterministic`.
+    Halt,
+    /// The machine halts with an error, as in `Mode::Strict`.
+    Error,
+}
+
+/// The transition coverage of a state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateCoverage {
+    /// The name of the state.
+    pub state: String,
+    /// The number of symbol combinations that have a transition.
+    pub covered: usize,
+    /// The symbol combinations, one symbol per tape, that have no transition, in the order of
+    /// the alphabets.
+    pub missing: Vec<Vec<char>>,
+}
+
+impl StateCoverage {
+    /// Returns the number of symbol combinations the state can read.
+    pub fn total(&self) -> usize {
+        self.covered + self.missing.len()
+    }
+
+    /// Checks if every symbol combination has a transition.
+    pub fn is_complete(&self) -> bool {
+        self.missing.is_empty()
+    }
+}
+
+/// The transition coverage of a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage {
+    /// The symbols each tape can hold: the blank symbol, the symbols of its initial content and
+    /// the symbols transitions read from and write to it, sorted.
+    pub alphabets: Vec<Vec<char>>,
+    /// The reachable states that read from the tapes, in the order they are reached from the
+    /// initial state. Stop states and states without transitions halt without reading, so they
+    /// aren't included.
+    pub states: Vec<StateCoverage>,
+    /// What the machine does on a missing transition.
+    pub outcome: Outcome,
+}
+
+impl Coverage {
+    /// Checks if every reachable state has a transition for every symbol combination.
+    pub fn is_complete(&self) -> bool {
+        self.states.iter().all(StateCoverage::is_complete)
+    }
+
+    /// Returns the coverage of a state, if it's reachable and reads from the tapes.
+    pub fn state(&self, state: &str) -> Option<&StateCoverage> {
+        self.states.iter().find(|coverage| coverage.state == state)
+    }
+}
+
+/// Analyzes which symbol combinations each reachable state of `program` has no transition for.
+///
+/// The combinations are the cartesian product of the tapes' alphabets, so every symbol a tape
+/// can hold is checked against every symbol of the other tapes. `_` in transitions is the
+/// program's blank symbol, and `*` covers every symbol.
+pub fn coverage(program: &Program) -> Coverage {
+    let alphabets = alphabets(program);
+    let combinations = combinations(&alphabets);
+
+    let states = reachable_states(program)
+        .into_iter()
+        .filter_map(|state| {
+            let transitions = &program.rules[&state];
+            if transitions.is_empty() || program.is_stop_state(&state) {
+                return None;
+            }
+
+            let missing: Vec<Vec<char>> = combinations
+                .iter()
+                .filter(|symbols| {
+                    !transitions
+                        .iter()
+                        .any(|transition| transition.matches(symbols, program.blank))
+                })
+                .cloned()
+                .collect();
+
+            Some(StateCoverage {
+                state,
+                covered: combinations.len() - missing.len(),
+                missing,
+            })
+        })
+        .collect();
+
+    Coverage {
+        alphabets,
+        states,
+        outcome: match program.mode {
+            Mode::Strict => Outcome::Error,
+            Mode::Normal | Mode::Nondeterministic => Outcome::Halt,
+        },
+    }
+}
+
+/// Returns the alphabet of each tape, sorted.
+fn alphabets(program: &Program) -> Vec<Vec<char>> {
+    program
+        .tapes
+        .iter()
+        .enumerate()
+        .map(|(i, tape)| {
+            let mut alphabet: BTreeSet<char> = tape.chars().collect();
+            alphabet.insert(program.blank);
+
+            for transition in program.rules.values().flatten() {
+                for symbol in [transition.read.get(i), transition.write.get(i)]
+                    .into_iter()
+                    .flatten()
+                {
+                    if *symbol != WILDCARD_SYMBOL {
+                        alphabet.insert(resolve(*symbol, program.blank));
+                    }
+                }
+            }
+
+            alphabet.into_iter().collect()
+        })
+        .collect()
+}
+
+/// Returns every combination of one symbol per alphabet, in order.
+fn combinations(alphabets: &[Vec<char>]) -> Vec<Vec<char>> {
+    alphabets
+        .iter()
+        .fold(vec![Vec::new()], |combinations, alphabet| {
+            combinations
+                .iter()
+                .flat_map(|combination| {
+                    alphabet.iter().map(move |&symbol| {
+                        let mut combination = combination.clone();
+                        combination.push(symbol);
+                        combination
+                    })
+                })
+                .collect()
+        })
+}
+
+/// Returns the states with rules reachable from the initial state, in breadth-first order.
+fn reachable_states(program: &Program) -> Vec<String> {
+    let mut reached = Vec::new();
+    let mut visited = HashSet::from([program.initial_state.clone()]);
+    let mut queue = VecDeque::from([program.initial_state.clone()]);
+
+    while let Some(state) = queue.pop_front() {
+        let Some(transitions) = program.rules.get(&state) else {
+            continue;
+        };
+
+        // Stop states halt as soon as they are entered, so their transitions aren't followed
+        if !program.is_stop_state(&state) {
+            for transition in transitions {
+                if visited.insert(transition.next_state.clone()) {
+                    queue.push_back(transition.next_state.clone());
+                }
+            }
+        }
+
+        reached.push(state);
+    }
+
+    reached
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_coverage() {
+        let program = parse(
+            r#"
+name: Coverage
+tape: a, b
+rules:
+  start:
+    a -> b, R, start
+    b, R, next
+  next:
+    *, L, done
+  done:
+"#,
+        )
+        .unwrap();
+
+        let coverage = coverage(&program);
+        assert_eq!(coverage.alphabets, vec![vec![' ', 'a', 'b']]);
+        assert_eq!(coverage.outcome, Outcome::Halt);
+        assert!(!coverage.is_complete());
+
+        // `done` has no transitions, so it halts without reading
+        let states: Vec<&str> = coverage.states.iter().map(|s| s.state.as_str()).collect();
+        assert_eq!(states, vec!["start", "next"]);
+
+        let start = coverage.state("start").unwrap();
+        assert_eq!(start.covered, 2);
+        assert_eq!(start.total(), 3);
+        assert_eq!(start.missing, vec![vec![' ']]);
+        assert!(coverage.state("next").unwrap().is_complete());
+    }
+
+    #[test]
+    fn test_coverage_multi_tape() {
+        let program = parse(
+            r#"
+name: Multi-tape coverage
+mode: strict
+blank: _
+tapes:
+  [a]
+  [_]
+rules:
+  start:
+    [a, _] -> [a, a], [R, S], start
+    [_, *], [S, S], halt
+"#,
+        )
+        .unwrap();
+
+        let coverage = coverage(&program);
+        assert_eq!(coverage.alphabets, vec![vec!['_', 'a'], vec!['_', 'a']]);
+        assert_eq!(coverage.outcome, Outcome::Error);
+        assert_eq!(coverage.states[0].missing, vec![vec!['a', 'a']]);
+    }
+}
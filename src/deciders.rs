@@ -0,0 +1,806 @@
+//! This module provides heuristic halting deciders for small Turing Machine programs, such as
+//! the ones enumerated in busy beaver searches.
+//!
+//! Deciding whether a machine halts is impossible in general, so every decider here is sound
+//! but incomplete: a program is only classified as halting or non-halting with a proof, and
+//! anything else is reported as `Decision::Unknown`.
+
+use crate::machine::TuringMachine;
+use crate::tape::Tape;
+use crate::types::{resolve, Direction, Halt, Mode, Program, Step, WILDCARD_SYMBOL};
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+
+/// The outcome of running the deciders on a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Decision {
+    /// The program halts after the given number of steps.
+    Halts(usize),
+    /// The program never halts, for the given reason.
+    NeverHalts(NonHalting),
+    /// No decider could classify the program within the step budget.
+    Unknown,
+}
+
+/// A proof that a program never halts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NonHalting {
+    /// Every state reachable from the initial state has a transition for every symbol that
+    /// can appear on the tape, and none of them is a stop state.
+    NoHaltingTransition,
+    /// The machine revisits a configuration (state, head positions and tape contents).
+    /// This covers every machine confined to a bounded region of the tape.
+    Cycler {
+        /// The step at which the repeated configuration was first seen.
+        first_seen_step: usize,
+        /// The number of steps between repetitions of the configuration.
+        period: usize,
+    },
+    /// The machine repeats the same local configuration at the edge of the tape, shifted by a
+    /// fixed offset, so it keeps growing the tape forever.
+    TranslatedCycler {
+        /// The step at which the repeated local configuration was first seen.
+        first_seen_step: usize,
+        /// The number of steps between repetitions of the local configuration.
+        period: usize,
+        /// How far the head moves in each period, negative when the tape grows to the left.
+        shift: isize,
+    },
+    /// The machine sweeps back and forth over a tape of fixed cells and repeated words, adding
+    /// copies of the words on every sweep, so it keeps growing the tape forever.
+    Bouncer {
+        /// The step from which the sweeps were proven to repeat.
+        first_seen_step: usize,
+        /// How many cells the tape grows by on each sweep.
+        growth: usize,
+    },
+}
+
+impl fmt::Display for NonHalting {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NonHalting::NoHaltingTransition => {
+                write!(f, "no reachable state and symbol halts the machine")
+            }
+            NonHalting::Cycler {
+                first_seen_step,
+                period,
+            } => write!(
+                f,
+                "cycler: the configuration at step {first_seen_step} repeats every {period} steps"
+            ),
+            NonHalting::TranslatedCycler {
+                first_seen_step,
+                period,
+                shift,
+            } => write!(
+                f,
+                "translated cycler: the configuration at step {first_seen_step} repeats every \
+                 {period} steps, shifted by {shift} cells"
+            ),
+            NonHalting::Bouncer {
+                first_seen_step,
+                growth,
+            } => write!(
+                f,
+                "bouncer: from step {first_seen_step}, every sweep grows the tape by {growth} \
+                 cells"
+            ),
+        }
+    }
+}
+
+/// The number of steps the machine may take on a single copy of a repeated word in a bouncer.
+const SHIFT_STEPS: usize = 1000;
+
+/// A configuration in which the head had just visited a new cell at the edge of the tape.
+struct Record {
+    step: usize,
+    state: String,
+    tape: Tape,
+}
+
+/// Runs the deciders on a program, starting from its initial tapes.
+///
+/// The program is first checked statically for missing transitions, then run for up to
+/// `max_steps` steps while looking for cyclers and translated cyclers. If it's still running
+/// then, it's checked for bouncers. Translated cyclers and bouncers are only detected on
+/// single-tape programs. Nondeterministic programs are always `Unknown`.
+///
+/// Detection keeps a copy of the tape for every configuration, so memory grows with
+/// `max_steps` times the size of the tape.
+pub fn decide(program: &Program, max_steps: usize) -> Decision {
+    if program.mode == Mode::Nondeterministic {
+        return Decision::Unknown;
+    }
+
+    if !can_halt(program) {
+        return Decision::NeverHalts(NonHalting::NoHaltingTransition);
+    }
+
+    let mut machine = TuringMachine::new(program.clone())
+        .with_max_steps(max_steps)
+        .with_history(false)
+        .with_loop_detection(true);
+
+    // Head positions after each step, and the configurations at each new rightmost or
+    // leftmost cell
+    let mut heads = vec![machine.tapes()[0].head()];
+    let mut right: Vec<Record> = Vec::new();
+    let mut left: Vec<Record> = Vec::new();
+
+    loop {
+        let (start, end) = (machine.tapes()[0].start(), machine.tapes()[0].end());
+
+        match machine.step() {
+            Step::Continue => {}
+            Step::Halt(Halt::Loop {
+                first_seen_step,
+                period,
+            }) => {
+                return Decision::NeverHalts(NonHalting::Cycler {
+                    first_seen_step,
+                    period,
+                })
+            }
+            Step::Halt(Halt::StepLimit) => {
+                return find_bouncer(program, &right, 1)
+                    .or_else(|| find_bouncer(program, &left, -1))
+                    .map_or(Decision::Unknown, Decision::NeverHalts)
+            }
+            Step::Halt(_) => return Decision::Halts(machine.step_count()),
+        }
+
+        if !program.is_single_tape() {
+            continue;
+        }
+
+        let tape = &machine.tapes()[0];
+        heads.push(tape.head());
+
+        let (records, direction) = if tape.end() > end && tape.head() == tape.end() - 1 {
+            (&mut right, 1)
+        } else if tape.start() < start && tape.head() == tape.start() {
+            (&mut left, -1)
+        } else {
+            continue;
+        };
+
+        let record = Record {
+            step: machine.step_count(),
+            state: machine.state().to_string(),
+            tape: tape.clone(),
+        };
+
+        if let Some(reason) = find_translation(records, &record, &heads, direction) {
+            return Decision::NeverHalts(reason);
+        }
+        records.push(record);
+    }
+}
+
+/// Checks if `current` repeats an earlier record at the same edge of the tape, shifted.
+///
+/// Everything beyond the head at a record is blank. So if the states match, and the cells
+/// behind the head match as far back as the head went since the earlier record, the machine
+/// will go through the same steps again from `current`, shifted, forever.
+fn find_translation(
+    records: &[Record],
+    current: &Record,
+    heads: &[isize],
+    direction: isize,
+) -> Option<NonHalting> {
+    let head = current.tape.head();
+    // The furthest position behind the head visited since the record being checked
+    let mut furthest = head;
+    let mut step = current.step;
+
+    for record in records.iter().rev() {
+        for &h in &heads[record.step..step] {
+            furthest = if direction > 0 {
+                furthest.min(h)
+            } else {
+                furthest.max(h)
+            };
+        }
+        step = record.step;
+
+        if record.state != current.state {
+            continue;
+        }
+
+        let record_head = record.tape.head();
+        let reach = (record_head - furthest) * direction;
+
+        let repeats = (0..=reach).all(|offset| {
+            record.tape.get(record_head - offset * direction)
+                == current.tape.get(head - offset * direction)
+        });
+
+        if repeats {
+            return Some(NonHalting::TranslatedCycler {
+                first_seen_step: record.step,
+                period: current.step - record.step,
+                shift: head - record_head,
+            });
+        }
+    }
+
+    None
+}
+
+/// A part of the tape in the proof that a machine is a bouncer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// A single cell.
+    Cell(char),
+    /// A word repeated any number of times, including none.
+    Repeat(Vec<char>),
+}
+
+/// Checks if the last three records in some state at the same edge of the tape prove that the
+/// machine is a bouncer.
+///
+/// The tape must grow by the same words, inserted at the same places, between the records. The
+/// tape of the first record then becomes a formula where each word is repeated any number of
+/// times, and the machine is a bouncer if running the formula to the next record gives it
+/// back with more repetitions, whatever their number.
+///
+/// Records at the left edge are checked on mirrored tapes, so the head is always at the right
+/// end.
+fn find_bouncer(program: &Program, records: &[Record], direction: isize) -> Option<NonHalting> {
+    let mut states: Vec<&str> = Vec::new();
+    for record in records.iter().rev() {
+        if !states.contains(&record.state.as_str()) {
+            states.push(&record.state);
+        }
+    }
+
+    states.into_iter().find_map(|state| {
+        let mut last = records.iter().rev().filter(|record| record.state == state);
+        let (third, second, first) = (last.next()?, last.next()?, last.next()?);
+        let [first_cells, second_cells, third_cells] = [first, second, third].map(|record| {
+            let mut cells = record.tape.to_vec();
+            if direction < 0 {
+                cells.reverse();
+            }
+            cells
+        });
+
+        let growth = second_cells.len().checked_sub(first_cells.len())?;
+        if growth == 0 || third_cells.len() != second_cells.len() + growth {
+            return None;
+        }
+
+        // The head is on the last cell, which stays out of the repeated words
+        let (&head, cells) = first_cells.split_last()?;
+        let words = insertions(cells, &second_cells[..second_cells.len() - 1])?;
+        let mut formula: Vec<Segment> = Vec::new();
+        for (i, &cell) in cells.iter().chain([&head]).enumerate() {
+            for (_, word) in words.iter().filter(|(position, _)| *position == i) {
+                formula.push(Segment::Repeat(word.clone()));
+            }
+            formula.push(Segment::Cell(cell));
+        }
+
+        if expand(&formula, 1) != second_cells || expand(&formula, 2) != third_cells {
+            return None;
+        }
+
+        // The sweep takes no more steps than the machine did from the first record to the
+        // second, which is fewer than from the second to the third
+        let budget = third.step - second.step;
+        let swept = sweep(program, &formula, state, direction, budget)?;
+
+        repeats(&formula, &swept, program.blank).then_some(NonHalting::Bouncer {
+            first_seen_step: first.step,
+            growth,
+        })
+    })
+}
+
+/// Finds the fewest words to insert into `shorter` to make `longer`, as the position in
+/// `shorter` where each word goes and the word.
+fn insertions(shorter: &[char], longer: &[char]) -> Option<Vec<(usize, Vec<char>)>> {
+    let growth = longer.len().checked_sub(shorter.len())?;
+
+    // The fewest words making `longer[..i + k]` from `shorter[..i]`, by `i` and `k`, ending
+    // with a kept cell and with an inserted cell
+    let mut fewest = vec![vec![[usize::MAX; 2]; growth + 1]; shorter.len() + 1];
+    fewest[0][0][0] = 0;
+    for i in 0..=shorter.len() {
+        for k in 0..=growth {
+            if i > 0 && shorter[i - 1] == longer[i + k - 1] {
+                let [kept, inserted] = fewest[i - 1][k];
+                fewest[i][k][0] = kept.min(inserted);
+            }
+            if k > 0 {
+                let [kept, inserted] = fewest[i][k - 1];
+                fewest[i][k][1] = kept.saturating_add(1).min(inserted);
+            }
+        }
+    }
+
+    // Walk back through the cheapest choices, noting which cells were inserted
+    let (mut i, mut k) = (shorter.len(), growth);
+    let [kept, inserted] = fewest[i][k];
+    if kept.min(inserted) == usize::MAX {
+        return None;
+    }
+    let mut inserting = inserted < kept;
+    let mut choices = Vec::new();
+    while i + k > 0 {
+        choices.push(inserting);
+        let current = fewest[i][k][usize::from(inserting)];
+        let [kept, inserted] = if inserting {
+            k -= 1;
+            fewest[i][k]
+        } else {
+            i -= 1;
+            fewest[i][k]
+        };
+        inserting = if inserting {
+            inserted == current
+        } else {
+            kept != current
+        };
+    }
+
+    let mut words: Vec<(usize, Vec<char>)> = Vec::new();
+    let (mut i, mut inserting) = (0, false);
+    for (&cell, &inserted) in longer.iter().zip(choices.iter().rev()) {
+        match (inserted, inserting) {
+            (true, true) => words.last_mut().unwrap().1.push(cell),
+            (true, false) => words.push((i, vec![cell])),
+            (false, _) => i += 1,
+        }
+        inserting = inserted;
+    }
+
+    Some(words)
+}
+
+/// Returns the cells of a formula with every word repeated `times` times.
+fn expand(formula: &[Segment], times: usize) -> Vec<char> {
+    formula
+        .iter()
+        .flat_map(|segment| match segment {
+            Segment::Cell(cell) => vec![*cell],
+            Segment::Repeat(word) => word.repeat(times),
+        })
+        .collect()
+}
+
+/// Runs a formula from `state`, with the head on its last cell, until the head next moves
+/// past that end in the same state. Returns the formula then.
+///
+/// The head crosses repeated words with `shift`, so the run holds for any number of
+/// repetitions. Returns `None` if a word can't be crossed that way, the machine halts, or the
+/// run takes more than `budget` steps.
+fn sweep(
+    program: &Program,
+    formula: &[Segment],
+    state: &str,
+    direction: isize,
+    budget: usize,
+) -> Option<Vec<Segment>> {
+    let mut tape = formula.to_vec();
+    let mut head = tape.len() - 1;
+    let mut current = state.to_string();
+
+    for _ in 0..budget {
+        let Segment::Cell(symbol) = tape[head] else {
+            unreachable!("the head is always on a cell");
+        };
+        let (write, step, next) = next_step(program, &current, symbol, direction)?;
+        tape[head] = Segment::Cell(write);
+        current = next;
+
+        if step == 0 {
+            continue;
+        }
+
+        let mut position = head as isize;
+        loop {
+            position += step;
+            if position < 0 {
+                tape.insert(0, Segment::Cell(program.blank));
+                position = 0;
+            } else if position as usize == tape.len() {
+                tape.push(Segment::Cell(program.blank));
+                if current == state {
+                    return Some(tape);
+                }
+            }
+
+            let Segment::Repeat(word) = &tape[position as usize] else {
+                break;
+            };
+            let word = shift(program, word, &current, step, direction)?;
+            tape[position as usize] = Segment::Repeat(word);
+        }
+        head = position as usize;
+    }
+
+    None
+}
+
+/// Runs the machine over one copy of a repeated word, entering it in `state` while moving by
+/// `step`. Returns the word written if the head leaves on the other side in the same state, as
+/// the machine then does the same on every copy.
+fn shift(
+    program: &Program,
+    word: &[char],
+    state: &str,
+    step: isize,
+    direction: isize,
+) -> Option<Vec<char>> {
+    let mut cells = word.to_vec();
+    let mut position = if step > 0 {
+        0
+    } else {
+        cells.len() as isize - 1
+    };
+    let mut current = state.to_string();
+
+    for _ in 0..SHIFT_STEPS {
+        if position < 0 || position as usize >= cells.len() {
+            let across = (position < 0) == (step < 0);
+            return (across && current == state).then_some(cells);
+        }
+
+        let (write, moved, next) =
+            next_step(program, &current, cells[position as usize], direction)?;
+        cells[position as usize] = write;
+        position += moved;
+        current = next;
+    }
+
+    None
+}
+
+/// Returns the symbol written, how far the head moves and the next state when the machine
+/// reads `symbol` in `state`, or `None` if it halts. Moves are mirrored if `direction` is
+/// negative.
+fn next_step(
+    program: &Program,
+    state: &str,
+    symbol: char,
+    direction: isize,
+) -> Option<(char, isize, String)> {
+    if program.is_stop_state(state) {
+        return None;
+    }
+
+    let transition = program
+        .rules
+        .get(state)?
+        .iter()
+        .find(|transition| transition.matches(&[symbol], program.blank))?;
+    let step = match transition.directions[0] {
+        Direction::Left => -1,
+        Direction::Right => 1,
+        Direction::Stay => 0,
+    };
+
+    Some((
+        transition.written(&[symbol], program.blank)[0],
+        step * direction,
+        transition.next_state.clone(),
+    ))
+}
+
+/// Checks if `swept` is `formula` with more repetitions of its words, i.e. the same words with
+/// each run of cells between them gaining copies of its neighbouring words. Blanks past the far
+/// end of the tape are ignored.
+fn repeats(formula: &[Segment], swept: &[Segment], blank: char) -> bool {
+    let (formula, swept) = (normalize(formula), normalize(swept));
+    let (cells, words) = split(&formula);
+    let (swept_cells, swept_words) = split(&swept);
+    if words != swept_words {
+        return false;
+    }
+
+    let strip = |cells: &[char]| -> Vec<char> {
+        let start = cells.iter().position(|&cell| cell != blank);
+        cells[start.unwrap_or(cells.len())..].to_vec()
+    };
+
+    cells
+        .iter()
+        .zip(&swept_cells)
+        .enumerate()
+        .all(|(i, (cells, swept))| {
+            let before = i.checked_sub(1).map(|i| words[i]);
+            let after = words.get(i).copied();
+
+            // The copies of the word before, then the cells, then the copies of the word after
+            (0..=swept.len()).any(|copies| {
+                let Some(rest) = (match before {
+                    Some(word) => swept.strip_prefix(word.repeat(copies).as_slice()),
+                    None if copies == 0 => Some(swept.as_slice()),
+                    None => None,
+                }) else {
+                    return false;
+                };
+
+                (0..=rest.len()).any(|copies| {
+                    let expected = match after {
+                        Some(word) => [cells.as_slice(), &word.repeat(copies)].concat(),
+                        None if copies == 0 => cells.clone(),
+                        None => return false,
+                    };
+                    if i == 0 {
+                        strip(&expected) == strip(rest)
+                    } else {
+                        expected == rest
+                    }
+                })
+            })
+        })
+}
+
+/// Moves each repeated word of a formula as far towards the start as it goes, rotating it past
+/// the cells before it that match its end, as `c (w c)^n` is `(c w)^n c`. Formulas for the same
+/// tapes then have their words in the same places.
+fn normalize(formula: &[Segment]) -> Vec<Segment> {
+    let mut formula = formula.to_vec();
+
+    for i in 1..formula.len() {
+        let mut i = i;
+        while let [Segment::Cell(cell), Segment::Repeat(word)] = &mut formula[i - 1..=i] {
+            if word.last() != Some(cell) {
+                break;
+            }
+            word.rotate_right(1);
+            formula.swap(i - 1, i);
+            i -= 1;
+            if i == 0 {
+                break;
+            }
+        }
+    }
+
+    formula
+}
+
+/// Splits a formula into its runs of cells and the repeated words between them.
+fn split(formula: &[Segment]) -> (Vec<Vec<char>>, Vec<&[char]>) {
+    let mut cells = vec![Vec::new()];
+    let mut words = Vec::new();
+
+    for segment in formula {
+        match segment {
+            Segment::Cell(cell) => cells.last_mut().unwrap().push(*cell),
+            Segment::Repeat(word) => {
+                words.push(word.as_slice());
+                cells.push(Vec::new());
+            }
+        }
+    }
+
+    (cells, words)
+}
+
+/// Checks if the program could ever halt, i.e. some state reachable from the initial state is
+/// a stop state or lacks a transition for a symbol that may appear on the tape.
+///
+/// Only single-tape programs are checked; multi-tape programs are assumed to be able to halt.
+fn can_halt(program: &Program) -> bool {
+    if !program.is_single_tape() {
+        return true;
+    }
+
+    // Every symbol that can be on the tape: blanks, the input and anything written. Writing
+    // a wildcard writes back a symbol that was already there.
+    let mut alphabet: HashSet<char> = program.initial_tape().chars().collect();
+    alphabet.insert(program.blank);
+    alphabet.extend(
+        program
+            .rules
+            .values()
+            .flatten()
+            .flat_map(|transition| transition.write.iter().copied())
+            .filter(|&symbol| symbol != WILDCARD_SYMBOL)
+            .map(|symbol| resolve(symbol, program.blank)),
+    );
+
+    let mut reachable = HashSet::from([program.initial_state.as_str()]);
+    let mut queue = VecDeque::from([program.initial_state.as_str()]);
+
+    while let Some(state) = queue.pop_front() {
+        let transitions = match program.rules.get(state) {
+            Some(transitions) if !program.is_stop_state(state) => transitions,
+            _ => return true,
+        };
+
+        let handles = |symbol: &char| {
+            transitions
+                .iter()
+                .any(|transition| transition.matches(&[*symbol], program.blank))
+        };
+        if !alphabet.iter().all(handles) {
+            return true;
+        }
+
+        for transition in transitions {
+            if reachable.insert(transition.next_state.as_str()) {
+                queue.push_back(transition.next_state.as_str());
+            }
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_busy_beaver_halts() {
+        let program = parse(include_str!("../examples/busy-beaver-3.tur")).unwrap();
+
+        assert!(matches!(decide(&program, 1000), Decision::Halts(_)));
+        assert_eq!(decide(&program, 5), Decision::Unknown);
+    }
+
+    #[test]
+    fn test_no_halting_transition() {
+        let program = parse(
+            r#"
+name: Flip
+tape: _
+rules:
+  A:
+    _ -> 1, R, A
+    1 -> _, L, A
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            decide(&program, 1000),
+            Decision::NeverHalts(NonHalting::NoHaltingTransition)
+        );
+    }
+
+    #[test]
+    fn test_cycler() {
+        let program = parse(
+            r#"
+name: Ping Pong
+tape: _
+rules:
+  A:
+    _ -> 1, R, B
+    1 -> 1, R, B
+  B:
+    _ -> _, L, A
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            decide(&program, 1000),
+            Decision::NeverHalts(NonHalting::Cycler {
+                first_seen_step: 1,
+                period: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_translated_cycler() {
+        // Writes two cells, steps back over the first and moves on: the head drifts right by
+        // two cells every four steps.
+        let program = parse(
+            r#"
+name: Drift
+tape: _
+rules:
+  A:
+    _ -> 1, R, B
+  B:
+    _ -> 1, L, C
+  C:
+    1 -> 1, R, D
+  D:
+    1 -> 1, R, A
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            decide(&program, 1000),
+            Decision::NeverHalts(NonHalting::TranslatedCycler {
+                first_seen_step: 1,
+                period: 4,
+                shift: 2
+            })
+        );
+    }
+
+    #[test]
+    fn test_translated_cycler_to_the_left() {
+        let program = parse(
+            r#"
+name: Left
+tape: _
+rules:
+  A:
+    _ -> 1, L, B
+  B:
+    _ -> 0, L, A
+"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            decide(&program, 1000),
+            Decision::NeverHalts(NonHalting::TranslatedCycler { shift: -2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_bouncer() {
+        // Sweeps back and forth over a growing run of 1s, adding one on each side. It never
+        // reads the x that would halt it.
+        let program = parse(
+            r#"
+name: Bouncer
+tape: _
+rules:
+  A:
+    _ -> 1, R, B
+    1, L, A
+    x, R, halt
+  B:
+    _ -> 1, L, A
+    1, R, B
+  halt:
+"#,
+        )
+        .unwrap();
+
+        assert!(matches!(
+            decide(&program, 1000),
+            Decision::NeverHalts(NonHalting::Bouncer { growth: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_busy_beaver_bouncer() {
+        // One of the 4-state machines left unknown by the other deciders in busy beaver
+        // enumeration
+        let program = parse(
+            r#"
+name: 1RB1LA_1RC1RB_1RD---_1LD1RA
+tape: _
+rules:
+  A:
+    _ -> 1, R, B
+    1,      L, A
+  B:
+    _ -> 1, R, C
+    1,      R, B
+  C:
+    _ -> 1, R, D
+  D:
+    _ -> 1, L, D
+    1,      R, A
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(decide(&program, 20), Decision::Unknown);
+        assert_eq!(
+            decide(&program, 1000),
+            Decision::NeverHalts(NonHalting::Bouncer {
+                first_seen_step: 687,
+                growth: 4
+            })
+        );
+    }
+}
@@ -157,10 +157,14 @@ pub fn decode(encoded: &str) -> Result<Program, String> {
         name: name.to_string(),
         mode: Mode::default(),
         initial_state,
+        stop_states: Vec::new(),
+        accept_states: Vec::new(),
+        reject_states: Vec::new(),
         tapes: vec![initial_tape],
         heads: vec![0],
         blank: '_',
         rules,
+        source_map: None,
     })
 }
 
@@ -313,10 +317,14 @@ mod tests {
             name: "Test Program".to_string(),
             mode: Mode::default(),
             initial_state: "start".to_string(),
+            stop_states: Vec::new(),
+            accept_states: Vec::new(),
+            reject_states: Vec::new(),
             tapes: vec!["abb".to_string()],
             heads: vec![0],
             blank: '_',
             rules,
+            source_map: None,
         }
     }
 
@@ -429,10 +437,14 @@ mod tests {
             name: "Simple Example".to_string(),
             mode: Mode::default(),
             initial_state: "start".to_string(),
+            stop_states: Vec::new(),
+            accept_states: Vec::new(),
+            reject_states: Vec::new(),
             tapes: vec!["ab".to_string()],
             heads: vec![0],
             blank: '_',
             rules,
+            source_map: None,
         };
 
         let encoded = encode(&program);
@@ -0,0 +1,962 @@
+//! This module turns programs back into `.tur` source, in a normalized layout.
+//!
+//! `to_source` writes a parsed `Program`, while `format_source` rewrites source through its
+//! syntax tree, so it keeps comments, imports, symbol sets, macros and symbol variables as they
+//! were written. Both lay out the result the same way.
+
+use crate::parser::parse_symbol;
+use crate::syntax::{parse_syntax, SyntaxNode, SyntaxTree};
+use crate::types::{
+    Direction, Mode, Program, TuringMachineError, DEFAULT_BLANK_SYMBOL, INPUT_BLANK_SYMBOL,
+};
+use crate::Rule;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// How directions are written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DirectionStyle {
+    /// `L`, `R` and `S`.
+    #[default]
+    Letters,
+    /// `<`, `>` and `-`.
+    Arrows,
+}
+
+/// Options for formatting a program.
+#[derive(Debug, Default, Clone)]
+pub struct FormatOptions {
+    /// Whether to sort the states by name, after the initial state, instead of keeping them in
+    /// the order of the source.
+    pub sort_states: bool,
+    /// How directions are written.
+    pub directions: DirectionStyle,
+}
+
+/// Returns the `.tur` source of a program.
+///
+/// Programs don't keep the order of their states, so the initial state comes first and the
+/// others follow sorted by name. The transitions of each state are aligned on their arrows,
+/// reserved symbols are quoted and blanks are written as `_`.
+pub fn to_source(program: &Program, options: &FormatOptions) -> String {
+    write_layout(program_layout(program, options), Comments::default())
+}
+
+/// Formats the `.tur` source of a program, keeping its states in their original order unless
+/// `options.sort_states` is set.
+///
+/// Sections are kept as declared, even when they declare the default, such as `head: 0`.
+/// Comments are kept with the section, state or transition they precede, or follow on the same
+/// line. Comments at the end of a line are aligned within each state. Imports, symbol sets,
+/// macros and symbol variables are kept, with macros laid out like states.
+///
+/// Only the syntax of the source is checked, so programs with imports are formatted without
+/// loading them.
+///
+/// # Errors
+///
+/// Returns `TuringMachineError::ParseError` if the source doesn't match the grammar.
+pub fn format_source(source: &str, options: &FormatOptions) -> Result<String, TuringMachineError> {
+    let tree = parse_syntax(source)?;
+    let comments = attach_comments(&tree);
+
+    Ok(write_layout(tree_layout(&tree, options), comments))
+}
+
+/// What a comment is kept with. Sections, states, macros and transitions are identified by
+/// where they start in the source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Anchor {
+    /// A top-level section.
+    Section(usize),
+    /// A state in `rules:`, or a macro in `macros:`.
+    Block(usize),
+    /// A transition of a state or macro.
+    Transition(usize),
+    /// The end of a state or macro, after its transitions.
+    BlockEnd(usize),
+    /// The end of the program.
+    End,
+}
+
+/// The comments of a program, by what they're kept with.
+#[derive(Debug, Default)]
+struct Comments {
+    /// Comments on their own lines, before the line they're kept with.
+    leading: HashMap<Anchor, Vec<String>>,
+    /// Comments at the end of the line they're kept with.
+    trailing: HashMap<Anchor, String>,
+}
+
+/// Finds what each comment of a syntax tree is kept with.
+///
+/// A comment after code on the same line is kept with that line. Any other comment is kept with
+/// the next section, state, macro or transition, or the end of the state, macro or program it's
+/// in if there's none.
+fn attach_comments(tree: &SyntaxTree) -> Comments {
+    let blocks: Vec<&SyntaxNode> = blocks(tree).collect();
+    let mut anchors: Vec<(Anchor, &SyntaxNode)> = Vec::new();
+    for section in &tree.sections {
+        anchors.push((Anchor::Section(section.span.start), section));
+    }
+    for &block in &blocks {
+        anchors.push((Anchor::Block(block.span.start), block));
+        for action in block.children_of(Rule::action) {
+            anchors.push((Anchor::Transition(action.span.start), action));
+        }
+    }
+    anchors.sort_by_key(|(_, node)| node.span.start);
+
+    let source = tree.source();
+    let line = |offset: usize| tree.location(offset).line;
+    let mut comments = Comments::default();
+
+    for comment in &tree.comments {
+        let start = comment.span.start;
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+
+        if !source[line_start..start].trim().is_empty() {
+            // Transitions end on their line, and the other sections start on theirs
+            let same_line = anchors.iter().rev().find(|(anchor, node)| match anchor {
+                Anchor::Transition(_) => line(node.span.end) == line(start),
+                Anchor::Section(_) => line(node.span.start) == line(start),
+                _ => false,
+            });
+            if let Some((anchor, _)) = same_line {
+                comments
+                    .trailing
+                    .insert(anchor.clone(), comment.text.clone());
+                continue;
+            }
+
+            // Inside a section spanning several lines, such as `tapes:`
+            let inside = anchors.iter().rev().find(|(anchor, node)| {
+                matches!(anchor, Anchor::Section(_)) && node.span.contains(&start)
+            });
+            if let Some((anchor, _)) = inside {
+                let leading = comments.leading.entry(anchor.clone()).or_default();
+                leading.push(comment.text.clone());
+                continue;
+            }
+        }
+
+        let next = anchors.iter().find(|(_, node)| node.span.start > start);
+        let block = blocks.iter().find(|block| block.span.contains(&start));
+        let anchor = match (next, block) {
+            (Some((anchor, node)), Some(block)) if block.span.contains(&node.span.start) => {
+                anchor.clone()
+            }
+            (_, Some(block)) => Anchor::BlockEnd(block.span.start),
+            (Some((anchor, _)), None) => anchor.clone(),
+            (None, None) => Anchor::End,
+        };
+        let leading = comments.leading.entry(anchor).or_default();
+        leading.push(comment.text.clone());
+    }
+
+    comments
+}
+
+/// Returns the macros and states of a syntax tree, in source order.
+fn blocks(tree: &SyntaxTree) -> impl Iterator<Item = &SyntaxNode> {
+    let macros = tree
+        .section(Rule::macros)
+        .into_iter()
+        .flat_map(|macros| macros.children_of(Rule::macro_def));
+
+    macros.chain(tree.states())
+}
+
+/// A program as it's written, before its comments are added.
+struct Layout {
+    /// The sections before `macros:` and `rules:`, in order, each with its lines. A section
+    /// without lines, such as the default mode of a `Program`, is left out.
+    sections: Vec<(Anchor, Vec<String>)>,
+    /// The `macros:` section and its macros, if any.
+    macros: Option<(Anchor, Vec<Block>)>,
+    /// The `rules:` section and its states.
+    rules: (Anchor, Vec<Block>),
+}
+
+/// A state or macro as it's written.
+struct Block {
+    /// Where the block starts, which its comments are kept with.
+    start: usize,
+    /// The header, e.g. `start:`.
+    header: String,
+    rows: Vec<Row>,
+}
+
+/// A transition as it's written.
+struct Row {
+    /// Where the transition starts, which its comments are kept with.
+    start: usize,
+    read: String,
+    /// The symbols written, unless they're the symbols read.
+    write: Option<String>,
+    /// The directions and the next state.
+    rest: String,
+}
+
+/// Returns the layout of a program, with its states in the order of `sorted_states`. Programs
+/// have no comments, so their lines are only numbered in place of their source positions.
+fn program_layout(program: &Program, options: &FormatOptions) -> Layout {
+    let symbols = |symbols: &[char]| -> Vec<String> {
+        symbols
+            .iter()
+            .map(|&symbol| format_symbol(symbol, program.blank))
+            .collect()
+    };
+    let list = |items: Vec<String>| list(items, program.is_single_tape());
+    let mut sections = Vec::new();
+    let mut section = |lines: Vec<String>| {
+        sections.push((Anchor::Section(sections.len()), lines));
+    };
+
+    section(vec![format!("name: {}", program.name)]);
+    section(match program.mode {
+        Mode::Normal => Vec::new(),
+        Mode::Strict => vec!["mode: strict".to_string()],
+        Mode::Nondeterministic => vec!["mode: nondeterministic".to_string()],
+    });
+
+    // Heads at 0 and the default blank are left out
+    if program.heads.iter().any(|&head| head != 0) {
+        let heads: Vec<String> = program.heads.iter().map(usize::to_string).collect();
+        section(heads_lines(&heads, program.is_single_tape()));
+    }
+    if program.blank != DEFAULT_BLANK_SYMBOL {
+        section(blank_lines(program.blank));
+    }
+
+    // An empty tape is written as a single blank, which runs the same
+    let tapes: Vec<String> = program
+        .tapes()
+        .iter()
+        .map(|tape| {
+            if tape.is_empty() {
+                INPUT_BLANK_SYMBOL.to_string()
+            } else {
+                symbols(tape).join(", ")
+            }
+        })
+        .collect();
+    section(tapes_lines(tapes));
+
+    let states = sorted_states(program);
+    let mut declarations = Vec::new();
+    if states.first() != Some(&program.initial_state.as_str()) {
+        declarations.push(format!("  start: {}", program.initial_state));
+    }
+    for (name, states) in [
+        ("stop", &program.stop_states),
+        ("accept", &program.accept_states),
+        ("reject", &program.reject_states),
+    ] {
+        if !states.is_empty() {
+            declarations.push(format!("  {name}: {}", states.join(", ")));
+        }
+    }
+    section(states_lines(declarations));
+
+    let mut transitions = 0;
+    let blocks = states
+        .iter()
+        .enumerate()
+        .map(|(i, state)| Block {
+            start: i,
+            header: format!("{state}:"),
+            rows: program.rules[*state]
+                .iter()
+                .map(|transition| {
+                    transitions += 1;
+                    let directions = transition
+                        .directions
+                        .iter()
+                        .map(|&direction| {
+                            format_direction(direction, options.directions).to_string()
+                        })
+                        .collect();
+                    Row {
+                        start: transitions,
+                        read: list(symbols(&transition.read)),
+                        write: (transition.write != transition.read)
+                            .then(|| list(symbols(&transition.write))),
+                        rest: format!("{}, {}", list(directions), transition.next_state),
+                    }
+                })
+                .collect(),
+        })
+        .collect();
+
+    Layout {
+        rules: (Anchor::Section(sections.len()), blocks),
+        sections,
+        macros: None,
+    }
+}
+
+/// Returns the layout of a syntax tree, with the sections in a fixed order and the states in
+/// source order, or sorted by `options`. Macros keep their source order.
+fn tree_layout(tree: &SyntaxTree, options: &FormatOptions) -> Layout {
+    let blank = tree
+        .section(Rule::blank)
+        .and_then(|blank| blank.child(Rule::symbol))
+        .map_or(DEFAULT_BLANK_SYMBOL, |symbol| parse_symbol(&symbol.text));
+    let single_tape = tree
+        .section(Rule::tapes)
+        .is_none_or(|tapes| tapes.children_of(Rule::symbols).count() == 1);
+    let texts = |node: &SyntaxNode, kind: Rule| -> Vec<String> {
+        node.children_of(kind)
+            .map(|child| child.text.trim().to_string())
+            .collect()
+    };
+    let symbols = |node: &SyntaxNode| -> Vec<String> {
+        node.children_of(Rule::symbol)
+            .map(|symbol| format_symbol(parse_symbol(&symbol.text), blank))
+            .collect()
+    };
+
+    // Sections of the same kind, such as imports, keep their order
+    let order = [
+        Rule::name,
+        Rule::import,
+        Rule::mode,
+        Rule::head,
+        Rule::blank,
+        Rule::tape,
+        Rule::states,
+        Rule::symbol_sets,
+    ];
+    let mut sections: Vec<&SyntaxNode> = tree
+        .sections
+        .iter()
+        .filter(|section| !matches!(section.kind, Rule::macros | Rule::rules))
+        .collect();
+    sections.sort_by_key(|section| {
+        let kind = match section.kind {
+            Rule::heads => Rule::head,
+            Rule::tapes => Rule::tape,
+            kind => kind,
+        };
+        order.iter().position(|&k| k == kind)
+    });
+
+    let sections = sections
+        .into_iter()
+        .map(|section| {
+            let lines = match section.kind {
+                Rule::name | Rule::mode => {
+                    let value = section.child(Rule::string).map_or("", |s| s.text.trim());
+                    let key = if section.kind == Rule::name {
+                        "name"
+                    } else {
+                        "mode"
+                    };
+                    vec![format!("{key}: {value}")]
+                }
+                Rule::import => {
+                    let path = section.child(Rule::path).map_or("", |p| p.text.as_str());
+                    let namespace = section
+                        .child(Rule::namespace)
+                        .map_or("", |n| n.text.as_str());
+                    vec![format!("import \"{path}\" as {namespace}")]
+                }
+                Rule::head | Rule::heads => heads_lines(&texts(section, Rule::index), single_tape),
+                Rule::blank => blank_lines(blank),
+                Rule::tape | Rule::tapes => tapes_lines(
+                    section
+                        .children_of(Rule::symbols)
+                        .map(|tape| symbols(tape).join(", "))
+                        .collect(),
+                ),
+                Rule::states => states_lines(
+                    section
+                        .children
+                        .iter()
+                        .map(|declaration| {
+                            let key = match declaration.kind {
+                                Rule::state_start => "start",
+                                Rule::state_stop => "stop",
+                                Rule::state_accept => "accept",
+                                _ => "reject",
+                            };
+                            let states = texts(declaration, Rule::state).join(", ");
+                            format!("  {key}: {states}")
+                        })
+                        .collect(),
+                ),
+                Rule::symbol_sets => {
+                    let sets: Vec<String> = section
+                        .children_of(Rule::symbol_set)
+                        .map(|set| {
+                            let name = set.child(Rule::set_name).map_or("", |n| n.text.as_str());
+                            let symbols = set.child(Rule::symbols).map(symbols).unwrap_or_default();
+                            format!("{name} = {}", symbols.join("|"))
+                        })
+                        .collect();
+                    match sets.as_slice() {
+                        [set] => vec![format!("symbols: {set}")],
+                        _ => std::iter::once("symbols:".to_string())
+                            .chain(sets.iter().map(|set| format!("  {set}")))
+                            .collect(),
+                    }
+                }
+                _ => Vec::new(),
+            };
+            (Anchor::Section(section.span.start), lines)
+        })
+        .collect();
+
+    let block = |node: &SyntaxNode, header: String| Block {
+        start: node.span.start,
+        header,
+        rows: node
+            .children_of(Rule::action)
+            .map(|action| tree_row(action, blank, options))
+            .collect(),
+    };
+
+    let macros = tree.section(Rule::macros).map(|macros| {
+        let blocks = macros
+            .children_of(Rule::macro_def)
+            .map(|definition| {
+                let name = definition
+                    .child(Rule::state)
+                    .map_or("", |s| s.text.as_str());
+                let params = definition
+                    .child(Rule::macro_params)
+                    .map(|params| texts(params, Rule::state).join(", "))
+                    .unwrap_or_default();
+                block(definition, format!("{name}({params}):"))
+            })
+            .collect();
+        (Anchor::Section(macros.span.start), blocks)
+    });
+
+    let mut states: Vec<&SyntaxNode> = tree.states().collect();
+    if options.sort_states {
+        // The initial state stays first
+        let initial = tree
+            .section(Rule::states)
+            .and_then(|states| states.child(Rule::state_start))
+            .and_then(|start| start.child(Rule::state))
+            .map(|state| state.text.trim().to_string())
+            .or_else(|| states.first().and_then(|state| state.name()));
+        states.sort_by_key(|state| {
+            let name = state.name();
+            (name != initial, name)
+        });
+    }
+    let rules = tree
+        .section(Rule::rules)
+        .map_or(0, |rules| rules.span.start);
+    let blocks = states
+        .into_iter()
+        .map(|state| block(state, format!("{}:", state.name().unwrap_or_default())))
+        .collect();
+
+    Layout {
+        sections,
+        macros,
+        rules: (Anchor::Section(rules), blocks),
+    }
+}
+
+/// Returns the row of an action node.
+fn tree_row(action: &SyntaxNode, blank: char, options: &FormatOptions) -> Row {
+    let symbol = |node: &SyntaxNode| match node.kind {
+        Rule::symbol => format_symbol(parse_symbol(&node.text), blank),
+        _ => node.text.clone(),
+    };
+    let direction = |node: &SyntaxNode| {
+        let direction = match node.text.as_str() {
+            "<" | "L" => Direction::Left,
+            ">" | "R" => Direction::Right,
+            _ => Direction::Stay,
+        };
+        format_direction(direction, options.directions).to_string()
+    };
+
+    // Rule: action > (single_tape_action > [read, write?, direction, next_state]
+    //                 | multi_tape_action > [reads, writes?, directions, next_state])
+    let action = &action.children[0];
+    let single_tape = action.kind == Rule::single_tape_action;
+    let (next_state, items) = action.children.split_last().unwrap();
+    let (directions, items) = items.split_last().unwrap();
+    let list = |node: &SyntaxNode, item: &dyn Fn(&SyntaxNode) -> String| {
+        if single_tape {
+            item(node)
+        } else {
+            list(node.children.iter().map(item).collect(), false)
+        }
+    };
+
+    let read = list(&items[0], &symbol);
+    let write = items.get(1).map(|write| list(write, &symbol));
+    Row {
+        start: action.span.start,
+        write: write.filter(|write| *write != read),
+        read,
+        rest: format!(
+            "{}, {}",
+            list(directions, &direction),
+            next_state.text.replace(' ', "")
+        ),
+    }
+}
+
+/// Returns the `head:` or `heads:` line.
+fn heads_lines(heads: &[String], single_tape: bool) -> Vec<String> {
+    if let (true, [head]) = (single_tape, heads) {
+        vec![format!("head: {head}")]
+    } else {
+        vec![format!("heads: [{}]", heads.join(", "))]
+    }
+}
+
+/// Returns the `blank:` line.
+fn blank_lines(blank: char) -> Vec<String> {
+    vec![format!("blank: {}", quote(blank))]
+}
+
+/// Returns the `tape:` line of a single tape, or the `tapes:` lines of several.
+fn tapes_lines(tapes: Vec<String>) -> Vec<String> {
+    match tapes.as_slice() {
+        [tape] => vec![format!("tape: {tape}")],
+        _ => std::iter::once("tapes:".to_string())
+            .chain(tapes.iter().map(|tape| format!("  [{tape}]")))
+            .collect(),
+    }
+}
+
+/// Returns the `states:` lines, or none without declarations.
+fn states_lines(declarations: Vec<String>) -> Vec<String> {
+    if declarations.is_empty() {
+        return declarations;
+    }
+
+    std::iter::once("states:".to_string())
+        .chain(declarations)
+        .collect()
+}
+
+/// Returns the states of a program, with the initial state first and the others sorted by name.
+fn sorted_states(program: &Program) -> Vec<&str> {
+    let mut states: Vec<&str> = program.rules.keys().map(String::as_str).collect();
+    states.sort_by_key(|&state| (state != program.initial_state, state));
+    states
+}
+
+/// Writes lines of source with the comments kept with them.
+struct Writer {
+    source: String,
+    comments: Comments,
+    /// Comments kept with lines that aren't written, to write before the next line.
+    pending: Vec<String>,
+}
+
+impl Writer {
+    /// Writes a line, after its leading comments, and followed by its trailing comment padded
+    /// to `width` characters.
+    fn line(&mut self, anchor: &Anchor, indent: &str, text: &str, width: usize) {
+        self.leading(anchor, indent);
+
+        match self.comments.trailing.remove(anchor) {
+            Some(comment) => writeln!(self.source, "{indent}{text:width$}  {comment}").unwrap(),
+            None => writeln!(self.source, "{indent}{text}").unwrap(),
+        }
+    }
+
+    /// Writes the comments kept with a line before it, and any pending comments.
+    fn leading(&mut self, anchor: &Anchor, indent: &str) {
+        let leading = self.comments.leading.remove(anchor).unwrap_or_default();
+        for comment in self.pending.drain(..).chain(leading) {
+            writeln!(self.source, "{indent}{comment}").unwrap();
+        }
+    }
+
+    /// Keeps the comments of a line that isn't written for the next line.
+    fn skip(&mut self, anchor: &Anchor) {
+        let leading = self.comments.leading.remove(anchor).unwrap_or_default();
+        self.pending.extend(leading);
+        self.pending.extend(self.comments.trailing.remove(anchor));
+    }
+
+    /// Checks if a line has a trailing comment.
+    fn has_trailing(&self, anchor: &Anchor) -> bool {
+        self.comments.trailing.contains_key(anchor)
+    }
+
+    /// Writes a section of blocks, such as `rules:`, with the transitions of each block aligned.
+    fn blocks(&mut self, section: &Anchor, name: &str, blocks: &[Block]) {
+        // A comment can't come first in a section of blocks, so the first block's comments go
+        // before it
+        if let Some(first) = blocks.first() {
+            self.skip(section);
+            self.skip(&Anchor::Block(first.start));
+        }
+        self.line(section, "", name, 0);
+
+        for block in blocks {
+            self.line(&Anchor::Block(block.start), "  ", &block.header, 0);
+
+            // Only the reads followed by an arrow are padded, so the arrows line up
+            let reads = block
+                .rows
+                .iter()
+                .filter(|row| row.write.is_some())
+                .map(|row| row.read.chars().count())
+                .max()
+                .unwrap_or(0);
+            let heads: Vec<String> = block
+                .rows
+                .iter()
+                .map(|row| match &row.write {
+                    Some(write) => format!("{:reads$} -> {write},", row.read),
+                    None => format!("{},", row.read),
+                })
+                .collect();
+
+            let width = heads
+                .iter()
+                .map(|head| head.chars().count())
+                .max()
+                .unwrap_or(0);
+            let lines: Vec<String> = heads
+                .iter()
+                .zip(&block.rows)
+                .map(|(head, row)| format!("{head:width$} {}", row.rest))
+                .collect();
+
+            // Comments at the end of the lines line up after the longest line with one
+            let anchors: Vec<Anchor> = block
+                .rows
+                .iter()
+                .map(|row| Anchor::Transition(row.start))
+                .collect();
+            let width = lines
+                .iter()
+                .zip(&anchors)
+                .filter(|(_, anchor)| self.has_trailing(anchor))
+                .map(|(line, _)| line.chars().count())
+                .max()
+                .unwrap_or(0);
+            for (line, anchor) in lines.iter().zip(&anchors) {
+                self.line(anchor, "    ", line, width);
+            }
+            self.leading(&Anchor::BlockEnd(block.start), "    ");
+        }
+    }
+}
+
+/// Writes the layout of a program, with the comments kept with its lines.
+fn write_layout(layout: Layout, comments: Comments) -> String {
+    let mut writer = Writer {
+        source: String::new(),
+        comments,
+        pending: Vec::new(),
+    };
+
+    for (anchor, lines) in &layout.sections {
+        let Some((first, rest)) = lines.split_first() else {
+            writer.skip(anchor);
+            continue;
+        };
+
+        writer.line(anchor, "", first, 0);
+        for line in rest {
+            writeln!(writer.source, "{line}").unwrap();
+        }
+    }
+
+    if let Some((anchor, macros)) = &layout.macros {
+        writer.blocks(anchor, "macros:", macros);
+    }
+    let (anchor, states) = &layout.rules;
+    writer.blocks(anchor, "rules:", states);
+
+    writer.leading(&Anchor::End, "  ");
+    writer.source
+}
+
+/// Joins the items of a transition, in brackets for multi-tape programs.
+fn list(items: Vec<String>, single_tape: bool) -> String {
+    if single_tape {
+        items.concat()
+    } else {
+        format!("[{}]", items.join(", "))
+    }
+}
+
+/// Returns how a symbol is written, with `_` for blank.
+fn format_symbol(symbol: char, blank: char) -> String {
+    if symbol == blank {
+        INPUT_BLANK_SYMBOL.to_string()
+    } else {
+        quote(symbol)
+    }
+}
+
+/// Returns a symbol, in quotes if it's a reserved character.
+fn quote(symbol: char) -> String {
+    match symbol {
+        '#' | ' ' | ',' | '>' | '<' | '[' | ']' | '|' | '$' => format!("'{symbol}'"),
+        _ => symbol.to_string(),
+    }
+}
+
+/// Returns how a direction is written.
+fn format_direction(direction: Direction, style: DirectionStyle) -> char {
+    match (direction, style) {
+        (Direction::Left, DirectionStyle::Letters) => 'L',
+        (Direction::Right, DirectionStyle::Letters) => 'R',
+        (Direction::Stay, DirectionStyle::Letters) => 'S',
+        (Direction::Left, DirectionStyle::Arrows) => '<',
+        (Direction::Right, DirectionStyle::Arrows) => '>',
+        (Direction::Stay, DirectionStyle::Arrows) => '-',
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_to_source_round_trip() {
+        let sources = [
+            include_str!("../examples/binary-addition.tur"),
+            include_str!("../examples/busy-beaver-3.tur"),
+            include_str!("../examples/even-zeros-and-ones.tur"),
+            include_str!("../examples/multi-tape-addition.tur"),
+            include_str!("../examples/multi-tape-compare.tur"),
+            include_str!("../examples/nondeterministic-substring.tur"),
+            include_str!("../examples/palindrome.tur"),
+            include_str!("../examples/subtraction.tur"),
+        ];
+
+        for source in sources {
+            let program = parse(source).unwrap();
+            let formatted = to_source(&program, &FormatOptions::default());
+            assert_eq!(parse(&formatted).unwrap(), program, "{formatted}");
+        }
+    }
+
+    #[test]
+    fn test_format_source() {
+        let source = r#"
+name: Format
+blank: '#'
+tape: a,'|',_
+states:
+  accept: done
+rules:
+  start:
+    a->b,>,start
+    '|' , >, start
+    _ -> '$', -, next
+  next:
+    *, <, done
+"#;
+        let expected = r#"name: Format
+blank: '#'
+tape: a, '|', _
+states:
+  accept: done
+rules:
+  start:
+    a -> b,   R, start
+    '|',      R, start
+    _ -> '$', S, next
+  next:
+    *, L, done
+"#;
+
+        let formatted = format_source(source, &FormatOptions::default()).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(
+            format_source(&formatted, &FormatOptions::default()).unwrap(),
+            expected
+        );
+
+        let options = FormatOptions {
+            sort_states: true,
+            directions: DirectionStyle::Arrows,
+        };
+        let formatted = format_source(source, &options).unwrap();
+        assert!(formatted.contains("    a -> b,   >, start\n"));
+        assert!(formatted.contains("    *, <, done\n"));
+    }
+
+    #[test]
+    fn test_format_keeps_state_order() {
+        let source = r#"name: Order
+tape: a
+rules:
+  c:
+    a, R, b
+  b:
+    a, R, a
+  a:
+    a, L, c
+"#;
+
+        let formatted = format_source(source, &FormatOptions::default()).unwrap();
+        assert_eq!(formatted, source);
+
+        // The initial state stays first when sorting
+        let options = FormatOptions {
+            sort_states: true,
+            ..FormatOptions::default()
+        };
+        let formatted = format_source(source, &options).unwrap();
+        let position = |state: &str| formatted.find(&format!("  {state}:\n")).unwrap();
+        assert!(position("c") < position("a") && position("a") < position("b"));
+        assert_eq!(parse(&formatted).unwrap(), parse(source).unwrap());
+    }
+
+    #[test]
+    fn test_format_keeps_macros_and_variables() {
+        let source = r#"name: Macros
+symbols:   ab = a,b
+import "lib.tur"   as lib
+tape: a, b
+macros:
+  skip( done ):
+    ab,R,skip(done)
+    _ ,L, done
+rules:
+  start:
+    $x->$x, R, mark
+    ab, R, skip( end )
+  mark:
+    $x -> ' ', R, lib.halt
+  end:
+"#;
+        let expected = r#"name: Macros
+import "lib.tur" as lib
+tape: a, b
+symbols: ab = a|b
+macros:
+  skip(done):
+    ab, R, skip(done)
+    _,  L, done
+rules:
+  start:
+    $x, R, mark
+    ab, R, skip(end)
+  mark:
+    $x -> _, R, lib.halt
+  end:
+"#;
+
+        let formatted = format_source(source, &FormatOptions::default()).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(
+            format_source(&formatted, &FormatOptions::default()).unwrap(),
+            formatted
+        );
+
+        // Only the syntax is checked, so the import isn't loaded
+        assert!(format_source(
+            "name: Bad\ntape: a\nrules:\n  start:\n    a b\n",
+            &FormatOptions::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_examples_are_formatted() {
+        let sources = [
+            include_str!("../examples/binary-addition.tur"),
+            include_str!("../examples/busy-beaver-3.tur"),
+            include_str!("../examples/even-zeros-and-ones.tur"),
+            include_str!("../examples/event-number-checker.tur"),
+            include_str!("../examples/multi-tape-addition.tur"),
+            include_str!("../examples/multi-tape-compare.tur"),
+            include_str!("../examples/multi-tape-copy.tur"),
+            include_str!("../examples/multi-tape-example.tur"),
+            include_str!("../examples/nondeterministic-substring.tur"),
+            include_str!("../examples/palindrome.tur"),
+            include_str!("../examples/subtraction.tur"),
+        ];
+
+        for source in sources {
+            let formatted = format_source(source, &FormatOptions::default()).unwrap();
+            assert_eq!(formatted, source);
+        }
+    }
+
+    #[test]
+    fn test_format_keeps_comments() {
+        let source = r#"# A program with comments
+name: Comments
+head: 0  # the default
+tape: a, b
+rules:
+  start:
+    a -> x, R, start  # mark
+    # b is kept
+    b, R, end
+  # The end
+  end:
+    # Nothing to do
+"#;
+        let expected = r#"# A program with comments
+name: Comments
+head: 0  # the default
+tape: a, b
+rules:
+  start:
+    a -> x, R, start  # mark
+    # b is kept
+    b,      R, end
+  # The end
+  end:
+    # Nothing to do
+"#;
+
+        let formatted = format_source(source, &FormatOptions::default()).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(
+            format_source(&formatted, &FormatOptions::default()).unwrap(),
+            expected
+        );
+
+        // Sections keep their comments when they're moved, even when they declare the default
+        let source = r#"name: Sections
+# The input
+tape: a, b
+
+# The blank symbol
+blank: ' '
+rules:
+  start:
+    a, R, start
+"#;
+        let expected = r#"name: Sections
+# The blank symbol
+blank: ' '
+# The input
+tape: a, b
+rules:
+  start:
+    a, R, start
+"#;
+        assert_eq!(
+            format_source(source, &FormatOptions::default()).unwrap(),
+            expected
+        );
+
+        // Sorting moves the comments with their states
+        let options = FormatOptions {
+            sort_states: true,
+            ..FormatOptions::default()
+        };
+        let source = "name: Sort\ntape: a\nrules:\n  b:\n    a, R, a\n  # Before a\n  a:\n    a, L, b  # back\n";
+        let formatted = format_source(source, &options).unwrap();
+        assert!(formatted.contains("  # Before a\n  a:\n    a, L, b  # back\n"));
+        assert!(parse(&formatted).is_ok());
+    }
+}
@@ -0,0 +1,502 @@
+//! This module provides an abstract interpretation of a program's heads: for each state reachable
+//! from the initial state, the symbols that can be under each head, and whether each head can be
+//! left or right of the initial content of its tape.
+//!
+//! Every position of the initial content is tracked on its own, while the cells left and right of
+//! it are each summarized as one place holding the blank symbol. A symbol a transition writes can
+//! later be read at the place it was written to, and a head which stays reads exactly the symbol
+//! it wrote. The analysis over-approximates: a symbol it lists may never actually be read,
+//! but a symbol it leaves out never is.
+//!
+//! It also finds states that loop forever once they read a blank past the written part of the
+//! tapes, like a right-sweep that has no rule for the blank at the end of the input.
+
+use crate::types::{Direction, Mode, Program, Transition};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+/// A place of a head on a tape, relative to the tape's initial content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Place {
+    /// Left of the initial content.
+    Before,
+    /// At a position of the initial content.
+    At(usize),
+    /// Right of the initial content.
+    After,
+}
+
+/// What a state knows about a tape: the places its head can be at, with the symbols under it
+/// there, and the symbols that may have been written to each place.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct TapeValue {
+    cells: BTreeSet<(Place, char)>,
+    written: BTreeSet<(Place, char)>,
+}
+
+/// What a state knows about every tape, and the symbols read by the transitions entering it.
+#[derive(Debug, Clone, Default, PartialEq)]
+struct StateValue {
+    tapes: Vec<TapeValue>,
+    entries: BTreeSet<Vec<char>>,
+}
+
+/// What a state knows about one of its heads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadSummary {
+    /// The symbols that can be under the head, sorted.
+    pub symbols: Vec<char>,
+    /// Whether the head can be left of the initial content of its tape.
+    pub left_of_input: bool,
+    /// Whether the head can be right of the initial content of its tape.
+    pub right_of_input: bool,
+}
+
+/// What the analysis knows about a state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateHeads {
+    /// The name of the state.
+    pub state: String,
+    /// What the state knows about each head, one per tape.
+    pub heads: Vec<HeadSummary>,
+    /// Whether the state is only entered by transitions that read a blank from every tape. The
+    /// initial state is entered when the machine starts, so it never is.
+    pub entered_on_blank: bool,
+    /// Whether the state can read a blank past the initial content of every tape it sweeps and,
+    /// once it does past the written part of the tapes, loops forever: every transition it then
+    /// takes reads a blank again, so the machine never halts.
+    pub loops_on_blank: bool,
+}
+
+/// The results of the head analysis of a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadAnalysis {
+    /// The reachable states, in the order they are reached from the initial state.
+    pub states: Vec<StateHeads>,
+}
+
+impl HeadAnalysis {
+    /// Returns what the analysis knows about a state, if it's reachable.
+    pub fn state(&self, state: &str) -> Option<&StateHeads> {
+        self.states.iter().find(|heads| heads.state == state)
+    }
+
+    /// Checks if any head can run off the left end of the initial content of its tape.
+    pub fn runs_off_left(&self) -> bool {
+        self.states
+            .iter()
+            .any(|state| state.heads.iter().any(|head| head.left_of_input))
+    }
+
+    /// Returns the states that loop forever once they read a blank.
+    pub fn blank_loops(&self) -> impl Iterator<Item = &StateHeads> {
+        self.states.iter().filter(|state| state.loops_on_blank)
+    }
+}
+
+/// Computes what symbols can be under each head of `program` in each reachable state, and where
+/// the heads can be.
+///
+/// Transitions are taken in the order they are defined, except in `Mode::Nondeterministic`, where
+/// every matching transition is taken. Stop states halt as soon as they are entered, so their
+/// transitions aren't followed.
+pub fn head_analysis(program: &Program) -> HeadAnalysis {
+    let contents: Vec<Vec<char>> = program
+        .tapes
+        .iter()
+        .map(|tape| tape.chars().collect())
+        .collect();
+
+    let initial = StateValue {
+        tapes: contents
+            .iter()
+            .zip(&program.heads)
+            .map(|(content, &head)| {
+                let place = if head < content.len() {
+                    Place::At(head)
+                } else {
+                    Place::After
+                };
+                TapeValue {
+                    cells: BTreeSet::from([(place, symbol_at(content, place, program.blank))]),
+                    written: BTreeSet::new(),
+                }
+            })
+            .collect(),
+        entries: BTreeSet::new(),
+    };
+
+    let mut order = vec![program.initial_state.clone()];
+    let mut values = BTreeMap::from([(program.initial_state.clone(), initial)]);
+    let mut queue = VecDeque::from([program.initial_state.clone()]);
+
+    while let Some(state) = queue.pop_front() {
+        let Some(transitions) = program.rules.get(&state) else {
+            continue;
+        };
+        if program.is_stop_state(&state) {
+            continue;
+        }
+
+        let value = values[&state].clone();
+        for (transition, reads) in taken_transitions(program, transitions, &value) {
+            let next = step(program, &contents, &value, transition, &reads);
+
+            let target = values
+                .entry(transition.next_state.clone())
+                .or_insert_with(|| {
+                    order.push(transition.next_state.clone());
+                    StateValue {
+                        tapes: vec![TapeValue::default(); contents.len()],
+                        entries: BTreeSet::new(),
+                    }
+                });
+
+            if join(target, next) && !queue.contains(&transition.next_state) {
+                queue.push_back(transition.next_state.clone());
+            }
+        }
+    }
+
+    let states = order
+        .into_iter()
+        .map(|state| {
+            let value = &values[&state];
+            let heads: Vec<HeadSummary> = value
+                .tapes
+                .iter()
+                .map(|tape| HeadSummary {
+                    symbols: tape
+                        .cells
+                        .iter()
+                        .map(|&(_, symbol)| symbol)
+                        .collect::<BTreeSet<char>>()
+                        .into_iter()
+                        .collect(),
+                    left_of_input: tape.cells.iter().any(|(place, _)| *place == Place::Before),
+                    right_of_input: tape.cells.iter().any(|(place, _)| *place == Place::After),
+                })
+                .collect();
+
+            // The heads must be able to read a blank past the input in the direction they sweep
+            let loops_on_blank = blank_loop(program, &state).is_some_and(|directions| {
+                value.tapes.iter().zip(directions).all(|(tape, direction)| {
+                    tape.cells.iter().any(|&(place, symbol)| {
+                        symbol == program.blank
+                            && match direction {
+                                Some(Direction::Left) => place == Place::Before,
+                                Some(Direction::Right) => place == Place::After,
+                                _ => true,
+                            }
+                    })
+                })
+            });
+            let blank = vec![program.blank; heads.len()];
+
+            StateHeads {
+                entered_on_blank: state != program.initial_state
+                    && value.entries.iter().all(|entry| *entry == blank),
+                loops_on_blank,
+                heads,
+                state,
+            }
+        })
+        .collect();
+
+    HeadAnalysis { states }
+}
+
+/// Returns the symbol at a place of a tape with the given initial content.
+fn symbol_at(content: &[char], place: Place, blank: char) -> char {
+    match place {
+        Place::At(position) => content[position],
+        Place::Before | Place::After => blank,
+    }
+}
+
+/// Returns the places a head at `place` can be at after moving in `direction`.
+fn moved(place: Place, direction: Direction, len: usize) -> Vec<Place> {
+    // The first and last positions of the content, if it isn't empty
+    let first = if len > 0 { Place::At(0) } else { Place::After };
+    let last = len.checked_sub(1).map_or(Place::Before, Place::At);
+
+    match (place, direction) {
+        (place, Direction::Stay) => vec![place],
+        (Place::Before, Direction::Left) => vec![Place::Before],
+        (Place::Before, Direction::Right) => vec![Place::Before, first],
+        (Place::At(0), Direction::Left) => vec![Place::Before],
+        (Place::At(position), Direction::Left) => vec![Place::At(position - 1)],
+        (Place::At(position), Direction::Right) if position + 1 < len => {
+            vec![Place::At(position + 1)]
+        }
+        (Place::At(_), Direction::Right) => vec![Place::After],
+        (Place::After, Direction::Left) => vec![Place::After, last],
+        (Place::After, Direction::Right) => vec![Place::After],
+    }
+}
+
+/// Returns the transitions a state can take, each with the combinations of symbols it's taken for.
+fn taken_transitions<'a>(
+    program: &Program,
+    transitions: &'a [Transition],
+    value: &StateValue,
+) -> Vec<(&'a Transition, Vec<Vec<char>>)> {
+    let symbols: Vec<BTreeSet<char>> = value
+        .tapes
+        .iter()
+        .map(|tape| tape.cells.iter().map(|&(_, symbol)| symbol).collect())
+        .collect();
+    let combinations = symbols
+        .iter()
+        .fold(vec![Vec::new()], |combinations, symbols| {
+            combinations
+                .iter()
+                .flat_map(|combination| {
+                    symbols.iter().map(move |&symbol| {
+                        let mut combination: Vec<char> = combination.clone();
+                        combination.push(symbol);
+                        combination
+                    })
+                })
+                .collect()
+        });
+
+    let mut taken: Vec<Vec<Vec<char>>> = vec![Vec::new(); transitions.len()];
+    for combination in combinations {
+        let mut matching = transitions
+            .iter()
+            .enumerate()
+            .filter(|(_, transition)| transition.matches(&combination, program.blank));
+
+        if program.mode == Mode::Nondeterministic {
+            for (i, _) in matching {
+                taken[i].push(combination.clone());
+            }
+        } else if let Some((i, _)) = matching.next() {
+            taken[i].push(combination);
+        }
+    }
+
+    transitions
+        .iter()
+        .zip(taken)
+        .filter(|(_, reads)| !reads.is_empty())
+        .collect()
+}
+
+/// Returns what the next state of a transition knows after the transition is taken for `reads`.
+fn step(
+    program: &Program,
+    contents: &[Vec<char>],
+    value: &StateValue,
+    transition: &Transition,
+    reads: &[Vec<char>],
+) -> StateValue {
+    let tapes = value
+        .tapes
+        .iter()
+        .enumerate()
+        .map(|(i, tape)| {
+            // What the transition writes to this tape for each symbol it's taken for
+            let writes: BTreeMap<char, char> = reads
+                .iter()
+                .map(|symbols| (symbols[i], transition.written(symbols, program.blank)[i]))
+                .collect();
+            let cells: Vec<(Place, char, char)> = tape
+                .cells
+                .iter()
+                .filter_map(|&(place, symbol)| Some((place, symbol, *writes.get(&symbol)?)))
+                .collect();
+
+            // Writing back the symbol that was read leaves the tape as it was
+            let mut written = tape.written.clone();
+            written.extend(
+                cells
+                    .iter()
+                    .filter(|(_, symbol, write)| symbol != write)
+                    .map(|&(place, _, write)| (place, write)),
+            );
+
+            let direction = transition.directions[i];
+            let mut next = BTreeSet::new();
+            for &(place, _, write) in &cells {
+                if direction == Direction::Stay {
+                    next.insert((place, write));
+                    continue;
+                }
+                for place in moved(place, direction, contents[i].len()) {
+                    next.insert((place, symbol_at(&contents[i], place, program.blank)));
+                    next.extend(written.iter().filter(|(at, _)| *at == place));
+                }
+            }
+
+            TapeValue {
+                cells: next,
+                written,
+            }
+        })
+        .collect();
+
+    StateValue {
+        tapes,
+        entries: reads.iter().cloned().collect(),
+    }
+}
+
+/// Adds what `other` knows to `value`, returning whether `value` changed.
+fn join(value: &mut StateValue, other: StateValue) -> bool {
+    let before = value.clone();
+    for (tape, other) in value.tapes.iter_mut().zip(other.tapes) {
+        tape.cells.extend(other.cells);
+        tape.written.extend(other.written);
+    }
+    value.entries.extend(other.entries);
+
+    *value != before
+}
+
+/// Returns the direction each head sweeps in, if any, if `state` loops forever once it reads a
+/// blank from every tape past the written part of the tapes.
+///
+/// The machine is run from `state` with a blank under every head, assuming that every cell a head
+/// moves onto is blank, as long as it keeps moving in the same direction. Every cell it moves onto
+/// is then unwritten, so if the machine repeats a configuration that includes `state`, it repeats
+/// it forever. The run gives up when a head turns back, as the cells behind it are unknown, and in
+/// `Mode::Nondeterministic` when more than one transition matches.
+fn blank_loop(program: &Program, state: &str) -> Option<Vec<Option<Direction>>> {
+    // The direction each head has moved in, if any, and the symbol under it
+    let mut heads: Vec<(Option<Direction>, char)> =
+        vec![(None, program.blank); program.tapes.len()];
+    let initial = state;
+    let mut state = state.to_string();
+    let mut configurations = Vec::new();
+
+    loop {
+        let configuration = (state.clone(), heads.clone());
+        if let Some(start) = configurations
+            .iter()
+            .position(|seen| *seen == configuration)
+        {
+            // The configurations from the first occurrence of the repeated one form the cycle
+            return configurations[start..]
+                .iter()
+                .any(|(state, _)| state == initial)
+                .then(|| heads.iter().map(|&(direction, _)| direction).collect());
+        }
+        configurations.push(configuration);
+
+        if program.is_stop_state(&state) {
+            return None;
+        }
+
+        let symbols: Vec<char> = heads.iter().map(|&(_, symbol)| symbol).collect();
+        let mut matching = program
+            .rules
+            .get(&state)
+            .into_iter()
+            .flatten()
+            .filter(|transition| transition.matches(&symbols, program.blank));
+        let transition = matching.next()?;
+        if program.mode == Mode::Nondeterministic && matching.next().is_some() {
+            return None;
+        }
+
+        let written = transition.written(&symbols, program.blank);
+        for (i, (head, write)) in heads.iter_mut().zip(written).enumerate() {
+            *head = match (transition.directions[i], head.0) {
+                (Direction::Stay, moved) => (moved, write),
+                (direction, Some(moved)) if direction != moved => return None,
+                (direction, _) => (Some(direction), program.blank),
+            };
+        }
+        state = transition.next_state.clone();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_head_analysis() {
+        let program = parse(
+            r#"
+name: Heads
+tape: a, b
+rules:
+  start:
+    a -> b, R, start
+    b, R, next
+    _, L, back
+  next:
+    _, L, back
+  back:
+    b, L, back
+    _, R, done
+  done:
+"#,
+        )
+        .unwrap();
+
+        let analysis = head_analysis(&program);
+        let states: Vec<&str> = analysis.states.iter().map(|s| s.state.as_str()).collect();
+        assert_eq!(states, vec!["start", "next", "back", "done"]);
+
+        // `start` moves right on `a` only, so it never reads past the `b` and its `_` rule is dead
+        let start = &analysis.state("start").unwrap().heads[0];
+        assert_eq!(start.symbols, vec!['a', 'b']);
+        assert!(!start.left_of_input && !start.right_of_input);
+
+        let next = analysis.state("next").unwrap();
+        assert_eq!(next.heads[0].symbols, vec![' ']);
+        assert!(next.heads[0].right_of_input);
+        assert!(!next.entered_on_blank);
+        assert!(!next.loops_on_blank);
+        assert!(analysis.state("done").unwrap().entered_on_blank);
+
+        // `back` sweeps left over the written `b` and stops at the blank left of the input
+        let back = &analysis.state("back").unwrap().heads[0];
+        assert_eq!(back.symbols, vec![' ', 'a', 'b']);
+        assert!(back.left_of_input);
+        assert!(analysis.runs_off_left());
+        assert_eq!(analysis.blank_loops().count(), 0);
+    }
+
+    #[test]
+    fn test_head_analysis_blank_loops() {
+        let program = parse(
+            r#"
+name: Sweep
+tapes:
+  [a]
+  [a]
+rules:
+  start:
+    [a, a], [R, S], start
+    [_, a], [R, R], sweep
+  sweep:
+    [_, _] -> [x, _], [R, R], mark
+    [*, *], [S, S], halt
+  mark:
+    [_, _], [S, R], sweep
+  halt:
+"#,
+        )
+        .unwrap();
+
+        let analysis = head_analysis(&program);
+        let sweep = analysis.state("sweep").unwrap();
+        assert_eq!(sweep.heads[0].symbols, vec![' ']);
+        assert!(sweep.loops_on_blank);
+        assert!(analysis.state("mark").unwrap().loops_on_blank);
+
+        // `start` never reads a blank from the second tape
+        let start = analysis.state("start").unwrap();
+        assert!(!start.loops_on_blank);
+        assert!(!start.entered_on_blank);
+        assert!(!analysis.runs_off_left());
+
+        let loops: Vec<&str> = analysis.blank_loops().map(|s| s.state.as_str()).collect();
+        assert_eq!(loops, vec!["sweep", "mark"]);
+    }
+}
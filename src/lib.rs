@@ -3,26 +3,71 @@
 //! analyzing program correctness, and managing a collection of predefined programs.
 
 pub mod analyzer;
+pub mod binary;
+pub mod busy_beaver;
+mod compile;
+pub mod compose;
+pub mod coverage;
+pub mod deciders;
 pub mod encoder;
+pub mod formatter;
+pub mod heads;
 pub mod loader;
 pub mod machine;
+pub mod nondeterministic;
 pub mod parser;
 pub mod programs;
+pub mod single_tape;
+pub mod syntax;
+pub mod tape;
+pub mod trace;
 pub mod types;
 
 /// Re-exports the `Rule` enum from the parser module, used by the `pest` grammar.
 pub use crate::parser::Rule;
-/// Re-exports the `analyze` function and `AnalysisError` enum from the analyzer module.
-pub use analyzer::{analyze, AnalysisError};
+/// Re-exports the `analyze`, `analyze_with`, `analysis_errors` and `diagnose` functions and the
+/// error and diagnostic types from the analyzer module.
+pub use analyzer::{
+    analysis_errors, analyze, analyze_with, diagnose, AnalysisError, AnalysisOptions, Diagnostic,
+    DiagnosticCode, Diagnostics, Severity,
+};
+/// Re-exports the `to_binary` reduction and its `BinaryProgram` result.
+pub use binary::{to_binary, BinaryProgram};
+/// Re-exports the `compose` and `product` program combinators.
+pub use compose::{compose, product};
+/// Re-exports the `coverage` analysis and its `Coverage` report.
+pub use coverage::{coverage, Coverage, StateCoverage};
+/// Re-exports the `decide` function and its `Decision` and `NonHalting` results.
+pub use deciders::{decide, Decision, NonHalting};
 /// Re-exports the encoding functions from the encoder module.
 pub use encoder::{decode, encode};
+/// Re-exports the `to_source` and `format_source` formatters and their options.
+pub use formatter::{format_source, to_source, DirectionStyle, FormatOptions};
+/// Re-exports the `head_analysis` function and its result types.
+pub use heads::{head_analysis, HeadAnalysis, HeadSummary, StateHeads};
 /// Re-exports the `ProgramLoader` struct from the loader module.
 pub use loader::ProgramLoader;
 /// Re-exports the `TuringMachine` struct from the machine module.
 pub use machine::TuringMachine;
-/// Re-exports the `parse` function from the parser module.
-pub use parser::parse;
+/// Re-exports the `NondeterministicMachine` struct and its `Exploration` result.
+pub use nondeterministic::{Exploration, NondeterministicMachine};
+/// Re-exports the `parse`, `parse_unchecked`, `parse_unchecked_with_imports`,
+/// `parse_with_imports` and `parse_with_options` functions from the parser module.
+pub use parser::{
+    parse, parse_unchecked, parse_unchecked_with_imports, parse_with_imports, parse_with_options,
+};
 /// Re-exports `ProgramInfo`, `ProgramManager`, and `PROGRAMS` from the programs module.
 pub use programs::{ProgramInfo, ProgramManager, PROGRAMS};
+/// Re-exports the `to_single_tape` compiler and its `SingleTapeProgram` result.
+pub use single_tape::{to_single_tape, SingleTapeProgram};
+/// Re-exports the syntax tree and source locations from the syntax module.
+pub use syntax::{parse_syntax, Location, SourceMap, SyntaxNode, SyntaxTree};
+/// Re-exports the `Tape` struct from the tape module.
+pub use tape::Tape;
+/// Re-exports the `Trace` and `TraceStep` structs from the trace module.
+pub use trace::{Trace, TraceStep};
 /// Re-exports various types related to Turing Machine definition and execution from the types module.
-pub use types::{Direction, Program, Step, Transition, TuringMachineError, MAX_PROGRAM_SIZE};
+pub use types::{
+    Breakpoint, Direction, Halt, Pause, Program, Rejection, Step, Transition, TuringMachineError,
+    MAX_PROGRAM_SIZE,
+};
@@ -1,7 +1,7 @@
 //! This module provides the `ProgramLoader` struct, responsible for loading Turing Machine
 //! programs from various sources, including files and strings.
 
-use crate::parser::parse;
+use crate::parser::{parse, parse_unchecked_with_imports, parse_with_imports};
 use crate::types::{Program, TuringMachineError};
 use std::fs;
 use std::path::{Path, PathBuf};
@@ -14,6 +14,8 @@ pub struct ProgramLoader;
 impl ProgramLoader {
     /// Loads a single Turing Machine program from the specified file path.
     ///
+    /// Programs imported with `import` are loaded from paths relative to the importing file.
+    ///
     /// # Arguments
     ///
     /// * `path` - A reference to the `Path` of the `.tur` file to load.
@@ -22,13 +24,58 @@ impl ProgramLoader {
     ///
     /// * `Ok(Program)` if the file is successfully read and parsed into a `Program`.
     /// * `Err(TuringMachineError::FileError)` if the file cannot be read.
-    /// * `Err(TuringMachineError::ParseError)` if the file content is not a valid program.
+    /// * `Err(TuringMachineError::ParseError)` if the file content is not a valid program, or an
+    ///   import can't be loaded or is part of an import cycle.
     pub fn load_program(path: &Path) -> Result<Program, TuringMachineError> {
+        Self::load_program_importing(path, &mut Vec::new(), true)
+    }
+
+    /// Loads a program from the specified file path, like `load_program`, without analyzing it.
+    ///
+    /// This is for tools that report every analysis error themselves, e.g. with
+    /// `analyzer::diagnose`. The programs it imports are still analyzed, and syntax errors,
+    /// invalid sections and imports that can't be loaded are still returned as errors.
+    pub fn load_program_unchecked(path: &Path) -> Result<Program, TuringMachineError> {
+        Self::load_program_importing(path, &mut Vec::new(), false)
+    }
+
+    /// Loads a program and the programs it imports, analyzing the program if `checked` is set.
+    /// Imported programs are always analyzed. `importing` holds the files whose imports are
+    /// being loaded, to detect import cycles.
+    fn load_program_importing(
+        path: &Path,
+        importing: &mut Vec<PathBuf>,
+        checked: bool,
+    ) -> Result<Program, TuringMachineError> {
         let content = fs::read_to_string(path).map_err(|e| {
             TuringMachineError::FileError(format!("Failed to read file {}: {}", path.display(), e))
         })?;
 
-        parse(&content)
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if let Some(i) = importing.iter().position(|p| *p == canonical) {
+            let cycle: Vec<String> = importing[i..]
+                .iter()
+                .chain([&canonical])
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(TuringMachineError::FileError(format!(
+                "Import cycle: {}",
+                cycle.join(" -> ")
+            )));
+        }
+
+        let directory = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        importing.push(canonical);
+        let load =
+            |import: &str| Self::load_program_importing(&directory.join(import), importing, true);
+        let result = if checked {
+            parse_with_imports(&content, load)
+        } else {
+            parse_unchecked_with_imports(&content, load)
+        };
+        importing.pop();
+
+        result
     }
 
     /// Loads a single Turing Machine program from the provided string content.
@@ -141,6 +188,149 @@ mod tests {
         assert!(program.rules.contains_key("stop"));
     }
 
+    #[test]
+    fn test_load_program_with_imports() {
+        let dir = tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("lib")).unwrap();
+
+        let scan =
+            "name: Scan\ntape: a\nrules:\n  start:\n    a, R, start\n    _, L, done\n  done:";
+        File::create(dir.path().join("lib/scan.tur"))
+            .unwrap()
+            .write_all(scan.as_bytes())
+            .unwrap();
+
+        let main = "name: Main\nimport \"lib/scan.tur\" as scan\ntape: a, a\nrules:\n  \
+                    start:\n    a, S, scan.start\n  scan.done:\n    a -> b, S, halt\n  halt:";
+        let main_path = dir.path().join("main.tur");
+        File::create(&main_path)
+            .unwrap()
+            .write_all(main.as_bytes())
+            .unwrap();
+
+        let program = ProgramLoader::load_program(&main_path).unwrap();
+        assert_eq!(program.rules["scan.start"][1].next_state, "scan.done");
+        assert_eq!(program.rules["scan.done"][0].next_state, "halt");
+
+        let mut machine = crate::machine::TuringMachine::new(program);
+        machine.run();
+        assert_eq!(machine.tapes()[0].to_string().trim(), "ab");
+    }
+
+    #[test]
+    fn test_load_program_with_imported_stop_states() {
+        let dir = tempdir().unwrap();
+
+        // `done` halts the library even though it has transitions
+        let library = "name: Library\ntape: a\nstates:\n  stop: done\n  accept: yes\nrules:\n  \
+                       start:\n    a, R, done\n    b, R, yes\n  done:\n    *, R, done\n  yes:";
+        File::create(dir.path().join("lib.tur"))
+            .unwrap()
+            .write_all(library.as_bytes())
+            .unwrap();
+
+        let main = "name: Main\nimport \"lib.tur\" as lib\ntape: a\nrules:\n  start:\n    \
+                    a, S, lib.start";
+        let main_path = dir.path().join("main.tur");
+        File::create(&main_path)
+            .unwrap()
+            .write_all(main.as_bytes())
+            .unwrap();
+
+        let program = ProgramLoader::load_program(&main_path).unwrap();
+        assert_eq!(program.stop_states, vec!["lib.done"]);
+        assert_eq!(program.accept_states, vec!["lib.yes"]);
+
+        let mut machine = crate::machine::TuringMachine::new(program).with_max_steps(100);
+        assert_eq!(
+            machine.run(),
+            crate::types::Step::Halt(crate::types::Halt::Ok)
+        );
+        assert_eq!(machine.state(), "lib.done");
+        assert_eq!(machine.step_count(), 2);
+    }
+
+    #[test]
+    fn test_load_program_with_imported_blank() {
+        let dir = tempdir().unwrap();
+
+        let library =
+            "name: Library\nblank: 0\ntape: 0\nrules:\n  start:\n    0 -> 1, S, done\n  done:";
+        File::create(dir.path().join("lib.tur"))
+            .unwrap()
+            .write_all(library.as_bytes())
+            .unwrap();
+
+        // The library's `0` is the blank of the main program, where `0` is an ordinary symbol
+        let main = "name: Main\nimport \"lib.tur\" as lib\ntape: a, 0\nrules:\n  start:\n    \
+                    a, R, skip\n  skip:\n    0, R, lib.start";
+        let main_path = dir.path().join("main.tur");
+        File::create(&main_path)
+            .unwrap()
+            .write_all(main.as_bytes())
+            .unwrap();
+
+        let program = ProgramLoader::load_program(&main_path).unwrap();
+        assert_eq!(program.rules["lib.start"][0].read, vec!['_']);
+
+        let mut machine = crate::machine::TuringMachine::new(program);
+        machine.run();
+        assert_eq!(machine.state(), "lib.done");
+        assert_eq!(machine.tapes()[0].to_string().trim(), "a01");
+    }
+
+    #[test]
+    fn test_load_program_unchecked() {
+        let dir = tempdir().unwrap();
+
+        let scan =
+            "name: Scan\ntape: a\nrules:\n  start:\n    a, R, start\n    _, L, done\n  done:";
+        File::create(dir.path().join("scan.tur"))
+            .unwrap()
+            .write_all(scan.as_bytes())
+            .unwrap();
+
+        // `orphan` is unreachable and `missing` is undefined
+        let main = "name: Main\nimport \"scan.tur\" as scan\ntape: a\nrules:\n  start:\n    \
+                    a, S, scan.start\n  scan.done:\n    a, R, missing\n  orphan:\n    a, R, orphan";
+        let main_path = dir.path().join("main.tur");
+        File::create(&main_path)
+            .unwrap()
+            .write_all(main.as_bytes())
+            .unwrap();
+
+        assert!(ProgramLoader::load_program(&main_path).is_err());
+
+        let program = ProgramLoader::load_program_unchecked(&main_path).unwrap();
+        assert!(program.rules.contains_key("scan.start"));
+        assert_eq!(crate::analyzer::analysis_errors(&program).len(), 2);
+
+        // Syntax errors are still errors
+        File::create(&main_path)
+            .unwrap()
+            .write_all(b"name: Main\nrules:")
+            .unwrap();
+        assert!(ProgramLoader::load_program_unchecked(&main_path).is_err());
+    }
+
+    #[test]
+    fn test_load_program_with_import_cycle() {
+        let dir = tempdir().unwrap();
+
+        for (name, other) in [("a", "b"), ("b", "a")] {
+            let content = format!(
+                "name: {name}\nimport \"{other}.tur\" as {other}\ntape: a\nrules:\n  start:\n    a, R, stop\n  stop:"
+            );
+            File::create(dir.path().join(format!("{name}.tur")))
+                .unwrap()
+                .write_all(content.as_bytes())
+                .unwrap();
+        }
+
+        let error = ProgramLoader::load_program(&dir.path().join("a.tur")).unwrap_err();
+        assert!(error.to_string().contains("Import cycle"));
+    }
+
     #[test]
     fn test_load_invalid_program() {
         let dir = tempdir().unwrap();
@@ -2,23 +2,56 @@
 //! multi-tape Turing Machine. It handles the machine's state, tape operations, head movements,
 //! and execution of transition rules.
 
+use crate::tape::{Tape, TapeDelta};
 use crate::types::{
-    Direction, Halt, Mode, Program, Step, Transition, TuringMachineError, INPUT_BLANK_SYMBOL,
-    MAX_EXECUTION_STEPS,
+    resolve, Breakpoint, Halt, Mode, Pause, Program, Rejection, Step, Transition,
+    TuringMachineError, MAX_EXECUTION_STEPS,
 };
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
 
 /// Represents a multi-tape Turing Machine.
 ///
 /// This struct encapsulates the current state of the Turing Machine, including its
 /// current state, the contents of its tapes, the positions of its read/write heads,
 /// the blank symbol, and the set of transition rules.
+///
+/// With [`TuringMachine::with_history`], every step taken with [`TuringMachine::step`] is
+/// recorded as a reversible change, so the machine can move backwards with
+/// [`TuringMachine::step_back`] and [`TuringMachine::seek`].
+///
+/// Cloning a machine is cheap relative to its program: the transition rules are shared
+/// between clones, and only the configuration (state, tapes and heads) is copied.
+#[derive(Clone)]
 pub struct TuringMachine {
     state: String,
-    tapes: Vec<Vec<char>>,
-    heads: Vec<usize>,
+    tapes: Vec<Tape>,
     blank: char,
-    program: Program,
+    program: Arc<Program>,
     step_count: usize,
+    max_steps: usize,
+    history: Vec<Change>,
+    record_history: bool,
+    breakpoints: Vec<Breakpoint>,
+    /// The step at which each configuration was first seen, if loop detection is enabled.
+    visited: Option<HashMap<Configuration, usize>>,
+}
+
+/// A snapshot of everything that determines a machine's future: its state, head positions and
+/// tape contents, with blanks trimmed so that how far a tape has been visited doesn't matter.
+#[derive(Clone, PartialEq, Eq, Hash)]
+struct Configuration {
+    state: String,
+    heads: Vec<isize>,
+    tapes: Vec<(isize, Vec<char>)>,
+}
+
+/// A reversible record of a single step, holding the parts of the configuration it overwrote.
+#[derive(Clone)]
+struct Change {
+    state: String,
+    tapes: Vec<TapeDelta>,
 }
 
 impl TuringMachine {
@@ -33,14 +66,81 @@ impl TuringMachine {
     pub fn new(program: Program) -> Self {
         Self {
             state: program.initial_state.clone(),
-            tapes: program.tapes().clone(),
-            heads: program.heads.clone(),
+            tapes: Self::initial_tapes(&program),
             blank: program.blank,
-            program,
+            program: Arc::new(program),
             step_count: 0,
+            max_steps: MAX_EXECUTION_STEPS,
+            history: Vec::new(),
+            record_history: false,
+            breakpoints: Vec::new(),
+            visited: None,
         }
     }
 
+    /// Builds the initial tapes of a program, with each head at its declared position.
+    fn initial_tapes(program: &Program) -> Vec<Tape> {
+        program
+            .tapes()
+            .into_iter()
+            .zip(&program.heads)
+            .map(|(content, &head)| Tape::new(content, head as isize, program.blank))
+            .collect()
+    }
+
+    /// Sets the maximum number of steps the machine may execute before it stops with
+    /// `Halt::StepLimit`. Defaults to `MAX_EXECUTION_STEPS`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_steps` - The step budget, counted from the initial configuration.
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Returns the maximum number of steps the machine may execute.
+    pub fn max_steps(&self) -> usize {
+        self.max_steps
+    }
+
+    /// Enables or disables recording of the execution history, which stepping back needs.
+    /// History isn't recorded by default, since it costs memory for every step of the run.
+    pub fn with_history(mut self, enabled: bool) -> Self {
+        self.record_history = enabled;
+        if !enabled {
+            self.history = Vec::new();
+        }
+        self
+    }
+
+    /// Enables or disables loop detection. When enabled, the machine remembers every
+    /// configuration it visits and stops with `Halt::Loop` as soon as one repeats, since a
+    /// deterministic machine that revisits a configuration never halts.
+    ///
+    /// Loop detection is disabled by default. It costs memory proportional to the number of
+    /// steps times the size of the tapes.
+    pub fn with_loop_detection(mut self, enabled: bool) -> Self {
+        self.visited = enabled.then(HashMap::new);
+        self
+    }
+
+    /// Enables loop detection if it's disabled, or disables it otherwise. Returns whether it's
+    /// now enabled. Enabled in the middle of a run, it only finds loops through the
+    /// configurations visited from then on.
+    pub fn toggle_loop_detection(&mut self) -> bool {
+        self.visited = match self.visited {
+            Some(_) => None,
+            None => Some(HashMap::new()),
+        };
+        self.visited.is_some()
+    }
+
+    /// Returns whether loop detection is enabled.
+    pub fn detects_loops(&self) -> bool {
+        self.visited.is_some()
+    }
+
     /// Executes a single step of the Turing Machine's computation.
     ///
     /// This involves reading symbols, writing new symbols, moving heads, and transitioning
@@ -49,17 +149,26 @@ impl TuringMachine {
     /// # Returns
     ///
     /// * `ExecutionResult::Continue` if the machine successfully performs a step.
-    /// * `ExecutionResult::Halt(_)` if the machine enters a halt state (no defined transitions).
+    /// * `ExecutionResult::Halt(_)` if the machine is in a halt state (a declared stop state or
+    ///   one with no defined transitions). Declared accept and reject states halt with
+    ///   `Halt::Accept` and `Halt::Reject` respectively.
+    /// * `ExecutionResult::Halt(Halt::StepLimit)` if the step budget has been used up and a
+    ///   transition would be taken. A machine that halts on the step after its last one halts
+    ///   as usual.
+    /// * `ExecutionResult::Halt(Halt::Loop { .. })` if loop detection is enabled and the current
+    ///   configuration was seen before.
+    /// * `ExecutionResult::Halt(Halt::Err(_))` if the program is in `Mode::Nondeterministic` and
+    ///   several transitions match, since only `NondeterministicMachine` follows them all.
     pub fn step(&mut self) -> Step {
         if self.is_halted() {
-            return Step::Halt(Halt::Ok);
+            return Step::Halt(self.halt());
         }
 
-        // Ensure all tapes are large enough
-        for (i, head_pos) in self.heads.iter().enumerate() {
-            if *head_pos >= self.tapes[i].len() {
-                self.tapes[i].resize(*head_pos + 1, self.blank);
-            }
+        if self.program.mode == Mode::Nondeterministic && self.transitions().len() > 1 {
+            return Step::Halt(Halt::Err(TuringMachineError::ValidationError(format!(
+                "Several transitions match in state '{}' of a nondeterministic program",
+                self.state
+            ))));
         }
 
         // Find matching transition
@@ -68,62 +177,256 @@ impl TuringMachine {
             None => {
                 // No transition found for the current symbols.
                 return match self.program.mode {
-                    Mode::Normal => Step::Halt(Halt::Ok),
+                    Mode::Normal | Mode::Nondeterministic => Step::Halt(Halt::Ok),
                     Mode::Strict => Step::Halt(Halt::Err(TuringMachineError::UndefinedTransition(
                         self.state.clone(),
                         self.symbols(),
+                        self.program
+                            .source_map
+                            .as_ref()
+                            .and_then(|source_map| source_map.states.get(&self.state))
+                            .copied(),
                     ))),
                 };
             }
         };
 
-        // Apply transition to all tapes
-        for i in 0..self.tapes.len() {
-            // Write new symbol
-            self.tapes[i][self.heads[i]] = if transition.write[i] == INPUT_BLANK_SYMBOL {
-                self.blank
-            } else {
-                transition.write[i]
-            };
+        // The budget only runs out if the machine would take another step
+        if self.step_count >= self.max_steps {
+            return Step::Halt(Halt::StepLimit);
+        }
+
+        if let Some(halt) = self.detect_loop() {
+            return Step::Halt(halt);
+        }
+
+        if self.record_history {
+            self.history.push(Change {
+                state: self.state.clone(),
+                tapes: self.tapes.iter().map(Tape::delta).collect(),
+            });
+        }
 
-            // Move head according to direction
-            match transition.directions[i] {
-                Direction::Left => {
-                    if self.heads[i] == 0 {
-                        // Extend tape to the left
-                        self.tapes[i].insert(0, self.blank);
-                    } else {
-                        self.heads[i] -= 1;
-                    }
-                }
-                Direction::Right => {
-                    self.heads[i] += 1;
-                    if self.heads[i] >= self.tapes[i].len() {
-                        self.tapes[i].push(self.blank);
-                    }
-                }
-                Direction::Stay => {
-                    // Head position remains unchanged
-                }
+        self.apply(&transition);
+
+        Step::Continue
+    }
+
+    /// Records the current configuration, returning `Halt::Loop` if it was seen at an earlier
+    /// step. Does nothing unless loop detection is enabled.
+    fn detect_loop(&mut self) -> Option<Halt> {
+        let visited = self.visited.as_mut()?;
+        let configuration = Configuration {
+            state: self.state.clone(),
+            heads: self.tapes.iter().map(Tape::head).collect(),
+            tapes: self.tapes.iter().map(Tape::trimmed).collect(),
+        };
+        let step_count = self.step_count;
+
+        match visited.entry(configuration) {
+            Entry::Occupied(entry) if *entry.get() < step_count => Some(Halt::Loop {
+                first_seen_step: *entry.get(),
+                period: step_count - entry.get(),
+            }),
+            Entry::Occupied(_) => None,
+            Entry::Vacant(entry) => {
+                entry.insert(step_count);
+                None
             }
         }
+    }
+
+    /// Undoes the most recent step, restoring the previous state, tape contents and head
+    /// positions.
+    ///
+    /// # Returns
+    ///
+    /// * `true` if a step was undone.
+    /// * `false` if there is no recorded step to undo, e.g. at the initial configuration.
+    pub fn step_back(&mut self) -> bool {
+        let Some(change) = self.history.pop() else {
+            return false;
+        };
+
+        for (tape, delta) in self.tapes.iter_mut().zip(change.tapes) {
+            tape.restore(delta);
+        }
+
+        self.state = change.state;
+        self.step_count -= 1;
+
+        // Forget configurations from the undone steps
+        let step_count = self.step_count;
+        if let Some(visited) = &mut self.visited {
+            visited.retain(|_, &mut step| step < step_count);
+        }
+
+        true
+    }
+
+    /// Moves the machine to the configuration after `step` steps, stepping backwards through
+    /// the recorded history or forwards by executing the program.
+    ///
+    /// # Returns
+    ///
+    /// * `Step::Continue` if the machine reached the requested step, or stepped back as far as
+    ///   its history allows.
+    /// * `Step::Halt(_)` if the machine halted before reaching the requested step.
+    pub fn seek(&mut self, step: usize) -> Step {
+        while self.step_count > step {
+            if !self.step_back() {
+                return Step::Continue;
+            }
+        }
+
+        while self.step_count < step {
+            if let Step::Halt(halt) = self.step() {
+                return Step::Halt(halt);
+            }
+        }
+
+        Step::Continue
+    }
+
+    /// Checks if there is a recorded step that [`TuringMachine::step_back`] can undo.
+    pub fn can_step_back(&self) -> bool {
+        !self.history.is_empty()
+    }
+
+    /// Applies a transition to the current configuration: writes symbols, moves heads and
+    /// enters the transition's next state. The transition is assumed to match the symbols
+    /// under the heads.
+    pub(crate) fn apply(&mut self, transition: &Transition) {
+        let written = transition.written(&self.symbols(), self.blank);
+
+        for (i, tape) in self.tapes.iter_mut().enumerate() {
+            // Write new symbol
+            tape.write(written[i]);
+
+            // Move head according to direction, growing the tape as needed
+            tape.move_head(transition.directions[i]);
+        }
 
         self.state = transition.next_state.clone();
         self.step_count += 1;
+    }
 
-        Step::Continue
+    /// Returns the halt outcome for the current state.
+    pub(crate) fn halt(&self) -> Halt {
+        if self.program.is_accept_state(&self.state) {
+            Halt::Accept
+        } else if self.program.is_reject_state(&self.state) {
+            Halt::Reject(Rejection {
+                state: self.state.clone(),
+                symbols: self.symbols(),
+            })
+        } else {
+            Halt::Ok
+        }
     }
 
-    /// Runs the Turing Machine until it halts or reaches a maximum step count.
+    /// Runs the Turing Machine until it halts or reaches its maximum step count,
+    /// in which case `Halt::StepLimit` is returned.
     pub fn run(&mut self) -> Step {
-        for _ in 0..MAX_EXECUTION_STEPS {
+        loop {
             match self.step() {
                 Step::Continue => continue,
                 halt => return halt,
             }
         }
+    }
 
-        Step::Halt(Halt::Ok)
+    /// Executes a single step, then checks the breakpoints against the new configuration.
+    ///
+    /// # Returns
+    ///
+    /// * `None` if the machine performed a step without hitting a breakpoint.
+    /// * `Some(Pause::Breakpoint(_))` with the first breakpoint hit by the step.
+    /// * `Some(Pause::Halt(_))` if the machine is halted.
+    pub fn step_with_breakpoints(&mut self) -> Option<Pause> {
+        if self.breakpoints.is_empty() {
+            return match self.step() {
+                Step::Continue => None,
+                Step::Halt(halt) => Some(Pause::Halt(halt)),
+            };
+        }
+
+        // The symbols written by the step, for `Breakpoint::Write`, and the state it leaves, for
+        // `Breakpoint::State`.
+        let written: Vec<char> = self
+            .transition()
+            .map(|t| t.written(&self.symbols(), self.blank))
+            .unwrap_or_default();
+        let previous = self.state.clone();
+
+        if let Step::Halt(halt) = self.step() {
+            return Some(Pause::Halt(halt));
+        }
+
+        self.breakpoints
+            .iter()
+            .find(|breakpoint| self.is_hit(breakpoint, &previous, &written))
+            .cloned()
+            .map(Pause::Breakpoint)
+    }
+
+    /// Runs the Turing Machine until it hits a breakpoint or halts. At least one step is
+    /// executed, so calling it again resumes from a breakpoint.
+    pub fn run_until_break(&mut self) -> Pause {
+        loop {
+            if let Some(pause) = self.step_with_breakpoints() {
+                return pause;
+            }
+        }
+    }
+
+    /// Checks if a breakpoint matches the current configuration, given the state before the step
+    /// that produced it and the symbols the step wrote. State breakpoints only match when the
+    /// state is entered from another one, not on every step a state loops on itself.
+    fn is_hit(&self, breakpoint: &Breakpoint, previous: &str, written: &[char]) -> bool {
+        match breakpoint {
+            Breakpoint::State(state) => self.state == *state && previous != state,
+            Breakpoint::Read(symbols) => symbols
+                .iter()
+                .map(|&c| resolve(c, self.blank))
+                .eq(self.tapes.iter().map(Tape::read)),
+            Breakpoint::Write(symbol) => written.contains(&resolve(*symbol, self.blank)),
+            Breakpoint::Head { tape, position } => self
+                .tapes
+                .get(*tape)
+                .is_some_and(|tape| tape.head() == *position),
+            Breakpoint::Steps(steps) => self.step_count == *steps,
+        }
+    }
+
+    /// Adds a breakpoint. Adding a breakpoint that is already set has no effect.
+    pub fn add_breakpoint(&mut self, breakpoint: Breakpoint) {
+        if !self.breakpoints.contains(&breakpoint) {
+            self.breakpoints.push(breakpoint);
+        }
+    }
+
+    /// Removes a breakpoint, returning whether it was set.
+    pub fn remove_breakpoint(&mut self, breakpoint: &Breakpoint) -> bool {
+        let count = self.breakpoints.len();
+        self.breakpoints.retain(|b| b != breakpoint);
+        self.breakpoints.len() != count
+    }
+
+    /// Sets a breakpoint if it isn't set, or removes it otherwise. Returns whether the
+    /// breakpoint is now set.
+    pub fn toggle_breakpoint(&mut self, breakpoint: Breakpoint) -> bool {
+        if self.remove_breakpoint(&breakpoint) {
+            false
+        } else {
+            self.breakpoints.push(breakpoint);
+            true
+        }
+    }
+
+    /// Returns the breakpoints that are currently set.
+    pub fn breakpoints(&self) -> &[Breakpoint] {
+        &self.breakpoints
     }
 
     /// Returns the current state of the Turing Machine.
@@ -137,12 +440,15 @@ impl TuringMachine {
     }
 
     /// Resets the Turing Machine to its initial configuration.
-    /// This includes resetting the state, tapes, head positions, and step count.
+    /// This includes resetting the state, tapes, head positions, step count and history.
     pub fn reset(&mut self) {
         self.state = self.program.initial_state.clone();
-        self.tapes = self.program.tapes().clone();
-        self.heads = self.program.heads.clone();
+        self.tapes = Self::initial_tapes(&self.program);
         self.step_count = 0;
+        self.history.clear();
+        if let Some(visited) = &mut self.visited {
+            visited.clear();
+        }
     }
 
     /// Returns the total number of steps executed by the Turing Machine.
@@ -151,84 +457,86 @@ impl TuringMachine {
     }
 
     /// Checks if the Turing Machine is currently in a halted state.
-    /// A machine is halted if there are no defined transitions for its current state.
+    /// A machine is halted if its current state is a declared stop state, or if there are
+    /// no defined transitions for its current state.
     pub fn is_halted(&self) -> bool {
-        self.program
-            .rules
-            .get(&self.state)
-            .is_none_or(|transitions| transitions.is_empty())
+        self.program.is_stop_state(&self.state)
+            || self
+                .program
+                .rules
+                .get(&self.state)
+                .is_none_or(|transitions| transitions.is_empty())
+    }
+
+    /// Checks if the machine has used up its step budget without halting, i.e. it would take
+    /// another transition.
+    pub fn is_step_limit_reached(&self) -> bool {
+        !self.is_halted() && self.step_count >= self.max_steps && self.transition().is_some()
     }
 
     /// Returns a slice of the machine's tapes.
-    pub fn tapes(&self) -> &[Vec<char>] {
+    pub fn tapes(&self) -> &[Tape] {
         &self.tapes
     }
 
-    /// Returns a slice of the machine's head positions for all tapes.
-    pub fn heads(&self) -> &[usize] {
-        &self.heads
+    /// Returns the absolute head positions for all tapes. Position `0` is the first cell of the
+    /// initial tape content; cells to its left have negative positions.
+    pub fn heads(&self) -> Vec<isize> {
+        self.tapes.iter().map(Tape::head).collect()
     }
 
     /// Returns a vector of symbols currently under each tape's head.
-    /// If a head is beyond its tape's current length, the blank symbol is returned for that tape.
+    /// If a head is beyond its tape's stored cells, the blank symbol is returned for that tape.
     ///
-    /// | a | b | c | tape 1
-    /// | d | e |   | tape 2
-    ///   0   1   2   index
+    ///   | a | b | c | tape 1
+    ///   | d | e |   | tape 2
+    ///  -1   0   1   2   position
     ///
-    /// heads [0, 2] will return ['a', '_']
+    /// heads [0, 2] will return ['a', '_'], as will heads [0, -1].
     pub fn symbols(&self) -> Vec<char> {
-        self.heads
-            .iter()
-            .enumerate()
-            .map(|(i, &pos)| {
-                if pos < self.tapes[i].len() {
-                    self.tapes[i][pos]
-                } else {
-                    self.blank
-                }
-            })
-            .collect()
+        self.tapes.iter().map(Tape::read).collect()
     }
 
     /// Finds and returns the matching `Transition` for the current state and symbols under the heads.
     ///
     /// It iterates through the rules for the current state and finds the first transition
     /// whose `read` symbols match the current symbols on the tapes.
-    /// Special handling for `INPUT_BLANK_SYMBOL` allows it to match the machine's actual blank symbol.
+    /// Special handling for `INPUT_BLANK_SYMBOL` allows it to match the machine's actual blank symbol,
+    /// and `WILDCARD_SYMBOL` matches any symbol.
     ///
     /// # Returns
     ///
     /// * `Some(&Transition)` if a matching transition is found.
     /// * `None` if no matching transition exists.
     pub fn transition(&self) -> Option<&Transition> {
-        match self.program.rules.get(&self.state) {
-            Some(transitions) => {
-                let symbols = self.symbols();
-
-                transitions.iter().find(|t| {
-                    if t.read.len() != symbols.len() {
-                        return false;
-                    }
-
-                    for (i, &read) in t.read.iter().enumerate() {
-                        // If the transition rule specifies `INPUT_BLANK_SYMBOL`, it matches the program's blank symbol
-                        let expected = if read == INPUT_BLANK_SYMBOL {
-                            self.blank
-                        } else {
-                            read
-                        };
-
-                        if symbols[i] != expected {
-                            return false;
-                        }
-                    }
-
-                    true
-                })
-            }
-            _ => None,
-        }
+        let symbols = self.symbols();
+
+        self.program
+            .rules
+            .get(&self.state)?
+            .iter()
+            .find(|t| t.matches(&symbols, self.blank))
+    }
+
+    /// Returns every `Transition` that matches the current state and symbols under the heads,
+    /// in the order they are defined.
+    ///
+    /// Outside `Mode::Nondeterministic`, only the first one is taken. In
+    /// `Mode::Nondeterministic`, each returned transition is a separate branch of the
+    /// computation tree.
+    pub fn transitions(&self) -> Vec<&Transition> {
+        let symbols = self.symbols();
+
+        self.program
+            .rules
+            .get(&self.state)
+            .map(|transitions| {
+                transitions
+                    .iter()
+                    .filter(|t| t.matches(&symbols, self.blank))
+                    .collect()
+            })
+            .unwrap_or_default()
     }
 
     /// Returns the blank symbol used by this Turing Machine.
@@ -236,7 +544,8 @@ impl TuringMachine {
         self.blank
     }
 
-    /// Sets the content of a specific tape.
+    /// Sets the content of a specific tape. The execution history is cleared, since earlier
+    /// steps no longer lead to the new configuration.
     ///
     /// # Arguments
     ///
@@ -260,16 +569,12 @@ impl TuringMachine {
             )));
         }
 
-        self.tapes[tape_index] = content
-            .chars()
-            .map(|c| {
-                if c == INPUT_BLANK_SYMBOL {
-                    self.blank
-                } else {
-                    c
-                }
-            })
-            .collect();
+        let blank = self.blank;
+        self.tapes[tape_index].set_content(content.chars().map(|c| resolve(c, blank)));
+        self.history.clear();
+        if let Some(visited) = &mut self.visited {
+            visited.clear();
+        }
         Ok(())
     }
 
@@ -306,6 +611,10 @@ mod multi_tape_tests {
     use crate::types::{Direction, Halt, Mode, Program, Transition};
     use std::collections::HashMap;
 
+    fn contents(machine: &TuringMachine) -> Vec<Vec<char>> {
+        machine.tapes().iter().map(Tape::to_vec).collect()
+    }
+
     fn create_simple_multi_tape_program() -> Program {
         let mut rules = HashMap::new();
 
@@ -327,10 +636,14 @@ mod multi_tape_tests {
             name: "Simple Multi-Tape Test".to_string(),
             mode: Mode::default(),
             initial_state: "start".to_string(),
+            stop_states: Vec::new(),
+            accept_states: Vec::new(),
+            reject_states: Vec::new(),
             tapes: vec!["a".to_string(), "x".to_string()],
             heads: vec![0, 0],
             blank: '-',
             rules,
+            source_map: None,
         }
     }
 
@@ -340,7 +653,7 @@ mod multi_tape_tests {
         let machine = TuringMachine::new(program);
 
         assert_eq!(machine.state(), "start");
-        assert_eq!(machine.tapes(), &[vec!['a'], vec!['x']]);
+        assert_eq!(contents(&machine), [vec!['a'], vec!['x']]);
         assert_eq!(machine.heads(), &[0, 0]);
         assert_eq!(machine.step_count(), 0);
     }
@@ -354,7 +667,7 @@ mod multi_tape_tests {
 
         assert_eq!(result, Step::Continue);
         assert_eq!(machine.state(), "halt");
-        assert_eq!(machine.tapes(), &[vec!['b', '-'], vec!['y', '-']]); // Tapes extended when moving right
+        assert_eq!(contents(&machine), [vec!['b', '-'], vec!['y', '-']]); // Tapes extended when moving right
         assert_eq!(machine.heads(), &[1, 1]);
         assert_eq!(machine.step_count(), 1);
     }
@@ -388,7 +701,7 @@ mod multi_tape_tests {
         let result = machine.step();
 
         match result {
-            Step::Halt(Halt::Err(TuringMachineError::UndefinedTransition(state, symbols))) => {
+            Step::Halt(Halt::Err(TuringMachineError::UndefinedTransition(state, symbols, _))) => {
                 assert_eq!(state, "start");
                 assert_eq!(symbols, vec!['z', 'z']);
             }
@@ -396,6 +709,34 @@ mod multi_tape_tests {
         }
     }
 
+    #[test]
+    fn test_undefined_transition_location() {
+        let program = crate::parser::parse(
+            r#"
+name: Strict
+mode: strict
+tape: a, b
+rules:
+  start:
+    a, R, next
+    b, R, start
+  next:
+    a, R, next
+"#,
+        )
+        .unwrap();
+
+        let mut machine = TuringMachine::new(program);
+        let Step::Halt(Halt::Err(error)) = machine.run() else {
+            panic!("Expected an error");
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "No rule defined for state next and symbols ['b'] (state defined at line 9, column 3)"
+        );
+    }
+
     #[test]
     fn test_multi_tape_reset() {
         let program = create_simple_multi_tape_program();
@@ -409,7 +750,7 @@ mod multi_tape_tests {
         // Reset
         machine.reset();
         assert_eq!(machine.state(), "start");
-        assert_eq!(machine.tapes(), &[vec!['a'], vec!['x']]);
+        assert_eq!(contents(&machine), [vec!['a'], vec!['x']]);
         assert_eq!(machine.heads(), &[0, 0]);
         assert_eq!(machine.step_count(), 0);
     }
@@ -434,6 +775,133 @@ mod multi_tape_tests {
         assert!(machine.is_halted()); // Should be halted now
     }
 
+    #[test]
+    fn test_halt_on_declared_stop_state() {
+        let mut program = create_simple_multi_tape_program();
+
+        // The stop state has outgoing rules, but entering it must still halt the machine
+        program.rules.insert(
+            "halt".to_string(),
+            vec![Transition {
+                read: vec!['-', '-'],
+                write: vec!['c', 'z'],
+                directions: vec![Direction::Right, Direction::Right],
+                next_state: "halt".to_string(),
+            }],
+        );
+        program.stop_states = vec!["halt".to_string()];
+
+        let mut machine = TuringMachine::new(program);
+        assert_eq!(machine.step(), Step::Continue);
+        assert!(machine.is_halted());
+        assert_eq!(machine.step(), Step::Halt(Halt::Ok));
+        assert_eq!(contents(&machine), [vec!['b', '-'], vec!['y', '-']]);
+        assert_eq!(machine.step_count(), 1);
+    }
+
+    #[test]
+    fn test_run_accept_and_reject() {
+        let program = crate::parser::parse(include_str!("../examples/palindrome.tur")).unwrap();
+
+        let mut machine = TuringMachine::new(program.clone());
+        machine.set_tape_content(0, "abba").unwrap();
+        assert_eq!(machine.run(), Step::Halt(Halt::Accept));
+
+        let mut machine = TuringMachine::new(program);
+        machine.set_tape_content(0, "abab").unwrap();
+        assert_eq!(
+            machine.run(),
+            Step::Halt(Halt::Reject(Rejection {
+                state: "reject".to_string(),
+                symbols: vec!['b'],
+            }))
+        );
+    }
+
+    #[test]
+    fn test_run_nondeterministic_program() {
+        let program =
+            crate::parser::parse(include_str!("../examples/nondeterministic-substring.tur"))
+                .unwrap();
+
+        // Taking the first of several matching transitions would reject the input
+        let mut machine = TuringMachine::new(program.clone());
+        assert!(matches!(machine.run(), Step::Halt(Halt::Err(_))));
+        assert_eq!(machine.state(), "scan");
+
+        // A single matching transition is taken as usual
+        let mut machine = TuringMachine::new(program);
+        machine.set_tape_content(0, "0").unwrap();
+        assert!(matches!(machine.run(), Step::Halt(Halt::Reject(_))));
+    }
+
+    #[test]
+    fn test_run_step_limit() {
+        let program = crate::parser::parse(
+            r#"
+name: Endless
+tape: a
+rules:
+  start:
+    a, R, start
+    _ -> a, R, start
+"#,
+        )
+        .unwrap();
+
+        let mut machine = TuringMachine::new(program.clone());
+        assert_eq!(machine.max_steps(), MAX_EXECUTION_STEPS);
+        assert_eq!(machine.run(), Step::Halt(Halt::StepLimit));
+        assert_eq!(machine.step_count(), MAX_EXECUTION_STEPS);
+
+        let mut machine = TuringMachine::new(program).with_max_steps(25);
+        assert_eq!(machine.run(), Step::Halt(Halt::StepLimit));
+        assert_eq!(machine.step_count(), 25);
+        assert!(machine.is_step_limit_reached());
+        assert!(!machine.is_halted());
+
+        // Stepping past the limit keeps reporting it
+        assert_eq!(machine.step(), Step::Halt(Halt::StepLimit));
+        assert_eq!(machine.step_count(), 25);
+    }
+
+    #[test]
+    fn test_run_halts_at_step_limit() {
+        let program = crate::parser::parse(
+            r#"
+name: Halts at the limit
+mode: strict
+tape: a, a
+rules:
+  start:
+    a, R, start
+"#,
+        )
+        .unwrap();
+
+        // After 2 steps the head is on a blank, which has no transition
+        let mut machine = TuringMachine::new(program.clone()).with_max_steps(2);
+        assert!(matches!(
+            machine.run(),
+            Step::Halt(Halt::Err(TuringMachineError::UndefinedTransition(..)))
+        ));
+        assert_eq!(machine.step_count(), 2);
+        assert!(!machine.is_step_limit_reached());
+
+        let mut machine = TuringMachine::new(program).with_max_steps(1);
+        assert_eq!(machine.run(), Step::Halt(Halt::StepLimit));
+        assert!(machine.is_step_limit_reached());
+    }
+
+    #[test]
+    fn test_run_halts_before_step_limit() {
+        let program = create_simple_multi_tape_program();
+        let mut machine = TuringMachine::new(program).with_max_steps(1);
+
+        assert_eq!(machine.run(), Step::Halt(Halt::Ok));
+        assert!(!machine.is_step_limit_reached());
+    }
+
     #[test]
     fn test_multi_tape_get_current_symbols() {
         let program = create_simple_multi_tape_program();
@@ -462,10 +930,14 @@ mod multi_tape_tests {
             name: "Stay Direction Test".to_string(),
             mode: Mode::default(),
             initial_state: "start".to_string(),
+            stop_states: Vec::new(),
+            accept_states: Vec::new(),
+            reject_states: Vec::new(),
             tapes: vec!["a".to_string(), "x".to_string()],
             heads: vec![0, 0],
             blank: '-',
             rules,
+            source_map: None,
         };
 
         let mut machine = TuringMachine::new(program);
@@ -473,7 +945,314 @@ mod multi_tape_tests {
 
         // First head should stay at position 0, second head should move right
         assert_eq!(machine.heads(), &[0, 1]);
-        assert_eq!(machine.tapes(), &[vec!['b'], vec!['y', '-']]);
+        assert_eq!(contents(&machine), [vec!['b'], vec!['y', '-']]);
+    }
+
+    #[test]
+    fn test_step_back() {
+        let program = crate::parser::parse(include_str!("../examples/palindrome.tur")).unwrap();
+        let mut machine = TuringMachine::new(program).with_history(true);
+        machine.set_tapes_content(&["abba".to_string()]).unwrap();
+
+        let initial = (
+            contents(&machine),
+            machine.heads(),
+            machine.state().to_string(),
+        );
+        assert!(!machine.step_back());
+
+        // Run to completion, growing the tape on both sides, then rewind all the way.
+        machine.run();
+        let steps = machine.step_count();
+        assert!(steps > 0);
+
+        while machine.step_back() {}
+
+        assert_eq!(machine.step_count(), 0);
+        assert!(!machine.can_step_back());
+        assert_eq!(
+            (
+                contents(&machine),
+                machine.heads(),
+                machine.state().to_string()
+            ),
+            initial
+        );
+
+        // Stepping forward again retraces the same run.
+        assert_eq!(machine.run(), Step::Halt(Halt::Accept));
+        assert_eq!(machine.step_count(), steps);
+    }
+
+    #[test]
+    fn test_seek() {
+        let program = crate::parser::parse(include_str!("../examples/palindrome.tur")).unwrap();
+        let mut machine = TuringMachine::new(program).with_history(true);
+
+        assert_eq!(machine.seek(3), Step::Continue);
+        assert_eq!(machine.step_count(), 3);
+        let third = (
+            contents(&machine),
+            machine.heads(),
+            machine.state().to_string(),
+        );
+
+        machine.seek(6);
+        assert_eq!(machine.seek(3), Step::Continue);
+        assert_eq!(
+            (
+                contents(&machine),
+                machine.heads(),
+                machine.state().to_string()
+            ),
+            third
+        );
+
+        // Seeking past the end stops at the halting step.
+        assert_eq!(machine.seek(usize::MAX), Step::Halt(Halt::Accept));
+        assert_eq!(machine.seek(0), Step::Continue);
+        assert_eq!(machine.state(), machine.initial_state());
+    }
+
+    #[test]
+    fn test_run_until_break() {
+        let program = crate::parser::parse(include_str!("../examples/palindrome.tur")).unwrap();
+        let mut machine = TuringMachine::new(program);
+        machine.set_tapes_content(&["abba".to_string()]).unwrap();
+
+        let breakpoint = Breakpoint::State("check_last_a".to_string());
+        machine.add_breakpoint(breakpoint.clone());
+
+        assert_eq!(
+            machine.run_until_break(),
+            Pause::Breakpoint(breakpoint.clone())
+        );
+        assert_eq!(machine.state(), "check_last_a");
+        assert_eq!(machine.step_count(), 5);
+
+        // Resuming continues past the breakpoint.
+        let head = Breakpoint::Head {
+            tape: 0,
+            position: -1,
+        };
+        machine.add_breakpoint(head.clone());
+        assert_eq!(machine.run_until_break(), Pause::Breakpoint(head.clone()));
+        assert_eq!(machine.heads(), vec![-1]);
+
+        assert!(machine.toggle_breakpoint(Breakpoint::Write('Y')));
+        assert_eq!(
+            machine.run_until_break(),
+            Pause::Breakpoint(Breakpoint::Write('Y'))
+        );
+        assert_eq!(machine.symbols(), vec!['b']);
+
+        assert!(!machine.toggle_breakpoint(Breakpoint::Write('Y')));
+        assert!(machine.remove_breakpoint(&breakpoint));
+        assert!(!machine.remove_breakpoint(&breakpoint));
+        assert_eq!(machine.breakpoints(), std::slice::from_ref(&head));
+        machine.remove_breakpoint(&head);
+
+        assert_eq!(machine.run_until_break(), Pause::Halt(Halt::Accept));
+    }
+
+    #[test]
+    fn test_state_breakpoint_on_entry() {
+        let program = crate::parser::parse(
+            r#"
+name: Sweep
+tape: a, a, a
+rules:
+  start:
+    a, R, sweep
+  sweep:
+    a, R, sweep
+    _, L, done
+  done:
+"#,
+        )
+        .unwrap();
+        let mut machine = TuringMachine::new(program);
+        machine.add_breakpoint(Breakpoint::State("sweep".to_string()));
+
+        assert_eq!(
+            machine.run_until_break(),
+            Pause::Breakpoint(Breakpoint::State("sweep".to_string()))
+        );
+        assert_eq!(machine.step_count(), 1);
+
+        // The steps `sweep` loops on itself don't enter it again
+        assert_eq!(machine.run_until_break(), Pause::Halt(Halt::Ok));
+        assert_eq!(machine.step_count(), 4);
+    }
+
+    #[test]
+    fn test_read_and_step_breakpoints() {
+        let program = crate::parser::parse(include_str!("../examples/palindrome.tur")).unwrap();
+        let mut machine = TuringMachine::new(program);
+        machine.set_tapes_content(&["abba".to_string()]).unwrap();
+
+        machine.add_breakpoint(Breakpoint::Read(vec!['_']));
+        machine.add_breakpoint(Breakpoint::Steps(2));
+
+        assert_eq!(
+            machine.run_until_break(),
+            Pause::Breakpoint(Breakpoint::Steps(2))
+        );
+        assert_eq!(
+            machine.run_until_break(),
+            Pause::Breakpoint(Breakpoint::Read(vec!['_']))
+        );
+        assert_eq!(machine.step_count(), 4);
+    }
+
+    #[test]
+    fn test_loop_detection() {
+        let program = crate::parser::parse(
+            r#"
+name: Ping Pong
+tape: a, b
+rules:
+  right:
+    a -> a, R, left
+  left:
+    b -> b, L, right
+"#,
+        )
+        .unwrap();
+
+        let mut machine = TuringMachine::new(program.clone()).with_loop_detection(true);
+        assert_eq!(
+            machine.run(),
+            Step::Halt(Halt::Loop {
+                first_seen_step: 0,
+                period: 2
+            })
+        );
+        assert_eq!(machine.step_count(), 2);
+
+        // Without loop detection, the machine runs until the step limit
+        let mut machine = TuringMachine::new(program).with_max_steps(100);
+        assert!(!machine.detects_loops());
+        assert_eq!(machine.run(), Step::Halt(Halt::StepLimit));
+
+        // Loop detection can be turned on in the middle of a run
+        machine.reset();
+        machine.step();
+        assert!(machine.toggle_loop_detection());
+        assert_eq!(
+            machine.run(),
+            Step::Halt(Halt::Loop {
+                first_seen_step: 1,
+                period: 2
+            })
+        );
+        assert!(!machine.toggle_loop_detection());
+        assert!(!machine.detects_loops());
+    }
+
+    #[test]
+    fn test_loop_detection_ignores_visited_blanks() {
+        // Sweeps right over blanks and back, growing the tape but revisiting the same
+        // configuration after each round trip.
+        let program = crate::parser::parse(
+            r#"
+name: Sweep
+tapes:
+  [a]
+  [x]
+rules:
+  start:
+    [a, x] -> [a, x], [R, S], back
+  back:
+    [_, x] -> [_, x], [L, S], start
+"#,
+        )
+        .unwrap();
+
+        let mut machine = TuringMachine::new(program)
+            .with_history(true)
+            .with_loop_detection(true);
+        assert_eq!(
+            machine.run(),
+            Step::Halt(Halt::Loop {
+                first_seen_step: 0,
+                period: 2
+            })
+        );
+
+        // Stepping back forgets the undone configurations
+        assert!(machine.step_back());
+        assert_eq!(machine.step(), Step::Continue);
+        assert!(matches!(machine.step(), Step::Halt(Halt::Loop { .. })));
+    }
+
+    #[test]
+    fn test_wildcard_transitions() {
+        let program = crate::parser::parse(
+            r#"
+name: Wildcards
+tapes:
+  [a, b, c]
+  [x, y]
+rules:
+  start:
+    [c, *] -> [C, *], [S, S], halt
+    [*, *] -> [*, _], [R, R], start
+  halt:
+"#,
+        )
+        .unwrap();
+
+        let mut machine = TuringMachine::new(program);
+        assert_eq!(machine.run(), Step::Halt(Halt::Ok));
+
+        // Wildcard writes put back the symbols that were read
+        assert_eq!(contents(&machine), [vec!['a', 'b', 'C'], vec![' '; 3]]);
+    }
+
+    #[test]
+    fn test_without_history() {
+        let program = create_simple_multi_tape_program();
+
+        // History is off by default
+        let mut machine = TuringMachine::new(program.clone());
+        machine.step();
+        assert!(!machine.can_step_back());
+
+        let mut machine = TuringMachine::new(program).with_history(false);
+
+        machine.step();
+
+        assert!(!machine.can_step_back());
+        assert!(!machine.step_back());
+        assert_eq!(machine.step_count(), 1);
+    }
+
+    #[test]
+    fn test_two_way_infinite_tape() {
+        let program = crate::parser::parse(
+            r#"
+name: Sweep Left
+tape: a
+rules:
+  start:
+    a -> a, L, start
+    _ -> b, L, start
+"#,
+        )
+        .unwrap();
+
+        let mut machine = TuringMachine::new(program).with_max_steps(1000);
+
+        assert_eq!(machine.run(), Step::Halt(Halt::StepLimit));
+        assert_eq!(machine.heads(), &[-1000]);
+
+        // Coordinates of the initial content don't shift as the tape grows to the left.
+        let tape = &machine.tapes()[0];
+        assert_eq!(tape.start(), -1000);
+        assert_eq!(tape.get(0), 'a');
+        assert_eq!(tape.get(-1), 'b');
+        assert_eq!(tape.len(), 1001);
     }
 
     #[test]
@@ -485,7 +1264,7 @@ mod multi_tape_tests {
         machine.set_tape_content(0, "a_b").unwrap();
 
         // The '_' should be converted to the machine's blank symbol ('-')
-        assert_eq!(machine.tapes()[0], vec!['a', '-', 'b']);
+        assert_eq!(machine.tapes()[0].to_vec(), vec!['a', '-', 'b']);
     }
 
     #[test]
@@ -498,8 +1277,8 @@ mod multi_tape_tests {
         machine.set_tapes_content(&contents).unwrap();
 
         // The '_' should be converted to the machine's blank symbol ('-')
-        assert_eq!(machine.tapes()[0], vec!['a', '-', 'b']);
-        assert_eq!(machine.tapes()[1], vec!['x', '-', 'y']);
+        assert_eq!(machine.tapes()[0].to_vec(), vec!['a', '-', 'b']);
+        assert_eq!(machine.tapes()[1].to_vec(), vec!['x', '-', 'y']);
     }
 
     #[test]
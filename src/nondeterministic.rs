@@ -0,0 +1,298 @@
+//! This module implements execution of nondeterministic Turing Machine programs.
+//!
+//! A nondeterministic program may define several transitions for the same state and symbols.
+//! Rather than picking one, the `NondeterministicMachine` explores the whole computation tree
+//! breadth-first and accepts as soon as any branch accepts.
+
+use crate::machine::TuringMachine;
+use crate::trace::Trace;
+use crate::types::{
+    Halt, Program, Transition, TuringMachineError, MAX_EXPLORATION_DEPTH, MAX_EXPLORATION_WIDTH,
+};
+
+/// Explores every branch of a nondeterministic Turing Machine's computation.
+///
+/// Branches are expanded level by level, so the first accepting branch found is also one of
+/// the shortest. Exploration is bounded by a maximum depth (steps per branch) and a maximum
+/// width (live branches per level).
+pub struct NondeterministicMachine {
+    machine: TuringMachine,
+    decider: bool,
+    max_depth: usize,
+    max_width: usize,
+}
+
+/// The outcome of exploring a nondeterministic computation.
+#[derive(Clone)]
+pub struct Exploration {
+    /// The overall outcome:
+    /// - `Halt::Accept` (or `Halt::Ok` for programs without accept/reject states) if some branch
+    ///   accepted.
+    /// - `Halt::StepLimit` if no branch accepted but some were cut off by the depth or width limit.
+    /// - Otherwise the outcome of the first branch to reject, or of the first branch to halt.
+    pub halt: Halt,
+    /// The transitions taken by the reported branch, from the initial configuration.
+    pub trace: Vec<Transition>,
+    /// The final configuration of the reported branch. This is the accepting branch if there is
+    /// one, otherwise the first rejecting or halted branch, otherwise the initial configuration.
+    pub machine: TuringMachine,
+    /// The total number of configurations visited during exploration.
+    pub branches: usize,
+}
+
+/// A node in the computation tree, used to rebuild the trace of a branch.
+struct Node {
+    parent: Option<usize>,
+    transition: Transition,
+}
+
+impl NondeterministicMachine {
+    /// Creates a new `NondeterministicMachine` with the default exploration limits.
+    pub fn new(program: Program) -> Self {
+        Self {
+            decider: program.is_decider(),
+            machine: TuringMachine::new(program),
+            max_depth: MAX_EXPLORATION_DEPTH,
+            max_width: MAX_EXPLORATION_WIDTH,
+        }
+    }
+
+    /// Sets the maximum number of steps explored along any branch.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the maximum number of branches kept alive at each level of the tree.
+    pub fn with_max_width(mut self, max_width: usize) -> Self {
+        self.max_width = max_width;
+        self
+    }
+
+    /// Returns the maximum number of steps explored along any branch.
+    pub fn max_depth(&self) -> usize {
+        self.max_depth
+    }
+
+    /// Returns the maximum number of branches kept alive at each level of the tree.
+    pub fn max_width(&self) -> usize {
+        self.max_width
+    }
+
+    /// Sets the initial content of all tapes. See [`TuringMachine::set_tapes_content`].
+    pub fn set_tapes_content(&mut self, contents: &[String]) -> Result<(), TuringMachineError> {
+        self.machine.set_tapes_content(contents)
+    }
+
+    /// Explores the computation tree breadth-first until a branch accepts, every branch halts,
+    /// or the exploration limits are reached.
+    pub fn run(&self) -> Exploration {
+        let mut nodes: Vec<Node> = Vec::new();
+        let mut frontier = vec![(self.machine.clone(), None)];
+        let mut halted: Option<(Halt, TuringMachine, Option<usize>)> = None;
+        let mut truncated = false;
+        let mut branches = 1;
+
+        while !frontier.is_empty() {
+            let mut next = Vec::new();
+
+            for (machine, node) in frontier {
+                let transitions = machine.transitions();
+
+                if machine.is_halted() || transitions.is_empty() {
+                    let halt = machine.halt();
+                    if self.accepts(&halt) {
+                        return self.exploration(halt, machine, node, &nodes, branches);
+                    }
+
+                    // Report the first rejecting branch, or the first halted one if none rejects.
+                    let replace = match &halted {
+                        None => true,
+                        Some((Halt::Reject(_), ..)) => false,
+                        Some(_) => matches!(halt, Halt::Reject(_)),
+                    };
+                    if replace {
+                        halted = Some((halt, machine.clone(), node));
+                    }
+                    continue;
+                }
+
+                if machine.step_count() >= self.max_depth {
+                    truncated = true;
+                    continue;
+                }
+
+                for transition in transitions {
+                    if next.len() >= self.max_width {
+                        truncated = true;
+                        break;
+                    }
+
+                    let mut child = machine.clone();
+                    child.apply(transition);
+
+                    nodes.push(Node {
+                        parent: node,
+                        transition: transition.clone(),
+                    });
+                    next.push((child, Some(nodes.len() - 1)));
+                    branches += 1;
+                }
+            }
+
+            frontier = next;
+        }
+
+        match halted {
+            Some((halt, machine, node)) => {
+                let halt = if truncated { Halt::StepLimit } else { halt };
+                self.exploration(halt, machine, node, &nodes, branches)
+            }
+            None => Exploration {
+                halt: Halt::StepLimit,
+                trace: Vec::new(),
+                machine: self.machine.clone(),
+                branches,
+            },
+        }
+    }
+
+    /// Replays the reported branch of an exploration from the initial configuration, recording
+    /// each step in a `Trace`.
+    pub fn trace(&self, exploration: &Exploration, snapshots: bool) -> Trace {
+        let mut trace = Trace::new(snapshots);
+        trace.replay(&mut self.machine.clone(), &exploration.trace);
+        trace
+    }
+
+    /// Checks if a branch that halted with the given outcome accepts its input.
+    ///
+    /// Programs that declare accept or reject states accept only in an accept state; other
+    /// programs accept whenever a branch halts.
+    fn accepts(&self, halt: &Halt) -> bool {
+        match halt {
+            Halt::Accept => true,
+            Halt::Ok => !self.decider,
+            _ => false,
+        }
+    }
+
+    /// Builds an `Exploration` for the branch ending at `node`.
+    fn exploration(
+        &self,
+        halt: Halt,
+        machine: TuringMachine,
+        mut node: Option<usize>,
+        nodes: &[Node],
+        branches: usize,
+    ) -> Exploration {
+        let mut trace = Vec::new();
+        while let Some(i) = node {
+            trace.push(nodes[i].transition.clone());
+            node = nodes[i].parent;
+        }
+        trace.reverse();
+
+        Exploration {
+            halt,
+            trace,
+            machine,
+            branches,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    const CONTAINS_101: &str = include_str!("../examples/nondeterministic-substring.tur");
+
+    fn explore(input: &str) -> Exploration {
+        let program = parse(CONTAINS_101).unwrap();
+        let mut machine = NondeterministicMachine::new(program);
+        machine.set_tapes_content(&[input.to_string()]).unwrap();
+        machine.run()
+    }
+
+    #[test]
+    fn test_accepting_branch() {
+        let exploration = explore("110110");
+
+        assert_eq!(exploration.halt, Halt::Accept);
+        assert_eq!(exploration.machine.state(), "accept");
+
+        // The shortest accepting branch guesses that `101` starts at the second symbol.
+        let states: Vec<_> = exploration
+            .trace
+            .iter()
+            .map(|t| t.next_state.as_str())
+            .collect();
+        assert_eq!(states, vec!["scan", "one", "one-zero", "accept"]);
+        assert_eq!(exploration.machine.step_count(), exploration.trace.len());
+    }
+
+    #[test]
+    fn test_trace_accepting_branch() {
+        let program = parse(CONTAINS_101).unwrap();
+        let mut machine = NondeterministicMachine::new(program);
+        machine.set_tapes_content(&["0101".to_string()]).unwrap();
+
+        let exploration = machine.run();
+        let trace = machine.trace(&exploration, true);
+
+        assert_eq!(trace.steps.len(), exploration.trace.len());
+        let last = trace.steps.last().unwrap();
+        assert_eq!(last.next_state, "accept");
+        assert_eq!(last.heads, exploration.machine.heads());
+    }
+
+    #[test]
+    fn test_no_accepting_branch() {
+        let exploration = explore("1100");
+
+        assert!(matches!(exploration.halt, Halt::Reject(_)));
+        assert!(exploration.branches > 1);
+    }
+
+    #[test]
+    fn test_exploration_limits() {
+        let program = parse(CONTAINS_101).unwrap();
+        let mut machine = NondeterministicMachine::new(program).with_max_depth(2);
+        machine.set_tapes_content(&["0000101".to_string()]).unwrap();
+
+        assert_eq!(machine.run().halt, Halt::StepLimit);
+
+        let program = parse(CONTAINS_101).unwrap();
+        let mut machine = NondeterministicMachine::new(program).with_max_width(1);
+        machine.set_tapes_content(&["101".to_string()]).unwrap();
+
+        assert_eq!(machine.run().halt, Halt::StepLimit);
+    }
+
+    #[test]
+    fn test_non_decider_accepts_on_halt() {
+        let program = parse(
+            r#"
+name: Guess
+mode: nondeterministic
+tape: a
+rules:
+  start:
+    a -> a, R, loop
+    a -> b, S, done
+  loop:
+    _ -> _, S, loop
+  done:
+"#,
+        )
+        .unwrap();
+
+        let exploration = NondeterministicMachine::new(program).run();
+
+        assert_eq!(exploration.halt, Halt::Ok);
+        assert_eq!(exploration.machine.state(), "done");
+        assert_eq!(exploration.machine.tapes()[0].to_vec(), vec!['b']);
+    }
+}
@@ -2,10 +2,11 @@
 //! It defines the grammar for `.tur` files and functions to parse the input into a `Program` struct.
 
 use crate::{
-    analyzer::analyze,
+    analyzer::{analyze, analyze_with, AnalysisOptions},
+    syntax::{Location, SourceMap},
     types::{
         Direction, Mode, Program, Transition, TuringMachineError, DEFAULT_BLANK_SYMBOL,
-        INPUT_BLANK_SYMBOL,
+        INPUT_BLANK_SYMBOL, WILDCARD_SYMBOL,
     },
 };
 use pest::{
@@ -14,9 +15,20 @@ use pest::{
     Parser as PestParser, Span,
 };
 use pest_derive::Parser as PestParser;
-use std::collections::{HashMap, HashSet};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 
 type Tape = Vec<char>;
+/// Named symbol sets declared in the `symbols:` section.
+type SymbolSets = HashMap<String, Vec<char>>;
+/// Macros declared in the `macros:` section, by name.
+type Macros = HashMap<String, Macro>;
+/// Macro instances used as next states, with the action that uses them.
+type MacroCalls<'i> = Vec<(String, Span<'i>)>;
+
+/// The maximum number of states macro expansion may add to a program. This stops macros that
+/// keep instantiating themselves with ever larger arguments.
+const MAX_MACRO_INSTANCES: usize = 1000;
 
 /// Derives a `PestParser` for the Turing Machine grammar defined in `grammar.pest`.
 #[derive(PestParser)]
@@ -40,15 +52,91 @@ pub struct TuringMachineParser;
 /// * `Err(TuringMachineError::ParseError)` if there are any syntax errors.
 /// * `Err(TuringMachineError::ValidationError)` if the program fails validation.
 pub fn parse(input: &str) -> Result<Program, TuringMachineError> {
+    parse_with_imports(input, no_imports)
+}
+
+/// Parses the given input string into a `Program` struct, like `parse`, loading the programs it
+/// imports with `load`.
+///
+/// `load` is called with the path of each `import`, as written in the input. The rules of each
+/// imported program are added to the program, with their states prefixed by the import's
+/// namespace.
+pub fn parse_with_imports(
+    input: &str,
+    mut load: impl FnMut(&str) -> Result<Program, TuringMachineError>,
+) -> Result<Program, TuringMachineError> {
+    let program = parse_source(input, &mut load)?;
+
+    // Analyze the parsed program
+    analyze(&program)?;
+
+    Ok(program)
+}
+
+/// Parses the given input string into a `Program` struct, like `parse`, analyzing it with
+/// `options`.
+///
+/// With `options.unreachable_states` set to `Severity::Warning`, programs with unreachable
+/// states are returned rather than rejected. Use `analyzer::diagnose` to get the warnings.
+pub fn parse_with_options(
+    input: &str,
+    options: &AnalysisOptions,
+) -> Result<Program, TuringMachineError> {
+    let program = parse_source(input, &mut no_imports)?;
+    analyze_with(&program, options)?;
+
+    Ok(program)
+}
+
+/// Parses the given input string into a `Program` struct, like `parse`, without analyzing it.
+///
+/// This is for tools that report every analysis error themselves, e.g. with
+/// `analyzer::analysis_errors`, rather than stopping at the first one. Syntax errors and invalid
+/// sections are still returned as errors.
+pub fn parse_unchecked(input: &str) -> Result<Program, TuringMachineError> {
+    parse_source(input, &mut no_imports)
+}
+
+/// Parses the given input string into a `Program` struct, like `parse_unchecked`, loading the
+/// programs it imports with `load` like `parse_with_imports`.
+pub fn parse_unchecked_with_imports(
+    input: &str,
+    mut load: impl FnMut(&str) -> Result<Program, TuringMachineError>,
+) -> Result<Program, TuringMachineError> {
+    parse_source(input, &mut load)
+}
+
+/// Refuses imports, for sources that aren't loaded from a file.
+fn no_imports(path: &str) -> Result<Program, TuringMachineError> {
+    Err(TuringMachineError::FileError(format!(
+        "Can't import {path}: imports are only supported when loading a program from a file"
+    )))
+}
+
+/// Parses a program without analyzing it, moving its source map past the trimmed input.
+fn parse_source(
+    input: &str,
+    load: &mut dyn FnMut(&str) -> Result<Program, TuringMachineError>,
+) -> Result<Program, TuringMachineError> {
     let root = TuringMachineParser::parse(Rule::program, input.trim())
         .map_err(|e| TuringMachineError::ParseError(e.into()))? //
         .next()
         .unwrap();
 
-    let program = parse_program(root)?;
-
-    // Analyze the parsed program
-    analyze(&program)?;
+    let mut program = parse_program(root, load)?;
+
+    // Locations are found in the trimmed input, so move them down past the trimmed lines
+    let trimmed = &input[..input.len() - input.trim_start().len()];
+    let lines = trimmed.matches('\n').count();
+    if let Some(source_map) = &mut program.source_map {
+        for location in source_map
+            .states
+            .values_mut()
+            .chain(source_map.transitions.values_mut().flatten())
+        {
+            location.line += lines;
+        }
+    }
 
     Ok(program)
 }
@@ -57,14 +145,21 @@ pub fn parse(input: &str) -> Result<Program, TuringMachineError> {
 ///
 /// This function extracts the program's name, tapes, heads, blank symbol, rules, and initial state.
 /// It also performs initial validation checks for uniqueness and consistency of sections.
-fn parse_program(pair: Pair<Rule>) -> Result<Program, TuringMachineError> {
+fn parse_program(
+    pair: Pair<Rule>,
+    load: &mut dyn FnMut(&str) -> Result<Program, TuringMachineError>,
+) -> Result<Program, TuringMachineError> {
     let mut name: Option<String> = None;
     let mut mode: Option<Mode> = None;
     let mut tapes: Option<(Vec<Tape>, Vec<Vec<usize>>)> = None;
     let mut heads: Option<Vec<usize>> = None;
     let mut blank: Option<char> = None;
-    let mut rules: Option<HashMap<String, Vec<Transition>>> = None;
+    let mut rules: Option<Pair<Rule>> = None;
+    let mut macros: Option<Pair<Rule>> = None;
+    let mut imports: Vec<Import> = Vec::new();
+    let mut symbol_sets = SymbolSets::new();
     let mut initial_state: Option<String> = None;
+    let mut states = StateDeclarations::default();
     let mut seen = HashSet::new();
 
     // Parse top-level rules
@@ -78,7 +173,20 @@ fn parse_program(pair: Pair<Rule>) -> Result<Program, TuringMachineError> {
             Rule::name => name = Some(parse_inner_string(p)),
             Rule::mode => mode = Some(parse_mode(p)?),
             Rule::blank => blank = Some(parse_symbol(&parse_inner_string(p))),
-            Rule::rules => rules = Some(parse_transitions(p, &mut initial_state)?),
+            Rule::rules => rules = Some(p),
+            Rule::macros => macros = Some(p),
+            Rule::import => {
+                let import = parse_import(p, load)?;
+                if imports.iter().any(|i| i.namespace == import.namespace) {
+                    return Err(parse_error(
+                        &format!("Duplicate import namespace: {}", import.namespace),
+                        span,
+                    ));
+                }
+                imports.push(import);
+            }
+            Rule::states => states = parse_states(p),
+            Rule::symbol_sets => symbol_sets = parse_symbol_sets(p)?,
             Rule::tape | Rule::tapes => {
                 check_exclusive_rule(tapes, vec!["tape", "tapes"], span)?;
                 tapes = Some(parse_tapes(p));
@@ -95,10 +203,34 @@ fn parse_program(pair: Pair<Rule>) -> Result<Program, TuringMachineError> {
     let name = check_required_rule(name, vec!["name"])?;
     let mode = mode.unwrap_or_default();
     let rules = check_required_rule(rules, vec!["rules"])?;
-    let initial_state = check_required_rule(initial_state, vec!["initial_state"])?;
     let tapes = check_required_rule(tapes, vec!["tape", "tapes"])?;
     let blank = blank.unwrap_or(DEFAULT_BLANK_SYMBOL);
 
+    // Rules are parsed last, since they may use symbols from every other section
+    let sections: Vec<_> = macros.iter().chain([&rules]).collect();
+    let symbols = Symbols {
+        alphabet: collect_alphabet(&tapes.0, &symbol_sets, &sections, blank),
+        sets: symbol_sets,
+        blank,
+    };
+    let macros = match macros {
+        Some(pair) => parse_macros(pair, &symbols)?,
+        None => Macros::new(),
+    };
+    let mut calls = MacroCalls::new();
+    let mut source_map = SourceMap::default();
+    let mut rules = parse_transitions(
+        rules,
+        &symbols,
+        &mut initial_state,
+        &mut calls,
+        &mut source_map,
+    )?;
+    expand_macros(&mut rules, &macros, calls, &mut source_map)?;
+    merge_imports(&mut rules, &mut states, blank, imports, &mut source_map)?;
+    // An explicit `start:` takes precedence over the first state in `rules:`
+    let initial_state = check_required_rule(states.start.or(initial_state), vec!["initial_state"])?;
+
     // Rewrite blank symbol
     let tapes = rewrite_tapes(tapes, blank);
     let heads = heads.unwrap_or_else(|| vec![0; tapes.len()]);
@@ -116,6 +248,10 @@ fn parse_program(pair: Pair<Rule>) -> Result<Program, TuringMachineError> {
         blank,
         rules,
         initial_state,
+        stop_states: states.stop,
+        accept_states: states.accept,
+        reject_states: states.reject,
+        source_map: Some(source_map),
     })
 }
 
@@ -124,13 +260,227 @@ fn parse_mode(pair: Pair<'_, Rule>) -> Result<Mode, TuringMachineError> {
     match parse_inner_string(pair).as_str() {
         "normal" => Ok(Mode::Normal),
         "strict" => Ok(Mode::Strict),
+        "nondeterministic" => Ok(Mode::Nondeterministic),
         mode => Err(parse_error(
-            &format!("Invalid mode: {mode}. Expected 'normal' or 'strict'",),
+            &format!("Invalid mode: {mode}. Expected 'normal', 'strict' or 'nondeterministic'",),
             span,
         )),
     }
 }
 
+/// State declarations collected from the `states:` section.
+#[derive(Default)]
+struct StateDeclarations {
+    start: Option<String>,
+    stop: Vec<String>,
+    accept: Vec<String>,
+    reject: Vec<String>,
+}
+
+/// Parses the states section from a `Pair<Rule::states>`.
+///
+/// It extracts the optional `start:` state and the `stop:`, `accept:` and `reject:` state lists.
+fn parse_states(pair: Pair<Rule>) -> StateDeclarations {
+    let mut states = StateDeclarations::default();
+
+    // Rule: states > (state_start > state) | ((state_stop | state_accept | state_reject) > [state])
+    for p in pair.into_inner() {
+        let list = match p.as_rule() {
+            Rule::state_start => {
+                states.start = p.into_inner().next().map(|s| s.as_str().to_string());
+                continue;
+            }
+            Rule::state_stop => &mut states.stop,
+            Rule::state_accept => &mut states.accept,
+            Rule::state_reject => &mut states.reject,
+            _ => continue,
+        };
+
+        for state in p.into_inner() {
+            let state = state.as_str().to_string();
+            if !list.contains(&state) {
+                list.push(state);
+            }
+        }
+    }
+
+    states
+}
+
+/// A program imported with `import "path" as namespace`.
+struct Import<'i> {
+    namespace: String,
+    program: Program,
+    span: Span<'i>,
+}
+
+/// Parses an import from a `Pair<Rule::import>`, loading the imported program with `load`.
+fn parse_import<'i>(
+    pair: Pair<'i, Rule>,
+    load: &mut dyn FnMut(&str) -> Result<Program, TuringMachineError>,
+) -> Result<Import<'i>, TuringMachineError> {
+    let span = pair.as_span();
+    let mut pairs = pair.into_inner();
+    let path = parse_string(&mut pairs);
+    let namespace = parse_string(&mut pairs);
+
+    let program =
+        load(&path).map_err(|e| parse_error(&format!("Failed to import {path}: {e}"), span))?;
+
+    Ok(Import {
+        namespace,
+        program,
+        span,
+    })
+}
+
+/// Adds the rules of each imported program to `rules`, with every state prefixed by the
+/// import's namespace (e.g. `scan.start`), and its stop, accept and reject states to `states`.
+///
+/// States without transitions in an imported program, including the ones it only goes to such
+/// as `halt`, are where it halts, so the importing program may define them to continue from
+/// there, and they no longer halt. Any other state it defines in the namespace is a duplicate.
+/// The imported states and their transitions are located at the `import`.
+///
+/// The imported program's blank symbol is rewritten to `_`, so its transitions read and write
+/// the blank symbol of the importing program.
+fn merge_imports(
+    rules: &mut HashMap<String, Vec<Transition>>,
+    states: &mut StateDeclarations,
+    blank: char,
+    imports: Vec<Import>,
+    source_map: &mut SourceMap,
+) -> Result<(), TuringMachineError> {
+    for import in imports {
+        let namespaced = |state: &str| format!("{}.{}", import.namespace, state);
+        let at = location(import.span);
+        let program = import.program;
+
+        // The imported blank symbol is the importer's, whatever each program calls it
+        let rewrite = |symbols: Vec<char>| -> Vec<char> {
+            symbols
+                .into_iter()
+                .map(|symbol| {
+                    if symbol == program.blank && program.blank != blank {
+                        INPUT_BLANK_SYMBOL
+                    } else {
+                        symbol
+                    }
+                })
+                .collect()
+        };
+
+        // States the imported program halts in without defining them, such as `halt`
+        let implicit: HashSet<String> = program
+            .rules
+            .values()
+            .flatten()
+            .map(|transition| transition.next_state.clone())
+            .filter(|state| !program.rules.contains_key(state))
+            .collect();
+
+        // The imported program stops in them unless it declares otherwise
+        let stop_states: Vec<String> = implicit
+            .iter()
+            .filter(|state| !program.is_stop_state(state))
+            .chain(&program.stop_states)
+            .cloned()
+            .collect();
+
+        let mut continued = HashSet::new();
+        for state in &implicit {
+            let state = namespaced(state);
+            match rules.entry(state) {
+                Entry::Occupied(entry) => {
+                    continued.insert(entry.key().clone());
+                }
+                Entry::Vacant(entry) => {
+                    source_map.states.insert(entry.key().clone(), at);
+                    entry.insert(Vec::new());
+                }
+            }
+        }
+
+        for (state, transitions) in program.rules {
+            let state = namespaced(&state);
+            let transitions: Vec<Transition> = transitions
+                .into_iter()
+                .map(|transition| Transition {
+                    read: rewrite(transition.read),
+                    write: rewrite(transition.write),
+                    next_state: namespaced(&transition.next_state),
+                    ..transition
+                })
+                .collect();
+
+            match rules.get(&state) {
+                Some(_) if transitions.is_empty() => {
+                    continued.insert(state);
+                }
+                Some(_) => {
+                    return Err(parse_error(
+                        &format!("Duplicate transition rule: {state}"),
+                        import.span,
+                    ))
+                }
+                None => {
+                    source_map.states.insert(state.clone(), at);
+                    source_map
+                        .transitions
+                        .insert(state.clone(), vec![at; transitions.len()]);
+                    rules.insert(state, transitions);
+                }
+            }
+        }
+
+        for (imported, declared) in [
+            (&stop_states, &mut states.stop),
+            (&program.accept_states, &mut states.accept),
+            (&program.reject_states, &mut states.reject),
+        ] {
+            for state in imported.iter().map(|state| namespaced(state)) {
+                if !continued.contains(&state) && !declared.contains(&state) {
+                    declared.push(state);
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses the symbol sets section from a `Pair<Rule::symbol_sets>`.
+///
+/// Each set maps a name to the symbols it stands for in transition reads.
+fn parse_symbol_sets(pair: Pair<Rule>) -> Result<SymbolSets, TuringMachineError> {
+    let mut sets = SymbolSets::new();
+
+    // Rule: symbol_sets > [symbol_set > set_name, symbols > [symbol]]
+    for set_pair in pair.into_inner() {
+        let span = set_pair.as_span();
+        let mut pairs = set_pair.into_inner();
+        let name = parse_string(&mut pairs);
+        let symbols: Vec<char> = pairs
+            .next()
+            .unwrap()
+            .into_inner()
+            .map(|symbol| parse_symbol(symbol.as_str()))
+            .collect();
+
+        if symbols.contains(&WILDCARD_SYMBOL) {
+            return Err(parse_error(
+                &format!("Symbol set {name} can't contain the wildcard symbol"),
+                span,
+            ));
+        }
+        if sets.insert(name.clone(), symbols).is_some() {
+            return Err(parse_error(&format!("Duplicate symbol set: {name}"), span));
+        }
+    }
+
+    Ok(sets)
+}
+
 /// Parses tape definitions from a `Pair<Rule::tape>` or `Pair<Rule::tapes>`.
 ///
 /// It extracts the symbols for each tape and records the positions of any `INPUT_BLANK_SYMBOL`s
@@ -161,6 +511,12 @@ fn parse_tapes(pair: Pair<Rule>) -> (Vec<Vec<char>>, Vec<Vec<usize>>) {
     (tapes, blank_indices)
 }
 
+/// Returns the location of the start of a `Span`.
+fn location(span: Span) -> Location {
+    let (line, column) = span.start_pos().line_col();
+    Location { line, column }
+}
+
 /// Creates a `TuringMachineError::ParseError` from a message and a `Span`.
 fn parse_error(msg: &str, span: Span) -> TuringMachineError {
     TuringMachineError::ParseError(Box::new(Error::new_from_span(
@@ -192,19 +548,79 @@ fn parse_heads(pair: Pair<Rule>) -> Vec<usize> {
     positions
 }
 
+/// The symbols available to transition rules.
+struct Symbols {
+    /// Named symbol sets declared in the `symbols:` section.
+    sets: SymbolSets,
+    /// Every symbol used in the program, which symbol variables range over. The blank symbol
+    /// is written as `INPUT_BLANK_SYMBOL`.
+    alphabet: Vec<char>,
+    blank: char,
+}
+
+/// A read or write symbol in a transition rule, before expansion.
+enum Pattern {
+    /// A literal symbol, or the wildcard.
+    Symbol(char),
+    /// Any symbol in a named set.
+    Set(Vec<char>),
+    /// A symbol variable, bound to the symbol read where it first appears.
+    Variable(String),
+}
+
+/// Collects every symbol used in the tapes, symbol sets and rule `sections`, in sorted order.
+///
+/// The blank symbol is written as `INPUT_BLANK_SYMBOL`, and the wildcard is left out.
+fn collect_alphabet(
+    tapes: &[Tape],
+    sets: &SymbolSets,
+    sections: &[&Pair<Rule>],
+    blank: char,
+) -> Vec<char> {
+    let rule_symbols = sections
+        .iter()
+        .flat_map(|&section| section.clone().into_inner().flatten())
+        .filter(|pair| pair.as_rule() == Rule::symbol)
+        .map(|pair| parse_symbol(pair.as_str()));
+
+    let alphabet: BTreeSet<char> = tapes
+        .iter()
+        .flatten()
+        .copied()
+        .chain(sets.values().flatten().copied())
+        .chain(rule_symbols)
+        .chain([INPUT_BLANK_SYMBOL])
+        .map(|symbol| {
+            if symbol == blank {
+                INPUT_BLANK_SYMBOL
+            } else {
+                symbol
+            }
+        })
+        .filter(|&symbol| symbol != WILDCARD_SYMBOL)
+        .collect();
+
+    alphabet.into_iter().collect()
+}
+
 /// Parses the transition rules section from a `Pair<Rule::rules>`.
 ///
 /// It extracts each state's transitions and sets the first encountered state as the initial state.
-/// It also checks for duplicate transition rules for the same state.
-fn parse_transitions(
-    pair: Pair<Rule>,
+/// It also checks for duplicate transition rules for the same state, and records where each
+/// state and transition is defined in `source_map`.
+fn parse_transitions<'i>(
+    pair: Pair<'i, Rule>,
+    symbols: &Symbols,
     initial_state: &mut Option<String>,
+    calls: &mut MacroCalls<'i>,
+    source_map: &mut SourceMap,
 ) -> Result<HashMap<String, Vec<Transition>>, TuringMachineError> {
     let mut transitions = HashMap::new();
 
     for transition_pair in pair.into_inner() {
         let span = transition_pair.as_span();
-        let (state, actions) = parse_multi_tape_transition(transition_pair)?;
+        let (state, actions, locations) =
+            parse_multi_tape_transition(transition_pair, symbols, calls)?;
 
         // Set first state as initial state
         if initial_state.is_none() {
@@ -219,6 +635,8 @@ fn parse_transitions(
             ));
         }
 
+        source_map.states.insert(state.clone(), location(span));
+        source_map.transitions.insert(state.clone(), locations);
         transitions.insert(state, actions);
     }
 
@@ -227,112 +645,507 @@ fn parse_transitions(
 
 /// Parses a single multi-tape transition rule from a `Pair<Rule::transition>`.
 ///
-/// It extracts the source state and a list of actions (transitions) associated with that state.
-fn parse_multi_tape_transition(
-    pair: Pair<Rule>,
-) -> Result<(String, Vec<Transition>), TuringMachineError> {
+/// It extracts the source state and a list of actions (transitions) associated with that state,
+/// with the location of each.
+fn parse_multi_tape_transition<'i>(
+    pair: Pair<'i, Rule>,
+    symbols: &Symbols,
+    calls: &mut MacroCalls<'i>,
+) -> Result<(String, Vec<Transition>, Vec<Location>), TuringMachineError> {
     let mut pairs = pair.into_inner();
-    let state = parse_string(&mut pairs);
-    let mut actions = Vec::new();
+    let state = parse_state_name(&mut pairs);
+    let (actions, locations) = parse_actions(pairs, symbols, calls)?;
+
+    Ok((state, actions, locations))
+}
+
+/// Parses the actions of a state or macro from `Pairs<Rule::action>`.
+///
+/// Actions using symbol variables skip the symbols already handled by earlier actions, since
+/// their transitions could never be taken. Next states that are macro instances are added to
+/// `calls`. Returns the transitions, and the location of the action each comes from.
+fn parse_actions<'i>(
+    pairs: Pairs<'i, Rule>,
+    symbols: &Symbols,
+    calls: &mut MacroCalls<'i>,
+) -> Result<(Vec<Transition>, Vec<Location>), TuringMachineError> {
+    let mut actions: Vec<Transition> = Vec::new();
+    let mut locations = Vec::new();
 
     for p in pairs {
         if p.as_rule() == Rule::action {
+            let span = p.as_span();
             for inner in p.into_inner() {
-                match inner.as_rule() {
-                    Rule::single_tape_action => {
-                        actions.push(parse_single_tape_action(inner)?);
+                let (transitions, has_variables) = match inner.as_rule() {
+                    Rule::single_tape_action => parse_single_tape_action(inner, symbols)?,
+                    Rule::multi_tape_action => parse_multi_tape_action(inner, symbols)?,
+                    _ => continue,
+                };
+
+                for transition in transitions {
+                    let shadowed = has_variables
+                        && actions
+                            .iter()
+                            .any(|earlier| earlier.covers(&transition, symbols.blank));
+                    if shadowed {
+                        continue;
                     }
-                    Rule::multi_tape_action => {
-                        actions.push(parse_multi_tape_action(inner)?);
+
+                    if parse_macro_instance(&transition.next_state).is_some() {
+                        calls.push((transition.next_state.clone(), span));
                     }
-                    _ => {} //
+                    actions.push(transition);
+                    locations.push(location(span));
                 }
             }
         }
     }
 
-    Ok((state, actions))
+    Ok((actions, locations))
+}
+
+/// A parameterized state declared in the `macros:` section.
+///
+/// Each instance of a macro, such as `scan(done)`, becomes a state with the macro's transitions,
+/// where next states naming a parameter are replaced by the matching argument.
+struct Macro {
+    params: Vec<String>,
+    transitions: Vec<Transition>,
+    location: Location,
+    locations: Vec<Location>,
+}
+
+/// Parses the macros section from a `Pair<Rule::macros>`.
+///
+/// It also checks that every macro instance used in a macro refers to a declared macro with the
+/// right number of arguments.
+fn parse_macros(pair: Pair<Rule>, symbols: &Symbols) -> Result<Macros, TuringMachineError> {
+    let mut macros = Macros::new();
+    let mut calls = MacroCalls::new();
+
+    // Rule: macros > [macro_def > state, macro_params > [state], [action]]
+    for macro_pair in pair.into_inner() {
+        let span = macro_pair.as_span();
+        let mut pairs = macro_pair.into_inner();
+        let name = parse_string(&mut pairs);
+
+        let mut params: Vec<String> = Vec::new();
+        for param in pairs.next().unwrap().into_inner() {
+            let param = param.as_str().to_string();
+            if params.contains(&param) {
+                return Err(parse_error(
+                    &format!("Duplicate parameter {param} in macro {name}"),
+                    span,
+                ));
+            }
+            params.push(param);
+        }
+
+        let (transitions, locations) = parse_actions(pairs, symbols, &mut calls)?;
+        if macros
+            .insert(
+                name.clone(),
+                Macro {
+                    params,
+                    transitions,
+                    location: location(span),
+                    locations,
+                },
+            )
+            .is_some()
+        {
+            return Err(parse_error(&format!("Duplicate macro: {name}"), span));
+        }
+    }
+
+    for (instance, span) in &calls {
+        let (name, args) = parse_macro_instance(instance).unwrap();
+        check_macro_arguments(&macros, name, &args, *span)?;
+    }
+
+    Ok(macros)
+}
+
+/// Adds a state for every macro instance reachable from the rules, named after the instance
+/// (e.g. `scan(done)`). States already defined in the rules are left as they are.
+///
+/// Errors point at the action in `rules:` whose next state led to the failing instance. The
+/// states of the instances are located at their macro in `source_map`.
+fn expand_macros(
+    rules: &mut HashMap<String, Vec<Transition>>,
+    macros: &Macros,
+    calls: MacroCalls,
+    source_map: &mut SourceMap,
+) -> Result<(), TuringMachineError> {
+    let mut queue = VecDeque::from(calls);
+    let mut instances = 0;
+
+    while let Some((instance, span)) = queue.pop_front() {
+        if rules.contains_key(&instance) {
+            continue;
+        }
+
+        let (name, args) = parse_macro_instance(&instance).unwrap();
+        let definition = check_macro_arguments(macros, name, &args, span)?;
+
+        instances += 1;
+        if instances > MAX_MACRO_INSTANCES {
+            return Err(parse_error(
+                &format!(
+                    "Macro expansion exceeds {MAX_MACRO_INSTANCES} states while expanding {instance}"
+                ),
+                span,
+            ));
+        }
+
+        let transitions: Vec<Transition> = definition
+            .transitions
+            .iter()
+            .map(|transition| Transition {
+                next_state: substitute_params(&transition.next_state, &definition.params, &args),
+                ..transition.clone()
+            })
+            .collect();
+
+        for transition in &transitions {
+            if parse_macro_instance(&transition.next_state).is_some() {
+                queue.push_back((transition.next_state.clone(), span));
+            }
+        }
+
+        source_map
+            .states
+            .insert(instance.clone(), definition.location);
+        source_map
+            .transitions
+            .insert(instance.clone(), definition.locations.clone());
+        rules.insert(instance, transitions);
+    }
+
+    Ok(())
+}
+
+/// Checks that a macro instance names a declared macro and passes one argument per parameter.
+fn check_macro_arguments<'m>(
+    macros: &'m Macros,
+    name: &str,
+    args: &[&str],
+    span: Span,
+) -> Result<&'m Macro, TuringMachineError> {
+    let definition = macros
+        .get(name)
+        .ok_or_else(|| parse_error(&format!("Undefined macro: {name}"), span))?;
+
+    if args.len() != definition.params.len() {
+        return Err(parse_error(
+            &format!(
+                "Macro {name} expects {} argument(s), got {}",
+                definition.params.len(),
+                args.len()
+            ),
+            span,
+        ));
+    }
+
+    Ok(definition)
+}
+
+/// Splits a macro instance such as `scan(done,mark(halt))` into the macro name and its
+/// arguments, or returns `None` for a plain state name.
+fn parse_macro_instance(state: &str) -> Option<(&str, Vec<&str>)> {
+    let (name, rest) = state.split_once('(')?;
+    let inner = rest.strip_suffix(')')?;
+
+    let mut args = Vec::new();
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in inner.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                args.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    args.push(&inner[start..]);
+
+    Some((name, args))
+}
+
+/// Replaces every state name in `state` that is one of the `params` with the matching argument,
+/// including inside macro instance arguments.
+fn substitute_params(state: &str, params: &[String], args: &[&str]) -> String {
+    let mut result = String::new();
+    let mut rest = state;
+
+    while !rest.is_empty() {
+        let end = rest.find(['(', ')', ',']).unwrap_or(rest.len());
+        let name = &rest[..end];
+        match params.iter().position(|param| param == name) {
+            Some(i) => result.push_str(args[i]),
+            None => result.push_str(name),
+        }
+
+        // Keep the delimiter, which is a single byte
+        let next = (end + 1).min(rest.len());
+        result.push_str(&rest[end..next]);
+        rest = &rest[next..];
+    }
+
+    result
 }
 
 /// Parses a single-tape action from a `Pair<Rule::single_tape_action>`.
 ///
 /// It extracts the read symbol, write symbol (defaults to read if omitted), direction, and next state.
-fn parse_single_tape_action(pair: Pair<Rule>) -> Result<Transition, TuringMachineError> {
+/// Returns the expanded transitions, and whether the action uses symbol variables.
+fn parse_single_tape_action(
+    pair: Pair<Rule>,
+    symbols: &Symbols,
+) -> Result<(Vec<Transition>, bool), TuringMachineError> {
+    let span = pair.as_span();
     let mut pairs = pair.into_inner();
-    let read = parse_symbol_from_pairs(&mut pairs);
+    let read = parse_pattern(pairs.next().unwrap(), symbols)?;
 
-    // If `write` is omitted, we'll make `write` equal to `read`
+    // If `write` is omitted, we'll write back what was read
     let write = match pairs.peek().unwrap().as_rule() {
-        Rule::direction => read,
-        _ => parse_symbol_from_pairs(&mut pairs),
+        Rule::direction => None,
+        _ => Some(vec![parse_write_pattern(pairs.next().unwrap(), symbols)?]),
     };
 
     let direction = parse_direction(pairs.next().unwrap())?;
-    let next_state = parse_string(&mut pairs);
+    let next_state = parse_state_name(&mut pairs);
 
-    Ok(Transition {
-        read: vec![read],
-        write: vec![write],
-        directions: vec![direction],
-        next_state,
-    })
+    expand_action(
+        vec![read],
+        write,
+        vec![direction],
+        &next_state,
+        symbols,
+        span,
+    )
 }
 
 /// Parses a multi-tape action from a `Pair<Rule::multi_tape_action>`.
 ///
 /// It extracts the read symbols, write symbols, directions, and next state for all tapes.
 /// It also validates that the number of read symbols, write symbols, and directions are consistent.
-fn parse_multi_tape_action(pair: Pair<Rule>) -> Result<Transition, TuringMachineError> {
+/// Returns the expanded transitions, and whether the action uses symbol variables.
+fn parse_multi_tape_action(
+    pair: Pair<Rule>,
+    symbols: &Symbols,
+) -> Result<(Vec<Transition>, bool), TuringMachineError> {
     let span = pair.as_span();
     let mut pairs = pair.into_inner();
 
     // Parse read symbols
-    let read = parse_multi_tape_symbols(pairs.next().unwrap())?;
+    let read = parse_patterns(pairs.next().unwrap(), symbols)?;
 
-    // Parse write symbols (or use read symbols if omitted)
+    // Parse write symbols (or write back the read symbols if omitted)
     let write = match pairs.peek().unwrap().as_rule() {
-        Rule::directions => read.clone(),
-        _ => parse_multi_tape_symbols(pairs.next().unwrap())?,
+        Rule::directions => None,
+        _ => Some(
+            pairs
+                .next()
+                .unwrap()
+                .into_inner()
+                .map(|pair| parse_write_pattern(pair, symbols))
+                .collect::<Result<_, _>>()?,
+        ),
     };
 
     // Parse directions
     let directions = parse_directions(pairs.next().unwrap())?;
 
     // Parse next state
-    let next_state = parse_string(&mut pairs);
+    let next_state = parse_state_name(&mut pairs);
 
     // Validate that all arrays have the same length
-    if read.len() != write.len() || read.len() != directions.len() {
+    let write_len = write.as_ref().map_or(read.len(), Vec::len);
+    if read.len() != write_len || read.len() != directions.len() {
         return Err(parse_error(
             &format!(
                 "Inconsistent multi-tape action: read={}, write={}, directions={}",
                 read.len(),
-                write.len(),
+                write_len,
                 directions.len()
             ),
             span,
         ));
     }
 
-    Ok(Transition {
-        read,
-        write,
-        directions,
-        next_state,
-    })
+    expand_action(read, write, directions, &next_state, symbols, span)
+}
+
+/// Parses a list of symbols from a `Pair<Rule::multi_tape_reads>`.
+fn parse_patterns(pair: Pair<Rule>, symbols: &Symbols) -> Result<Vec<Pattern>, TuringMachineError> {
+    pair.into_inner()
+        .map(|pair| parse_pattern(pair, symbols))
+        .collect()
 }
 
-/// Parses a list of multi-tape symbols from a `Pair<Rule::multi_tape_symbols>`.
-fn parse_multi_tape_symbols(pair: Pair<Rule>) -> Result<Vec<char>, TuringMachineError> {
-    let mut symbols = Vec::new();
+/// Parses a symbol from a `Pair<Rule::symbol>`, `Pair<Rule::set_name>` or `Pair<Rule::variable>`.
+fn parse_pattern(pair: Pair<Rule>, symbols: &Symbols) -> Result<Pattern, TuringMachineError> {
+    match pair.as_rule() {
+        Rule::variable => Ok(Pattern::Variable(pair.as_str()[1..].to_string())),
+        Rule::set_name => symbols
+            .sets
+            .get(pair.as_str())
+            .cloned()
+            .map(Pattern::Set)
+            .ok_or_else(|| {
+                parse_error(
+                    &format!("Undefined symbol set: {}", pair.as_str()),
+                    pair.as_span(),
+                )
+            }),
+        _ => Ok(Pattern::Symbol(parse_symbol(pair.as_str()))),
+    }
+}
+
+/// Parses a written symbol like `parse_pattern`, except that symbol sets can't be written.
+fn parse_write_pattern(pair: Pair<Rule>, symbols: &Symbols) -> Result<Pattern, TuringMachineError> {
+    if pair.as_rule() == Rule::set_name {
+        return Err(parse_error(
+            &format!("Symbol sets can only be read: {}", pair.as_str()),
+            pair.as_span(),
+        ));
+    }
+
+    parse_pattern(pair, symbols)
+}
+
+/// Builds one transition for each combination of symbols an action reads, in order.
+///
+/// Symbol sets read each of their symbols. A symbol variable reads every symbol in the
+/// alphabet where it first appears, and the same symbol wherever else it's read; it's
+/// replaced by that symbol in writes and in the next state's name. A wildcard write on a tape
+/// whose read is a known symbol is replaced by that symbol, so only wildcard reads are left to
+/// be written back at runtime.
+///
+/// Returns the transitions, and whether the action uses symbol variables.
+fn expand_action(
+    reads: Vec<Pattern>,
+    writes: Option<Vec<Pattern>>,
+    directions: Vec<Direction>,
+    next_state: &str,
+    symbols: &Symbols,
+    span: Span,
+) -> Result<(Vec<Transition>, bool), TuringMachineError> {
+    let has_variables = reads
+        .iter()
+        .any(|pattern| matches!(pattern, Pattern::Variable(_)));
+
+    // Check that written variables are bound by a read
+    let is_bound = |name: &str| {
+        reads
+            .iter()
+            .any(|pattern| matches!(pattern, Pattern::Variable(n) if n == name))
+    };
+    let unbound = writes
+        .iter()
+        .flatten()
+        .find_map(|pattern| match pattern {
+            Pattern::Variable(name) if !is_bound(name) => Some(name.clone()),
+            _ => None,
+        })
+        .or_else(|| substitute_variables(next_state, |name| is_bound(name).then_some(' ')).err());
+    if let Some(name) = unbound {
+        return Err(parse_error(
+            &format!("Undefined symbol variable: ${name}"),
+            span,
+        ));
+    }
+
+    let mut combinations: Vec<(Vec<char>, HashMap<&str, char>)> =
+        vec![(Vec::new(), HashMap::new())];
+    for pattern in &reads {
+        combinations = combinations
+            .into_iter()
+            .flat_map(|(read, bindings)| {
+                let choices = match pattern {
+                    Pattern::Symbol(symbol) => vec![*symbol],
+                    Pattern::Set(set) => set.clone(),
+                    Pattern::Variable(name) => match bindings.get(name.as_str()) {
+                        Some(&symbol) => vec![symbol],
+                        None => symbols.alphabet.clone(),
+                    },
+                };
+
+                choices.into_iter().map(move |symbol| {
+                    let mut read = read.clone();
+                    let mut bindings = bindings.clone();
+                    read.push(symbol);
+                    if let Pattern::Variable(name) = pattern {
+                        bindings.insert(name.as_str(), symbol);
+                    }
+                    (read, bindings)
+                })
+            })
+            .collect();
+    }
+
+    let transitions = combinations
+        .into_iter()
+        .map(|(read, bindings)| {
+            let write = match &writes {
+                None => read.clone(),
+                Some(writes) => writes
+                    .iter()
+                    .zip(&read)
+                    .map(|(write, &read)| match write {
+                        Pattern::Symbol(WILDCARD_SYMBOL) => read,
+                        Pattern::Symbol(symbol) => *symbol,
+                        Pattern::Variable(name) => bindings[name.as_str()],
+                        Pattern::Set(_) => unreachable!("symbol sets are rejected when parsed"),
+                    })
+                    .collect(),
+            };
+            let next_state =
+                substitute_variables(next_state, |name| bindings.get(name).copied()).unwrap();
+
+            Transition {
+                read,
+                write,
+                directions: directions.clone(),
+                next_state,
+            }
+        })
+        .collect();
+
+    Ok((transitions, has_variables))
+}
 
-    for symbol_pair in pair.into_inner() {
-        if symbol_pair.as_rule() == Rule::symbol {
-            symbols.push(parse_symbol(symbol_pair.as_str()));
+/// Replaces each symbol variable (`$` followed by a name) in a state name with the symbol
+/// returned by `lookup`, or returns the name of the first variable it doesn't know.
+fn substitute_variables(
+    state: &str,
+    lookup: impl Fn(&str) -> Option<char>,
+) -> Result<String, String> {
+    let mut result = String::new();
+    let mut rest = state;
+
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(rest.len());
+        let name = &rest[..end];
+        if name.is_empty() {
+            result.push('$');
+            continue;
         }
+
+        result.push(lookup(name).ok_or_else(|| name.to_string())?);
+        rest = &rest[end..];
     }
 
-    Ok(symbols)
+    result.push_str(rest);
+    Ok(result)
 }
 
 /// Parses a list of directions from a `Pair<Rule::directions>`.
@@ -365,7 +1178,7 @@ fn parse_direction(pair: Pair<Rule>) -> Result<Direction, TuringMachineError> {
 }
 
 /// Parses a single character symbol from a string, handling quoted and unquoted symbols.
-fn parse_symbol(input: &str) -> char {
+pub(crate) fn parse_symbol(input: &str) -> char {
     input
         .trim_matches('\'')
         .chars()
@@ -373,9 +1186,9 @@ fn parse_symbol(input: &str) -> char {
         .unwrap_or(DEFAULT_BLANK_SYMBOL)
 }
 
-/// Parses a single character symbol from a `Pairs` iterator.
-fn parse_symbol_from_pairs(pairs: &mut Pairs<Rule>) -> char {
-    parse_symbol(&parse_string(pairs))
+/// Parses a state name, removing the spaces allowed between macro instance arguments.
+fn parse_state_name(pairs: &mut Pairs<Rule>) -> String {
+    parse_string(pairs).replace(' ', "")
 }
 
 /// Extracts the inner string content from a `Pair`.
@@ -403,6 +1216,9 @@ fn check_unique_rule(
             | Rule::tapes
             | Rule::head
             | Rule::heads
+            | Rule::states
+            | Rule::symbol_sets
+            | Rule::macros
             | Rule::rules
     ) {
         return Ok(());
@@ -481,6 +1297,9 @@ fn format_rules(names: Vec<&str>) -> String {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::analyzer::Severity;
+    use crate::machine::TuringMachine;
+    use crate::types::{Halt, Step};
 
     #[test]
     fn test_parse_simple_program() {
@@ -542,6 +1361,141 @@ rules:
         assert!(program.rules.contains_key("halt"));
     }
 
+    #[test]
+    fn test_parse_states_section() {
+        let input = r#"
+name: States Section
+states:
+  start: begin
+  stop: done, fail
+tape: a
+rules:
+  other:
+    a -> a, R, done
+  begin:
+    a -> b, R, other
+    b -> b, R, fail
+"#;
+
+        let program = parse(input).unwrap();
+        assert_eq!(program.initial_state, "begin");
+        assert_eq!(program.stop_states, vec!["done", "fail"]);
+        assert!(program.is_stop_state("done"));
+        assert!(!program.is_stop_state("other"));
+    }
+
+    #[test]
+    fn test_parse_states_section_stop_only() {
+        let input = r#"
+name: Stop Only
+states:
+  stop: done
+tape: a
+rules:
+  start:
+    a -> b, R, done
+  done:
+    b -> b, R, done
+"#;
+
+        let program = parse(input).unwrap();
+        assert_eq!(program.initial_state, "start");
+        assert_eq!(program.stop_states, vec!["done"]);
+    }
+
+    #[test]
+    fn test_parse_accept_reject_states() {
+        let input = r#"
+name: Accept Reject
+states:
+  accept: yes
+  reject: no, never
+tape: a
+rules:
+  start:
+    a, R, yes
+    b, R, no
+    c, R, never
+"#;
+
+        let program = parse(input).unwrap();
+        assert_eq!(program.accept_states, vec!["yes"]);
+        assert_eq!(program.reject_states, vec!["no", "never"]);
+        assert!(program.stop_states.is_empty());
+        assert!(program.is_decider());
+        assert!(program.is_stop_state("never"));
+    }
+
+    #[test]
+    fn test_parse_conflicting_accept_reject_states() {
+        let input = r#"
+name: Conflicting
+states:
+  accept: done
+  reject: done
+tape: a
+rules:
+  start:
+    a, R, done
+"#;
+
+        let error = parse(input).unwrap_err();
+        assert!(matches!(error, TuringMachineError::ValidationError(_)));
+        assert!(error
+            .to_string()
+            .contains("State 'done' is declared as both accept and reject"));
+    }
+
+    #[test]
+    fn test_parse_states_without_transitions() {
+        let input = r#"
+name: Empty States
+tape: a
+rules:
+  start:
+    a, R, yes
+    b, R, no
+  yes:
+  no:
+"#;
+
+        let program = parse(input).unwrap();
+        assert!(program.rules["yes"].is_empty());
+        assert!(program.rules["no"].is_empty());
+    }
+
+    #[test]
+    fn test_parse_undefined_start_state() {
+        let input = r#"
+name: Undefined Start
+states:
+  start: missing
+tape: a
+rules:
+  begin:
+    a -> b, R, halt
+"#;
+
+        let error = parse(input).unwrap_err();
+        assert!(matches!(error, TuringMachineError::ValidationError(_)));
+        assert!(error.to_string().contains("Invalid start state: missing"));
+    }
+
+    #[test]
+    fn test_parse_undefined_next_state() {
+        let input = r#"
+name: Undefined Next State
+tape: a
+rules:
+  start:
+    a -> b, R, nowhere
+"#;
+
+        let error = parse(input).unwrap_err();
+        assert!(matches!(error, TuringMachineError::ValidationError(_)));
+        assert!(error.to_string().contains("start[0] -> nowhere"));
+    }
+
     #[test]
     fn test_parse_duplicate_section() {
         let input = r#"
@@ -584,6 +1538,7 @@ tape: a
 rules:
   start:
     a -> b, R, other
+  other:
 "#;
         let program = parse(input).unwrap();
         assert_eq!(program.mode, Mode::Strict);
@@ -832,6 +1787,419 @@ rules:
         assert_eq!(transition.next_state, "halt");
     }
 
+    #[test]
+    fn test_parse_wildcard() {
+        let input = r#"
+name: Wildcard Test
+tape: a, b
+rules:
+  start:
+    _ -> _, L, halt
+    * -> x, R, start
+  halt:
+"#;
+
+        let program = parse(input).unwrap();
+        let transitions = &program.rules["start"];
+        assert_eq!(transitions[1].read, vec![WILDCARD_SYMBOL]);
+        assert_eq!(transitions[1].write, vec!['x']);
+    }
+
+    #[test]
+    fn test_parse_symbol_sets() {
+        let input = r#"
+name: Symbol Sets Test
+tapes:
+  [1, 0]
+  [_]
+symbols:
+  digits = 0|1|2
+  ab = a, b
+rules:
+  start:
+    [digits, _] -> [*, x], [R, S], start
+    [_, *], [S, S], halt
+  halt:
+"#;
+
+        let program = parse(input).unwrap();
+        let transitions = &program.rules["start"];
+        assert_eq!(transitions.len(), 4);
+
+        // Each symbol in the set gets its own transition, writing back what it read
+        for (transition, digit) in transitions.iter().zip(['0', '1', '2']) {
+            assert_eq!(transition.read, vec![digit, INPUT_BLANK_SYMBOL]);
+            assert_eq!(transition.write, vec![digit, 'x']);
+        }
+
+        // An omitted write keeps the wildcard, to be written back at runtime
+        assert_eq!(
+            transitions[3].write,
+            vec![INPUT_BLANK_SYMBOL, WILDCARD_SYMBOL]
+        );
+    }
+
+    #[test]
+    fn test_parse_single_line_symbol_set() {
+        let input = r#"
+name: Symbol Set Test
+tape: a
+symbols: letters = a|b
+rules:
+  start:
+    letters, R, start
+"#;
+
+        let program = parse(input).unwrap();
+        let reads: Vec<_> = program.rules["start"].iter().map(|t| t.read[0]).collect();
+        assert_eq!(reads, vec!['a', 'b']);
+        assert_eq!(program.rules["start"][1].write, vec!['b']);
+    }
+
+    #[test]
+    fn test_parse_undefined_symbol_set() {
+        let input = r#"
+name: Undefined Set Test
+tape: a
+rules:
+  start:
+    letters -> a, R, start
+"#;
+
+        match parse(input) {
+            Err(TuringMachineError::ParseError(e)) => {
+                assert!(e.to_string().contains("Undefined symbol set: letters"));
+            }
+            result => panic!("Expected a parse error, got {result:?}"),
+        }
+
+        // Symbol sets can only be read
+        let input = r#"
+name: Written Set Test
+symbols: letters = a|b
+tape: a
+rules:
+  start:
+    a -> letters, R, start
+"#;
+
+        match parse(input) {
+            Err(TuringMachineError::ParseError(e)) => {
+                assert!(e
+                    .to_string()
+                    .contains("Symbol sets can only be read: letters"));
+                assert_eq!(e.line(), "    a -> letters, R, start");
+            }
+            result => panic!("Expected a parse error, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn test_parse_symbol_variables() {
+        let input = r#"
+name: Variables Test
+tape: a, b
+rules:
+  start:
+    _ -> _, L, halt
+    $x -> $x, R, carry_$x
+  carry_a:
+    *, S, halt
+  carry_b:
+    *, S, halt
+  halt:
+"#;
+
+        let program = parse(input).unwrap();
+        let transitions = &program.rules["start"];
+
+        // The blank is handled by the first rule, so the variable only covers `a` and `b`
+        assert_eq!(transitions.len(), 3);
+        for (transition, symbol) in transitions[1..].iter().zip(['a', 'b']) {
+            assert_eq!(transition.read, vec![symbol]);
+            assert_eq!(transition.write, vec![symbol]);
+            assert_eq!(transition.next_state, format!("carry_{symbol}"));
+        }
+        assert!(crate::analyzer::analyze(&program).is_ok());
+    }
+
+    #[test]
+    fn test_parse_multi_tape_symbol_variables() {
+        let input = r#"
+name: Multi-Tape Variables Test
+tapes:
+  [0, 1]
+  [_]
+rules:
+  start:
+    [_, _], [S, S], halt
+    [$x, _] -> [$x, $x], [R, R], start
+    [$x, $x], [R, R], start
+  halt:
+"#;
+
+        let program = parse(input).unwrap();
+        let transitions = &program.rules["start"];
+
+        let reads: Vec<_> = transitions.iter().map(|t| t.read.clone()).collect();
+        assert_eq!(
+            reads,
+            vec![
+                vec!['_', '_'],
+                vec!['0', '_'],
+                vec!['1', '_'],
+                vec!['0', '0'],
+                vec!['1', '1'],
+            ]
+        );
+        assert_eq!(transitions[2].write, vec!['1', '1']);
+    }
+
+    #[test]
+    fn test_parse_undefined_symbol_variable() {
+        for rule in ["a -> $y, R, start", "$x -> a, R, state_$y"] {
+            let input =
+                format!("name: Undefined Variable Test\ntape: a\nrules:\n  start:\n    {rule}\n");
+
+            match parse(&input) {
+                Err(TuringMachineError::ParseError(e)) => {
+                    assert!(e.to_string().contains("Undefined symbol variable: $y"));
+                }
+                result => panic!("Expected a parse error, got {result:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_macros() {
+        let input = r#"
+name: Macros Test
+tape: a, a, b
+macros:
+  seek_blank(next):
+    _, L, next
+    *, R, seek_blank(next)
+  mark(next):
+    * -> X, S, next
+rules:
+  start:
+    a, R, seek_blank(mark( halt ))
+    b, R, mark(halt)
+  halt:
+"#;
+
+        let program = parse(input).unwrap();
+        assert_eq!(program.rules.len(), 4);
+
+        // Each instance is a state named after it, with the parameters replaced
+        let seek = &program.rules["seek_blank(mark(halt))"];
+        assert_eq!(seek[0].next_state, "mark(halt)");
+        assert_eq!(seek[1].next_state, "seek_blank(mark(halt))");
+        assert_eq!(program.rules["mark(halt)"][0].next_state, "halt");
+        assert_eq!(program.rules["start"][1].next_state, "mark(halt)");
+    }
+
+    #[test]
+    fn test_parse_source_map() {
+        let input = r#"
+
+name: Source Map
+tape: a, b
+macros:
+  skip(next):
+    *, R, next
+rules:
+  start:
+    a, R, skip(start)  # into a macro
+    b, R, halt
+  halt:
+"#;
+
+        let program = parse(input).unwrap();
+
+        // Programs are equal regardless of where they are defined
+        let relocated = parse(input.trim()).unwrap();
+        assert_ne!(program.source_map, relocated.source_map);
+        assert_eq!(program, relocated);
+
+        let source_map = program.source_map.unwrap();
+        let at = |line, column| Location { line, column };
+
+        assert_eq!(source_map.states["start"], at(9, 3));
+        assert_eq!(source_map.transition("start", 1), Some(at(11, 5)));
+        assert_eq!(source_map.transitions["halt"], vec![]);
+
+        // Macro instances point at their macro
+        assert_eq!(source_map.states["skip(start)"], at(6, 3));
+        assert_eq!(source_map.transition("skip(start)", 0), Some(at(7, 5)));
+    }
+
+    #[test]
+    fn test_parse_with_options() {
+        let input = r#"
+name: Work in progress
+tape: a
+rules:
+  start:
+    a, R, halt
+  later:
+    a, R, halt
+"#;
+
+        assert!(parse(input).is_err());
+        assert!(parse_with_options(input, &AnalysisOptions::default()).is_err());
+
+        let options = AnalysisOptions {
+            unreachable_states: Severity::Warning,
+            ..AnalysisOptions::default()
+        };
+        let program = parse_with_options(input, &options).unwrap();
+        assert_eq!(program.rules.len(), 2);
+
+        // Other errors are still errors
+        let input = input.replace("a, R, halt", "a, R, missing");
+        assert!(parse_with_options(&input, &options).is_err());
+    }
+
+    #[test]
+    fn test_parse_unchecked() {
+        let input = r#"
+name: Unchecked
+tape: a
+rules:
+  start:
+    a, R, halt
+  orphan:
+    a, R, halt
+"#;
+
+        assert!(parse(input).is_err());
+
+        let program = parse_unchecked(input).unwrap();
+        assert_eq!(program.rules.len(), 2);
+        assert_eq!(program.source_map.unwrap().states["orphan"].line, 7);
+
+        // Syntax errors are still reported
+        assert!(matches!(
+            parse_unchecked("name: Broken\ntape: a\nrules:\n  start\n"),
+            Err(TuringMachineError::ParseError(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_macro_errors() {
+        let cases = [
+            (
+                "a, R, mark(halt, halt)",
+                "Macro mark expects 1 argument(s), got 2",
+            ),
+            ("a, R, unknown(halt)", "Undefined macro: unknown"),
+            ("a, R, grow(halt)", "Macro expansion exceeds"),
+        ];
+
+        for (action, message) in cases {
+            let input = format!(
+                "name: Macro Errors Test\ntape: a\nmacros:\n  mark(next):\n    * -> X, S, next\n  \
+                 grow(next):\n    *, R, grow(mark(next))\nrules:\n  start:\n    {action}\n  halt:\n"
+            );
+
+            match parse(&input) {
+                Err(TuringMachineError::ParseError(e)) => {
+                    assert!(e.to_string().contains(message));
+                    // Errors point at the call site
+                    assert_eq!(e.line(), format!("    {action}"));
+                }
+                result => panic!("Expected a parse error, got {result:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_parse_with_imports() {
+        let input = r#"
+name: Imports Test
+import "lib/flip.tur" as flip
+tape: a
+rules:
+  start:
+    a, S, flip.start
+  flip.done:
+    b, R, halt
+  halt:
+"#;
+        let library =
+            "name: Flip\ntape: a\nstates:\n  stop: done\nrules:\n  start:\n    a -> b, S, done\n  done:";
+
+        let program = parse_with_imports(input, |path| {
+            assert_eq!(path, "lib/flip.tur");
+            parse(library)
+        })
+        .unwrap();
+        assert_eq!(program.rules["flip.start"][0].next_state, "flip.done");
+        assert_eq!(program.rules["flip.done"][0].next_state, "halt");
+
+        // The importer continues from where the imported program stops
+        assert!(program.stop_states.is_empty());
+
+        // Imported states are located at the import
+        let source_map = program.source_map.unwrap();
+        let import = Location { line: 3, column: 1 };
+        assert_eq!(source_map.states["flip.start"], import);
+        assert_eq!(source_map.transition("flip.start", 0), Some(import));
+        assert_eq!(source_map.states["flip.done"].line, 8);
+
+        // Only states where the imported program halts can be defined by the importer
+        let input = input.replace("flip.done:", "flip.start:");
+        assert!(parse_with_imports(&input, |_| parse(library)).is_err());
+
+        // Imports need a loader
+        assert!(parse(input.as_str()).is_err());
+
+        // An import starts on its own line
+        let input = r#"
+name: Imports Test
+tape: a import "lib/flip.tur" as flip
+rules:
+  start:
+    a, S, flip.start
+  flip.done:
+    b, R, halt
+  halt:
+"#;
+        assert!(parse_with_imports(input, |_| parse(library)).is_err());
+    }
+
+    #[test]
+    fn test_import_halting_in_undefined_state() {
+        let library = "name: Scan\ntape: a\nrules:\n  start:\n    a, R, start\n    _, L, halt";
+        let input = r#"
+name: Scan Twice
+import "lib/scan.tur" as scan
+tape: a, a
+rules:
+  start:
+    a, S, scan.start
+"#;
+
+        let program = parse_with_imports(input, |_| parse(library)).unwrap();
+        assert!(program.rules["scan.halt"].is_empty());
+        assert_eq!(program.stop_states, vec!["scan.halt"]);
+
+        let mut machine = TuringMachine::new(program);
+        assert_eq!(machine.run(), Step::Halt(Halt::Ok));
+        assert_eq!(machine.state(), "scan.halt");
+
+        // The importer may continue from the undefined state too
+        let input = format!("{input}  scan.halt:\n    a -> b, S, halt\n");
+        let program = parse_with_imports(&input, |_| parse(library)).unwrap();
+        assert!(program.stop_states.is_empty());
+
+        let mut machine = TuringMachine::new(program);
+        assert_eq!(machine.run(), Step::Halt(Halt::Ok));
+        assert_eq!(machine.state(), "halt");
+        assert_eq!(machine.tapes()[0].trimmed(), (0, vec!['a', 'b']));
+    }
+
     #[test]
     fn test_parse_tape_with_blank_symbol() {
         let input = r#"
@@ -0,0 +1,617 @@
+//! This module compiles multi-tape Turing Machine programs into equivalent single-tape programs.
+//!
+//! The single tape holds the `k` tapes as interleaved tracks: cell `j` of tape `i` is stored at
+//! position `j * k + i`. The position of each head is kept by writing a marked copy of the
+//! symbol under it, from an extended alphabet. Every step of the original program is simulated
+//! by a sweep right to find the symbols under the heads, followed by a sweep left to write the
+//! new symbols and move the head markers.
+
+use crate::compile::{build_rules, finish_program, halts_in, StateBuilder};
+use crate::tape::Tape;
+use crate::types::{resolve, Direction, Mode, Program, TuringMachineError, WILDCARD_SYMBOL};
+use std::collections::HashSet;
+
+/// The first character used for marked symbols, at the start of Unicode's private use area.
+const FIRST_MARKED_SYMBOL: u32 = 0xE000;
+
+/// A single-tape program compiled from a multi-tape program, with the helpers to convert tapes
+/// between the two.
+#[derive(Debug, Clone)]
+pub struct SingleTapeProgram {
+    /// The compiled single-tape program.
+    pub program: Program,
+    /// The initial head positions of the original program.
+    heads: Vec<usize>,
+    blank: char,
+    /// The symbols of the original program, in sorted order.
+    symbols: Vec<char>,
+    /// The marked copy of each symbol, in the same order.
+    marked: Vec<char>,
+}
+
+impl SingleTapeProgram {
+    /// Returns the number of tapes of the original program, i.e. tracks on the single tape.
+    pub fn tracks(&self) -> usize {
+        self.heads.len()
+    }
+
+    /// Encodes the content of each tape of the original program onto a single tape, with the
+    /// heads at the original program's initial positions.
+    ///
+    /// The result can be given to `TuringMachine::set_tapes_content` for the compiled program.
+    /// Returns an error if the number of tapes is wrong or a symbol isn't used by the original
+    /// program.
+    pub fn encode_tapes(&self, tapes: &[String]) -> Result<String, TuringMachineError> {
+        if tapes.len() != self.tracks() {
+            return Err(TuringMachineError::ValidationError(format!(
+                "Expected {} tapes, got {}",
+                self.tracks(),
+                tapes.len()
+            )));
+        }
+
+        let tapes: Vec<Vec<char>> = tapes
+            .iter()
+            .map(|tape| {
+                tape.chars()
+                    .map(|symbol| resolve(symbol, self.blank))
+                    .collect()
+            })
+            .collect();
+
+        if let Some(symbol) = tapes.iter().flatten().find(|s| !self.symbols.contains(s)) {
+            return Err(TuringMachineError::ValidationError(format!(
+                "Symbol '{symbol}' isn't used by the program"
+            )));
+        }
+
+        let cells = tapes
+            .iter()
+            .zip(&self.heads)
+            .map(|(tape, &head)| tape.len().max(head + 1))
+            .max()
+            .unwrap_or(0);
+
+        let mut encoded = String::new();
+        for cell in 0..cells {
+            for (tape, &head) in tapes.iter().zip(&self.heads) {
+                let symbol = tape.get(cell).copied().unwrap_or(self.blank);
+                encoded.push(if cell == head {
+                    self.mark(symbol)
+                } else {
+                    symbol
+                });
+            }
+        }
+
+        Ok(encoded)
+    }
+
+    /// Decodes a tape of the compiled program into the tapes of the original program, with their
+    /// symbols and heads at the same positions as on the original tapes.
+    pub fn decode_tape(&self, tape: &Tape) -> Vec<Tape> {
+        let tracks = self.tracks() as isize;
+        let start = tape.start().div_euclid(tracks);
+        let end = (tape.end() - 1).div_euclid(tracks);
+
+        (0..tracks)
+            .map(|track| {
+                let mut decoded = Tape::new(Vec::new(), start, self.blank);
+                let mut head = start;
+
+                for cell in start..=end {
+                    let symbol = tape.get(cell * tracks + track);
+                    match self.unmark(symbol) {
+                        Some(symbol) => {
+                            head = cell;
+                            decoded.write(symbol);
+                        }
+                        None => decoded.write(symbol),
+                    }
+                    decoded.move_head(Direction::Right);
+                }
+
+                while decoded.head() > head {
+                    decoded.move_head(Direction::Left);
+                }
+                decoded
+            })
+            .collect()
+    }
+
+    /// Returns the marked copy of a symbol.
+    fn mark(&self, symbol: char) -> char {
+        let i = self.symbols.binary_search(&symbol).unwrap();
+        self.marked[i]
+    }
+
+    /// Returns the symbol a marked symbol is a copy of, or `None` if it isn't marked.
+    fn unmark(&self, symbol: char) -> Option<char> {
+        let i = self.marked.iter().position(|&marked| marked == symbol)?;
+        Some(self.symbols[i])
+    }
+}
+
+/// The progress of the sweep left that applies a transition of the original program.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Sweep {
+    /// The state of the original program, and the index of the transition being applied.
+    state: String,
+    transition: usize,
+    /// The tracks whose head marker hasn't been reached yet.
+    pending: Vec<bool>,
+    /// The tracks whose head marker moves to the next cell of the track to the left.
+    carry: Vec<bool>,
+}
+
+impl Sweep {
+    fn is_done(&self) -> bool {
+        !self.pending.contains(&true) && !self.carry.contains(&true)
+    }
+}
+
+/// A state of the compiled program. `track` is the track of the cell under the head.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Node {
+    /// Moving right to find the symbols under the heads, of which `known` have been found.
+    Scan {
+        state: String,
+        track: usize,
+        known: Vec<Option<char>>,
+    },
+    /// Moving left to apply a transition to each head.
+    Update { sweep: Sweep, track: usize },
+    /// Moving right `distance` cells from a head marker that moves right.
+    Forward {
+        sweep: Sweep,
+        track: usize,
+        distance: usize,
+    },
+    /// Moving back to the cell the head marker moved right from.
+    Back {
+        sweep: Sweep,
+        track: usize,
+        distance: usize,
+    },
+    /// Moving left to the first track, before scanning for the next step.
+    Rewind { state: String, track: usize },
+    /// A state the original program halts in.
+    Halt(String),
+}
+
+/// Compiles a deterministic multi-tape program into a single-tape program that computes the
+/// same result.
+///
+/// The compiled program starts on the original tapes encoded by
+/// [`SingleTapeProgram::encode_tapes`], and halts when the original program does, with the
+/// tapes decoded by [`SingleTapeProgram::decode_tape`] holding the same symbols. It halts in the
+/// same state when the original program enters a state without transitions or a stop, accept or
+/// reject state; when no transition matches, it halts in the state that scanned the symbols.
+/// Each step of the original program takes a number of steps proportional to the distance
+/// between the heads.
+///
+/// # Errors
+///
+/// Returns `TuringMachineError::ValidationError` for nondeterministic programs.
+pub fn to_single_tape(program: &Program) -> Result<SingleTapeProgram, TuringMachineError> {
+    if program.mode == Mode::Nondeterministic {
+        return Err(TuringMachineError::ValidationError(
+            "Nondeterministic programs can't be compiled to a single tape".to_string(),
+        ));
+    }
+
+    // Every symbol that can be on the tapes: blanks, the input and anything read or written
+    let symbols: HashSet<char> = program
+        .tapes
+        .iter()
+        .flat_map(|tape| tape.chars())
+        .chain(program.rules.values().flatten().flat_map(|transition| {
+            transition
+                .read
+                .iter()
+                .chain(&transition.write)
+                .map(|&symbol| resolve(symbol, program.blank))
+        }))
+        .chain([program.blank])
+        .filter(|&symbol| symbol != WILDCARD_SYMBOL)
+        .collect();
+    let mut symbols: Vec<char> = symbols.into_iter().collect();
+    symbols.sort();
+
+    let marked = (FIRST_MARKED_SYMBOL..)
+        .filter_map(char::from_u32)
+        .filter(|symbol| !symbols.contains(symbol))
+        .take(symbols.len())
+        .collect();
+
+    let mut compiled = SingleTapeProgram {
+        program: program.clone(),
+        heads: program.heads.clone(),
+        blank: program.blank,
+        symbols,
+        marked,
+    };
+    let tapes = compiled.encode_tapes(&program.tapes)?;
+
+    let compiler = Compiler {
+        program,
+        compiled: &compiled,
+        tracks: compiled.tracks(),
+    };
+    let initial = compiler.finish(&program.initial_state, 0);
+    let rules = build_rules(&compiler, &initial, program.blank);
+
+    compiled.program = finish_program(Program {
+        name: format!("{} (single tape)", program.name),
+        mode: program.mode.clone(),
+        initial_state: compiler.name(&initial),
+        stop_states: program.stop_states.clone(),
+        accept_states: program.accept_states.clone(),
+        reject_states: program.reject_states.clone(),
+        tapes: vec![tapes],
+        heads: vec![0],
+        blank: program.blank,
+        rules,
+        source_map: None,
+    })?;
+
+    Ok(compiled)
+}
+
+/// Builds the states of a compiled program.
+struct Compiler<'a> {
+    program: &'a Program,
+    compiled: &'a SingleTapeProgram,
+    tracks: usize,
+}
+
+impl StateBuilder for Compiler<'_> {
+    type Node = Node;
+
+    fn transitions(&self, node: &Node) -> Vec<(char, char, Direction, Node)> {
+        let symbols = &self.compiled.symbols;
+        let next_track = |track: usize| (track + 1) % self.tracks;
+        let previous_track = |track: usize| (track + self.tracks - 1) % self.tracks;
+
+        match node {
+            Node::Scan {
+                state,
+                track,
+                known,
+            } => {
+                let next = Node::Scan {
+                    state: state.clone(),
+                    track: next_track(*track),
+                    known: known.clone(),
+                };
+                let mut transitions: Vec<_> = symbols
+                    .iter()
+                    .map(|&symbol| (symbol, symbol, Direction::Right, next.clone()))
+                    .collect();
+
+                if known[*track].is_some() {
+                    return transitions;
+                }
+
+                for &symbol in symbols {
+                    let mut known = known.clone();
+                    known[*track] = Some(symbol);
+                    let marked = self.compiled.mark(symbol);
+
+                    if known.contains(&None) {
+                        let next = Node::Scan {
+                            state: state.clone(),
+                            track: next_track(*track),
+                            known,
+                        };
+                        transitions.push((marked, marked, Direction::Right, next));
+                        continue;
+                    }
+
+                    // Every symbol is known, so apply the matching transition, if any
+                    let read: Vec<char> = known.into_iter().flatten().collect();
+                    let Some(i) = self.program.rules[state]
+                        .iter()
+                        .position(|transition| transition.matches(&read, self.program.blank))
+                    else {
+                        continue;
+                    };
+
+                    let sweep = Sweep {
+                        state: state.clone(),
+                        transition: i,
+                        pending: vec![true; self.tracks],
+                        carry: vec![false; self.tracks],
+                    };
+                    let (write, direction, next) = self.update(sweep, *track, marked);
+                    transitions.push((marked, write, direction, next));
+                }
+
+                transitions
+            }
+            Node::Update { sweep, track } => {
+                let mut transitions = Vec::new();
+                if sweep.carry[*track] || sweep.pending[*track] {
+                    for &symbol in symbols {
+                        let read = if sweep.carry[*track] {
+                            symbol
+                        } else {
+                            self.compiled.mark(symbol)
+                        };
+                        let (write, direction, next) = self.update(sweep.clone(), *track, read);
+                        transitions.push((read, write, direction, next));
+                    }
+                }
+
+                let next = Node::Update {
+                    sweep: sweep.clone(),
+                    track: previous_track(*track),
+                };
+                transitions.push((WILDCARD_SYMBOL, WILDCARD_SYMBOL, Direction::Left, next));
+                transitions
+            }
+            Node::Forward {
+                sweep,
+                track,
+                distance,
+            } if *distance < self.tracks => {
+                let next = Node::Forward {
+                    sweep: sweep.clone(),
+                    track: *track,
+                    distance: distance + 1,
+                };
+                vec![(WILDCARD_SYMBOL, WILDCARD_SYMBOL, Direction::Right, next)]
+            }
+            Node::Forward { sweep, track, .. } => {
+                let next = Node::Back {
+                    sweep: sweep.clone(),
+                    track: *track,
+                    distance: self.tracks - 1,
+                };
+                symbols
+                    .iter()
+                    .map(|&symbol| {
+                        let marked = self.compiled.mark(symbol);
+                        (symbol, marked, Direction::Left, next.clone())
+                    })
+                    .collect()
+            }
+            Node::Back {
+                sweep,
+                track,
+                distance,
+            } if *distance > 0 => {
+                let next = Node::Back {
+                    sweep: sweep.clone(),
+                    track: *track,
+                    distance: distance - 1,
+                };
+                vec![(WILDCARD_SYMBOL, WILDCARD_SYMBOL, Direction::Left, next)]
+            }
+            Node::Back { sweep, track, .. } => {
+                let (direction, next) = self.proceed(sweep.clone(), *track);
+                vec![(WILDCARD_SYMBOL, WILDCARD_SYMBOL, direction, next)]
+            }
+            Node::Rewind { state, track } if *track > 0 => {
+                let next = Node::Rewind {
+                    state: state.clone(),
+                    track: track - 1,
+                };
+                vec![(WILDCARD_SYMBOL, WILDCARD_SYMBOL, Direction::Left, next)]
+            }
+            Node::Rewind { state, .. } => {
+                let next = Node::Scan {
+                    state: state.clone(),
+                    track: 0,
+                    known: vec![None; self.tracks],
+                };
+                vec![(WILDCARD_SYMBOL, WILDCARD_SYMBOL, Direction::Stay, next)]
+            }
+            Node::Halt(_) => Vec::new(),
+        }
+    }
+
+    /// Returns the name of a state, e.g. `start:scan:1:0,-` for the state scanning for the
+    /// symbols of state `start` on track `1`, having found the first of the symbols.
+    fn name(&self, node: &Node) -> String {
+        let flags = |flags: &[bool]| -> String {
+            flags.iter().map(|&f| if f { '1' } else { '0' }).collect()
+        };
+        let sweep_name = |sweep: &Sweep| {
+            format!(
+                "{}:{}:{}:{}",
+                sweep.state,
+                sweep.transition,
+                flags(&sweep.pending),
+                flags(&sweep.carry)
+            )
+        };
+
+        match node {
+            Node::Scan {
+                state,
+                track,
+                known,
+            } => {
+                let known: Vec<String> = known
+                    .iter()
+                    .map(|symbol| match symbol {
+                        Some(symbol) => self.index(*symbol).to_string(),
+                        None => "-".to_string(),
+                    })
+                    .collect();
+                format!("{state}:scan:{track}:{}", known.join(","))
+            }
+            Node::Update { sweep, track } => {
+                format!("{}:update:{track}", sweep_name(sweep))
+            }
+            Node::Forward {
+                sweep,
+                track,
+                distance,
+            } => format!("{}:forward:{track}:{distance}", sweep_name(sweep)),
+            Node::Back {
+                sweep,
+                track,
+                distance,
+            } => format!("{}:back:{track}:{distance}", sweep_name(sweep)),
+            Node::Rewind { state, track } => format!("{state}:rewind:{track}"),
+            Node::Halt(state) => state.clone(),
+        }
+    }
+}
+
+impl Compiler<'_> {
+    /// Applies a sweep to the cell under the head, on `track`, holding `read`. Places a head
+    /// marker moved left from the track's previous cell, or writes the symbol and moves the
+    /// marker of the head on the track.
+    fn update(&self, mut sweep: Sweep, track: usize, read: char) -> (char, Direction, Node) {
+        if sweep.carry[track] {
+            sweep.carry[track] = false;
+            let (direction, next) = self.proceed(sweep, track);
+            return (self.compiled.mark(read), direction, next);
+        }
+
+        let transition = &self.program.rules[&sweep.state][sweep.transition];
+        let symbol = self.compiled.unmark(read).unwrap();
+        let write = match resolve(transition.write[track], self.program.blank) {
+            WILDCARD_SYMBOL => symbol,
+            write => write,
+        };
+        sweep.pending[track] = false;
+
+        match transition.directions[track] {
+            Direction::Stay => {
+                let (direction, next) = self.proceed(sweep, track);
+                (self.compiled.mark(write), direction, next)
+            }
+            Direction::Left => {
+                sweep.carry[track] = true;
+                let (direction, next) = self.proceed(sweep, track);
+                (write, direction, next)
+            }
+            Direction::Right => {
+                let next = Node::Forward {
+                    sweep,
+                    track,
+                    distance: 1,
+                };
+                (write, Direction::Right, next)
+            }
+        }
+    }
+
+    /// Returns how to carry on with a sweep after handling the cell on `track`: on to the cell
+    /// to the left, or to the next step once every head has been updated.
+    fn proceed(&self, sweep: Sweep, track: usize) -> (Direction, Node) {
+        if !sweep.is_done() {
+            let track = (track + self.tracks - 1) % self.tracks;
+            return (Direction::Left, Node::Update { sweep, track });
+        }
+
+        let transition = &self.program.rules[&sweep.state][sweep.transition];
+        (Direction::Stay, self.finish(&transition.next_state, track))
+    }
+
+    /// Returns the state that starts the next step in `state`, from a cell on `track`.
+    fn finish(&self, state: &str, track: usize) -> Node {
+        if halts_in(self.program, state) {
+            Node::Halt(state.to_string())
+        } else {
+            Node::Rewind {
+                state: state.to_string(),
+                track,
+            }
+        }
+    }
+
+    /// Returns the index of a symbol in the alphabet.
+    fn index(&self, symbol: char) -> usize {
+        self.compiled.symbols.binary_search(&symbol).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encoder::{decode, encode};
+    use crate::parser::parse;
+
+    /// Runs a program and its compiled single-tape program on the same input, and checks that
+    /// they halt with the same tapes, and in the same state if it's one the program halts in.
+    fn assert_equivalent(program: &Program, input: &[&str]) -> usize {
+        let compiled = to_single_tape(program).unwrap();
+        let tapes: Vec<String> = input.iter().map(|tape| tape.to_string()).collect();
+        crate::compile::tests::assert_equivalent(
+            program,
+            input,
+            &compiled.program,
+            compiled.encode_tapes(&tapes).unwrap(),
+            |tape| compiled.decode_tape(tape),
+        )
+    }
+
+    #[test]
+    fn test_multi_tape_addition() {
+        let program = parse(include_str!("../examples/multi-tape-addition.tur")).unwrap();
+        let compiled = to_single_tape(&program).unwrap();
+
+        assert!(compiled.program.is_single_tape());
+        assert_eq!(compiled.tracks(), 3);
+
+        assert_equivalent(&program, &["11", "10", ""]);
+        assert_equivalent(&program, &["1011", "111", ""]);
+    }
+
+    #[test]
+    fn test_heads_moving_left_and_staying() {
+        let program = parse(
+            r#"
+name: Reverse Copy
+heads: [2, 0]
+tapes:
+  [a, b, c]
+  [_]
+rules:
+  start:
+    [_, _], [S, S], halt
+    [$x, _] -> [$x, $x], [L, R], start
+  halt:
+"#,
+        )
+        .unwrap();
+
+        assert_equivalent(&program, &["abc", ""]);
+        assert_equivalent(&program, &["cab", "b"]);
+    }
+
+    #[test]
+    fn test_undefined_transition_halts() {
+        let program = parse(include_str!("../examples/multi-tape-compare.tur")).unwrap();
+
+        assert_equivalent(&program, &["abc", "abc", ""]);
+        assert_equivalent(&program, &["abc", "acb", ""]);
+        assert_equivalent(&program, &["abc", "abc", "Y"]);
+    }
+
+    #[test]
+    fn test_compiled_program_can_be_encoded() {
+        let program = parse(include_str!("../examples/multi-tape-copy.tur")).unwrap();
+        let compiled = to_single_tape(&program).unwrap();
+
+        let decoded = decode(&encode(&compiled.program)).unwrap();
+        assert!(decoded.is_single_tape());
+        assert_eq!(decoded.tapes, compiled.program.tapes);
+    }
+
+    #[test]
+    fn test_encode_tapes_checks_symbols() {
+        let program = parse(include_str!("../examples/multi-tape-copy.tur")).unwrap();
+        let compiled = to_single_tape(&program).unwrap();
+
+        assert!(compiled.encode_tapes(&["abc".to_string()]).is_err());
+        assert!(compiled
+            .encode_tapes(&["xyz".to_string(), String::new()])
+            .is_err());
+    }
+}
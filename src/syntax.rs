@@ -0,0 +1,284 @@
+//! This module provides a concrete syntax tree of `.tur` source.
+//!
+//! Unlike `Program`, the tree keeps the position of every section, state, action and symbol,
+//! and the comments that `parse` discards, so tools such as the formatter can work with the
+//! source as it was written. The tree keeps the source itself, so nothing is lost: the text
+//! between nodes and comments is whitespace.
+
+use crate::parser::TuringMachineParser;
+use crate::types::TuringMachineError;
+use crate::Rule;
+use pest::iterators::Pair;
+use pest::Parser;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Range;
+
+/// A position in the source, with 1-based line and column numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Location {
+    /// The line number, starting at 1.
+    pub line: usize,
+    /// The column number in characters, starting at 1.
+    pub column: usize,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// Where the states and transitions of a parsed program are defined in its source.
+///
+/// States imported from another program are located at the `import` that brings them in, since
+/// their own source is another file.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceMap {
+    /// The location of each state defined in the source, or of the macro it's an instance of.
+    pub states: HashMap<String, Location>,
+    /// The location of the action each transition comes from, in the order of the state's
+    /// transitions.
+    pub transitions: HashMap<String, Vec<Location>>,
+}
+
+impl SourceMap {
+    /// Returns the location of the action a transition of `state` comes from.
+    pub fn transition(&self, state: &str, index: usize) -> Option<Location> {
+        self.transitions.get(state)?.get(index).copied()
+    }
+}
+
+/// A node of the syntax tree, for a rule of the grammar.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxNode {
+    /// The grammar rule the node matched.
+    pub kind: Rule,
+    /// The byte range of the node in the source.
+    pub span: Range<usize>,
+    /// The source text of the node.
+    pub text: String,
+    /// The nodes for the rules matched inside this one.
+    pub children: Vec<SyntaxNode>,
+}
+
+impl SyntaxNode {
+    /// Returns the first child of the given kind.
+    pub fn child(&self, kind: Rule) -> Option<&SyntaxNode> {
+        self.children.iter().find(|child| child.kind == kind)
+    }
+
+    /// Returns the children of the given kind.
+    pub fn children_of(&self, kind: Rule) -> impl Iterator<Item = &SyntaxNode> {
+        self.children.iter().filter(move |child| child.kind == kind)
+    }
+
+    /// Returns every node below this one, depth first in source order.
+    pub fn descendants(&self) -> Vec<&SyntaxNode> {
+        let mut nodes = Vec::new();
+        let mut stack: Vec<&SyntaxNode> = self.children.iter().rev().collect();
+
+        while let Some(node) = stack.pop() {
+            nodes.push(node);
+            stack.extend(node.children.iter().rev());
+        }
+
+        nodes
+    }
+
+    /// Returns the name of a state, macro or macro instance node, without the spaces allowed
+    /// between macro instance arguments.
+    pub fn name(&self) -> Option<String> {
+        let name = self
+            .children
+            .iter()
+            .find(|child| matches!(child.kind, Rule::state | Rule::macro_instance))?;
+
+        Some(name.text.chars().filter(|c| !c.is_whitespace()).collect())
+    }
+}
+
+/// A comment, from its `#` to the end of the line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment {
+    /// The text of the comment, including the `#`.
+    pub text: String,
+    /// The byte range of the comment in the source.
+    pub span: Range<usize>,
+}
+
+/// The concrete syntax tree of a `.tur` source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxTree {
+    source: String,
+    /// The top-level sections, in source order.
+    pub sections: Vec<SyntaxNode>,
+    /// The comments, in source order.
+    pub comments: Vec<Comment>,
+}
+
+impl SyntaxTree {
+    /// Returns the source the tree was parsed from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Returns the location of a byte offset in the source.
+    pub fn location(&self, offset: usize) -> Location {
+        let before = &self.source[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+        Location {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+
+    /// Returns the first section of the given kind, such as `Rule::rules`.
+    pub fn section(&self, kind: Rule) -> Option<&SyntaxNode> {
+        self.sections.iter().find(|section| section.kind == kind)
+    }
+
+    /// Returns the states of the `rules:` section, in source order.
+    pub fn states(&self) -> impl Iterator<Item = &SyntaxNode> {
+        self.section(Rule::rules)
+            .into_iter()
+            .flat_map(|rules| &rules.children)
+            .filter(|node| matches!(node.kind, Rule::transition | Rule::last_transition))
+    }
+
+    /// Returns the state of the `rules:` section with the given name.
+    pub fn state(&self, name: &str) -> Option<&SyntaxNode> {
+        self.states()
+            .find(|state| state.name().as_deref() == Some(name))
+    }
+}
+
+impl fmt::Display for SyntaxTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Parses the syntax tree of a `.tur` source.
+///
+/// Only the syntax is checked, so the tree of a program that fails validation, e.g. with an
+/// undefined next state, is still returned.
+///
+/// # Errors
+///
+/// Returns `TuringMachineError::ParseError` if the source doesn't match the grammar.
+pub fn parse_syntax(source: &str) -> Result<SyntaxTree, TuringMachineError> {
+    // `parse` trims the source, so do the same and shift the spans back
+    let offset = source.len() - source.trim_start().len();
+    let root = TuringMachineParser::parse(Rule::program, source.trim())
+        .map_err(|e| TuringMachineError::ParseError(e.into()))?
+        .next()
+        .unwrap();
+
+    let sections: Vec<SyntaxNode> = root
+        .into_inner()
+        .filter(|pair| pair.as_rule() != Rule::EOI)
+        .map(|pair| build_node(pair, offset))
+        .collect();
+
+    // A `#` outside symbols, strings and paths starts a comment
+    let literals: Vec<Range<usize>> = sections
+        .iter()
+        .flat_map(|section| section.descendants())
+        .filter(|node| matches!(node.kind, Rule::symbol | Rule::string | Rule::path))
+        .map(|node| node.span.clone())
+        .collect();
+
+    let mut comments = Vec::new();
+    let mut position = 0;
+    while let Some(i) = source[position..].find('#') {
+        let start = position + i;
+        if literals.iter().any(|literal| literal.contains(&start)) {
+            position = start + 1;
+            continue;
+        }
+
+        let end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let text = source[start..end].trim_end();
+        comments.push(Comment {
+            text: text.to_string(),
+            span: start..start + text.len(),
+        });
+        position = end;
+    }
+
+    Ok(SyntaxTree {
+        source: source.to_string(),
+        sections,
+        comments,
+    })
+}
+
+/// Builds the node for a pair, with its span shifted by `offset` bytes.
+fn build_node(pair: Pair<Rule>, offset: usize) -> SyntaxNode {
+    let span = pair.as_span();
+
+    SyntaxNode {
+        kind: pair.as_rule(),
+        span: span.start() + offset..span.end() + offset,
+        text: span.as_str().to_string(),
+        children: pair
+            .into_inner()
+            .map(|child| build_node(child, offset))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_syntax() {
+        let source = r#"
+# Flips every a
+name: Flip
+tape: a, '#', a
+rules:
+  start:
+    a -> b, R, start  # flip
+    '#', R, start
+    _, S, halt
+  halt:
+"#;
+        let tree = parse_syntax(source).unwrap();
+        assert_eq!(tree.to_string(), source);
+
+        let kinds: Vec<Rule> = tree.sections.iter().map(|section| section.kind).collect();
+        assert_eq!(kinds, vec![Rule::name, Rule::tape, Rule::rules]);
+
+        let names: Vec<String> = tree.states().filter_map(SyntaxNode::name).collect();
+        assert_eq!(names, vec!["start", "halt"]);
+
+        // Quoted `#` symbols aren't comments
+        let comments: Vec<&str> = tree.comments.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(comments, vec!["# Flips every a", "# flip"]);
+        assert_eq!(
+            tree.location(tree.comments[1].span.start),
+            Location {
+                line: 7,
+                column: 23
+            }
+        );
+
+        let start = tree.state("start").unwrap();
+        let actions: Vec<&SyntaxNode> = start.children_of(Rule::action).collect();
+        assert_eq!(actions.len(), 3);
+        assert_eq!(&source[actions[1].span.clone()], "'#', R, start");
+        assert_eq!(tree.location(start.span.start).line, 6);
+    }
+
+    #[test]
+    fn test_parse_syntax_error() {
+        assert!(parse_syntax("name: Broken\ntape: a\nrules:\n  start\n").is_err());
+    }
+}
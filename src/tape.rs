@@ -0,0 +1,298 @@
+//! This module defines the `Tape` type, a two-way infinite tape with a read/write head.
+
+use crate::types::Direction;
+use std::fmt;
+
+/// The parts of a tape overwritten by a single step, used to undo that step.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TapeDelta {
+    head: isize,
+    symbol: char,
+    start: isize,
+    end: isize,
+}
+
+/// A two-way infinite Turing Machine tape.
+///
+/// Cells are addressed by signed absolute coordinates: the first symbol of the initial content
+/// is at position `0`, and cells to its left have negative positions. Coordinates never shift
+/// when the tape grows, so they can be used as a stable ruler.
+///
+/// Only the visited region of the tape is stored. It is split into two vectors growing away from
+/// the origin, which gives amortized O(1) growth in both directions. Every position outside the
+/// stored region holds the blank symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tape {
+    /// Cells at positions `-1, -2, -3, ...`, in that order.
+    left: Vec<char>,
+    /// Cells at positions `0, 1, 2, ...`, in that order.
+    right: Vec<char>,
+    head: isize,
+    blank: char,
+}
+
+impl Tape {
+    /// Creates a new tape holding `content` from position `0`, with the head at `head`.
+    pub fn new(content: impl IntoIterator<Item = char>, head: isize, blank: char) -> Self {
+        let mut tape = Self {
+            left: Vec::new(),
+            right: Vec::new(),
+            head,
+            blank,
+        };
+        tape.set_content(content);
+        tape
+    }
+
+    /// Replaces the content of the tape, starting at position `0`. The head is left in place.
+    pub fn set_content(&mut self, content: impl IntoIterator<Item = char>) {
+        self.left.clear();
+        self.right = content.into_iter().collect();
+
+        // Keep the head within the stored region when it's left of the content.
+        if self.head < 0 {
+            self.allocate(self.head);
+        }
+    }
+
+    /// Returns the absolute position of the head.
+    pub fn head(&self) -> isize {
+        self.head
+    }
+
+    /// Returns the index of the head within [`Tape::to_vec`], i.e. relative to [`Tape::start`].
+    ///
+    /// The head may be past the end of the stored region, in which case the index is too.
+    pub fn head_index(&self) -> usize {
+        (self.head - self.start()) as usize
+    }
+
+    /// Returns the blank symbol of the tape.
+    pub fn blank(&self) -> char {
+        self.blank
+    }
+
+    /// Returns the position of the leftmost stored cell.
+    pub fn start(&self) -> isize {
+        -(self.left.len() as isize)
+    }
+
+    /// Returns the position just past the rightmost stored cell.
+    pub fn end(&self) -> isize {
+        self.right.len() as isize
+    }
+
+    /// Returns the number of stored cells.
+    pub fn len(&self) -> usize {
+        self.left.len() + self.right.len()
+    }
+
+    /// Checks if no cells are stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the symbol at the given position, or the blank symbol if it isn't stored.
+    pub fn get(&self, position: isize) -> char {
+        let cell = if position < 0 {
+            self.left.get((-position - 1) as usize)
+        } else {
+            self.right.get(position as usize)
+        };
+
+        cell.copied().unwrap_or(self.blank)
+    }
+
+    /// Returns the symbol under the head.
+    pub fn read(&self) -> char {
+        self.get(self.head)
+    }
+
+    /// Writes a symbol under the head.
+    pub fn write(&mut self, symbol: char) {
+        self.allocate(self.head);
+
+        if self.head < 0 {
+            self.left[(-self.head - 1) as usize] = symbol;
+        } else {
+            self.right[self.head as usize] = symbol;
+        }
+    }
+
+    /// Moves the head one cell in the given direction, growing the tape if needed.
+    pub fn move_head(&mut self, direction: Direction) {
+        match direction {
+            Direction::Left => self.head -= 1,
+            Direction::Right => self.head += 1,
+            Direction::Stay => return,
+        }
+
+        self.allocate(self.head);
+    }
+
+    /// Returns the stored symbols with their positions, from left to right.
+    pub fn cells(&self) -> impl Iterator<Item = (isize, char)> + '_ {
+        (self.start()..).zip(self.symbols())
+    }
+
+    /// Returns the stored symbols from left to right.
+    pub fn symbols(&self) -> impl Iterator<Item = char> + '_ {
+        self.left.iter().rev().chain(&self.right).copied()
+    }
+
+    /// Returns the stored symbols from left to right as a vector.
+    pub fn to_vec(&self) -> Vec<char> {
+        self.symbols().collect()
+    }
+
+    /// Returns the stored symbols with leading and trailing blanks removed, along with the
+    /// position of the first remaining symbol.
+    ///
+    /// Two tapes with the same trimmed content hold the same symbols at every position, however
+    /// far each has been visited.
+    pub fn trimmed(&self) -> (isize, Vec<char>) {
+        let symbols = self.to_vec();
+        let Some(first) = symbols.iter().position(|&c| c != self.blank) else {
+            return (0, Vec::new());
+        };
+        let last = symbols
+            .iter()
+            .rposition(|&c| c != self.blank)
+            .unwrap_or(first);
+
+        (
+            self.start() + first as isize,
+            symbols[first..=last].to_vec(),
+        )
+    }
+
+    /// Records the head, the symbol under it and the stored region, so that a following
+    /// write and head move can be undone with [`Tape::restore`].
+    pub(crate) fn delta(&self) -> TapeDelta {
+        TapeDelta {
+            head: self.head,
+            symbol: self.read(),
+            start: self.start(),
+            end: self.end(),
+        }
+    }
+
+    /// Undoes a write and head move, restoring the tape to when `delta` was recorded.
+    pub(crate) fn restore(&mut self, delta: TapeDelta) {
+        self.head = delta.head;
+        self.write(delta.symbol);
+
+        // Drop any cells allocated since the delta was recorded
+        self.left.truncate((-delta.start) as usize);
+        self.right.truncate(delta.end as usize);
+    }
+
+    /// Grows the stored region with blanks until it includes `position`.
+    fn allocate(&mut self, position: isize) {
+        if position < self.start() {
+            self.left.resize((-position) as usize, self.blank);
+        } else if position >= self.end() {
+            self.right.resize(position as usize + 1, self.blank);
+        }
+    }
+}
+
+impl fmt::Display for Tape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.symbols().try_for_each(|symbol| write!(f, "{symbol}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_and_write() {
+        let mut tape = Tape::new("ab".chars(), 0, '_');
+
+        assert_eq!(tape.read(), 'a');
+        tape.write('x');
+        assert_eq!(tape.to_vec(), vec!['x', 'b']);
+
+        // Reading past the stored region yields blanks without growing the tape.
+        assert_eq!(tape.get(5), '_');
+        assert_eq!(tape.get(-5), '_');
+        assert_eq!(tape.len(), 2);
+    }
+
+    #[test]
+    fn test_grow_left_keeps_coordinates() {
+        let mut tape = Tape::new("ab".chars(), 0, '_');
+
+        tape.move_head(Direction::Left);
+        tape.write('x');
+        tape.move_head(Direction::Left);
+
+        assert_eq!(tape.head(), -2);
+        assert_eq!(tape.start(), -2);
+        assert_eq!(tape.head_index(), 0);
+        assert_eq!(tape.to_string(), "_xab");
+        assert_eq!(tape.get(0), 'a');
+        assert_eq!(
+            tape.cells().collect::<Vec<_>>(),
+            vec![(-2, '_'), (-1, 'x'), (0, 'a'), (1, 'b')]
+        );
+    }
+
+    #[test]
+    fn test_grow_right() {
+        let mut tape = Tape::new("a".chars(), 0, '_');
+
+        tape.move_head(Direction::Right);
+        tape.move_head(Direction::Stay);
+
+        assert_eq!(tape.head(), 1);
+        assert_eq!(tape.end(), 2);
+        assert_eq!(tape.to_string(), "a_");
+    }
+
+    #[test]
+    fn test_write_past_end() {
+        let mut tape = Tape::new("a".chars(), 3, '_');
+
+        assert_eq!(tape.read(), '_');
+        tape.write('b');
+
+        assert_eq!(tape.to_string(), "a__b");
+        assert_eq!(tape.head_index(), 3);
+    }
+
+    #[test]
+    fn test_trimmed() {
+        let mut tape = Tape::new("__ab_c__".chars(), 0, '_');
+        tape.move_head(Direction::Left);
+
+        assert_eq!(tape.trimmed(), (2, vec!['a', 'b', '_', 'c']));
+        assert_eq!(Tape::new("___".chars(), 0, '_').trimmed(), (0, vec![]));
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut tape = Tape::new("ab".chars(), 0, '_');
+        let original = tape.clone();
+
+        let delta = tape.delta();
+        tape.write('x');
+        tape.move_head(Direction::Left);
+        tape.restore(delta);
+
+        assert_eq!(tape, original);
+    }
+
+    #[test]
+    fn test_set_content() {
+        let mut tape = Tape::new("ab".chars(), 0, '_');
+        tape.move_head(Direction::Left);
+        tape.set_content("xyz".chars());
+
+        assert_eq!(tape.head(), -1);
+        assert_eq!(tape.head_index(), 0);
+        assert_eq!(tape.to_string(), "_xyz");
+    }
+}
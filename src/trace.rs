@@ -0,0 +1,300 @@
+//! This module provides execution traces: a step-by-step record of a Turing Machine run that
+//! can be exported as JSON Lines or CSV for post-processing, e.g. to diff two machines' runs.
+
+use crate::machine::TuringMachine;
+use crate::types::{Direction, Step, Transition};
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+
+/// A single step of an execution trace.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TraceStep {
+    /// The step number, starting at `1` for the first step executed.
+    pub step: usize,
+    /// The state the step was taken from.
+    pub state: String,
+    /// The symbols under each head before the step.
+    pub read: Vec<char>,
+    /// The symbols written on each tape.
+    pub write: Vec<char>,
+    /// The direction each head moved.
+    pub directions: Vec<Direction>,
+    /// The state entered by the step.
+    pub next_state: String,
+    /// The absolute head positions after the step.
+    pub heads: Vec<isize>,
+    /// The tape contents after the step, if snapshots are enabled.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tapes: Option<Vec<String>>,
+}
+
+/// An execution trace, recording every step a machine takes.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trace {
+    /// The recorded steps, in execution order.
+    pub steps: Vec<TraceStep>,
+    /// Whether each step records a snapshot of the tapes.
+    pub snapshots: bool,
+}
+
+impl Trace {
+    /// Creates an empty trace. With `snapshots`, every step also records the tape contents.
+    pub fn new(snapshots: bool) -> Self {
+        Self {
+            steps: Vec::new(),
+            snapshots,
+        }
+    }
+
+    /// Executes a single step of the machine and records it.
+    pub fn step(&mut self, machine: &mut TuringMachine) -> Step {
+        self.record(machine, TuringMachine::step)
+    }
+
+    /// Runs the machine until it halts or reaches its step limit, recording every step.
+    pub fn run(&mut self, machine: &mut TuringMachine) -> Step {
+        loop {
+            match self.step(machine) {
+                Step::Continue => continue,
+                halt => return halt,
+            }
+        }
+    }
+
+    /// Calls `advance` on the machine and records the step it executes, if any.
+    ///
+    /// This allows tracing any way of stepping the machine, such as
+    /// `TuringMachine::step_with_breakpoints`.
+    pub fn record<T>(
+        &mut self,
+        machine: &mut TuringMachine,
+        advance: impl FnOnce(&mut TuringMachine) -> T,
+    ) -> T {
+        let step_count = machine.step_count();
+        let state = machine.state().to_string();
+        let read = machine.symbols();
+        let transition = machine.transition().cloned();
+
+        let result = advance(machine);
+
+        if let Some(transition) = transition.filter(|_| machine.step_count() == step_count + 1) {
+            self.push(state, read, &transition, machine);
+        }
+
+        result
+    }
+
+    /// Applies the given transitions to the machine in order, recording each as a step.
+    ///
+    /// This is used to trace a branch of a nondeterministic computation, where the transition
+    /// taken isn't necessarily the first one that matches.
+    pub(crate) fn replay(&mut self, machine: &mut TuringMachine, transitions: &[Transition]) {
+        for transition in transitions {
+            let state = machine.state().to_string();
+            let read = machine.symbols();
+
+            machine.apply(transition);
+            self.push(state, read, transition, machine);
+        }
+    }
+
+    /// Records a step that took `transition` from `state` reading `read`, leading to the
+    /// machine's current configuration.
+    fn push(
+        &mut self,
+        state: String,
+        read: Vec<char>,
+        transition: &Transition,
+        machine: &TuringMachine,
+    ) {
+        self.steps.push(TraceStep {
+            step: machine.step_count(),
+            state,
+            write: transition.written(&read, machine.blank()),
+            read,
+            directions: transition.directions.clone(),
+            next_state: transition.next_state.clone(),
+            heads: machine.heads(),
+            tapes: self.snapshots.then(|| {
+                machine
+                    .tapes()
+                    .iter()
+                    .map(|tape| tape.to_string())
+                    .collect()
+            }),
+        });
+    }
+
+    /// Writes the trace as JSON Lines, one JSON object per step.
+    pub fn write_jsonl<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        for step in &self.steps {
+            serde_json::to_writer(&mut writer, step)?;
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the trace as CSV with a header row.
+    ///
+    /// Symbols are written one character per tape, directions as `L`, `R` or `S` per tape, and
+    /// head positions and tape snapshots in one column per tape.
+    pub fn write_csv<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let tape_count = self.steps.first().map_or(0, |step| step.heads.len());
+
+        let mut header = vec![
+            "step".to_string(),
+            "state".to_string(),
+            "read".to_string(),
+            "write".to_string(),
+            "directions".to_string(),
+            "next_state".to_string(),
+        ];
+        header.extend((1..=tape_count).map(|i| format!("head_{i}")));
+        if self.snapshots {
+            header.extend((1..=tape_count).map(|i| format!("tape_{i}")));
+        }
+        write_csv_row(&mut writer, &header)?;
+
+        for step in &self.steps {
+            let mut row = vec![
+                step.step.to_string(),
+                step.state.clone(),
+                step.read.iter().collect(),
+                step.write.iter().collect(),
+                step.directions
+                    .iter()
+                    .map(|direction| match direction {
+                        Direction::Left => 'L',
+                        Direction::Right => 'R',
+                        Direction::Stay => 'S',
+                    })
+                    .collect(),
+                step.next_state.clone(),
+            ];
+            row.extend(step.heads.iter().map(isize::to_string));
+            row.extend(step.tapes.iter().flatten().cloned());
+            write_csv_row(&mut writer, &row)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes a CSV row, quoting fields that contain separators, quotes, line breaks, or leading
+/// or trailing spaces.
+fn write_csv_row<W: Write>(writer: &mut W, fields: &[String]) -> io::Result<()> {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            let needs_quotes = field.contains([',', '"', '\n', '\r'])
+                || field.starts_with(' ')
+                || field.ends_with(' ');
+
+            if needs_quotes {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect();
+
+    writeln!(writer, "{}", fields.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+    use crate::types::Halt;
+
+    fn trace_palindrome(input: &str, snapshots: bool) -> (Trace, Step) {
+        let program = parse(include_str!("../examples/palindrome.tur")).unwrap();
+        let mut machine = TuringMachine::new(program);
+        machine.set_tapes_content(&[input.to_string()]).unwrap();
+
+        let mut trace = Trace::new(snapshots);
+        let result = trace.run(&mut machine);
+
+        assert_eq!(trace.steps.len(), machine.step_count());
+        (trace, result)
+    }
+
+    #[test]
+    fn test_trace_run() {
+        let (trace, result) = trace_palindrome("aa", true);
+
+        assert_eq!(result, Step::Halt(Halt::Accept));
+
+        let first = &trace.steps[0];
+        assert_eq!(first.step, 1);
+        assert_eq!(first.state, "start");
+        assert_eq!(first.read, vec!['a']);
+        assert_eq!(first.write, vec!['X']);
+        assert_eq!(first.directions, vec![Direction::Right]);
+        assert_eq!(first.next_state, "find_end_a");
+        assert_eq!(first.heads, vec![1]);
+        assert_eq!(first.tapes, Some(vec!["Xa".to_string()]));
+
+        let last = trace.steps.last().unwrap();
+        assert_eq!(last.next_state, "accept");
+    }
+
+    #[test]
+    fn test_trace_jsonl() {
+        let (trace, _) = trace_palindrome("aa", false);
+
+        let mut output = Vec::new();
+        trace.write_jsonl(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), trace.steps.len());
+        assert_eq!(
+            lines[0],
+            r#"{"step":1,"state":"start","read":["a"],"write":["X"],"directions":["Right"],"next_state":"find_end_a","heads":[1]}"#
+        );
+
+        // Each line deserializes back to its step
+        let parsed: Vec<TraceStep> = lines
+            .iter()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(parsed, trace.steps);
+    }
+
+    #[test]
+    fn test_trace_csv() {
+        let (trace, _) = trace_palindrome("aa", true);
+
+        let mut output = Vec::new();
+        trace.write_csv(&mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), trace.steps.len() + 1);
+        assert_eq!(
+            lines[0],
+            "step,state,read,write,directions,next_state,head_1,tape_1"
+        );
+        assert_eq!(lines[1], "1,start,a,X,R,find_end_a,1,Xa");
+        // Blank symbols and tapes with leading or trailing blanks are quoted
+        assert_eq!(lines[3], r#"3,find_end_a," "," ",L,check_last_a,1,"Xa ""#);
+    }
+
+    #[test]
+    fn test_record_with_breakpoints() {
+        let program = parse(include_str!("../examples/palindrome.tur")).unwrap();
+        let mut machine = TuringMachine::new(program);
+        machine.add_breakpoint(crate::types::Breakpoint::Steps(2));
+
+        let mut trace = Trace::new(false);
+        while trace
+            .record(&mut machine, TuringMachine::step_with_breakpoints)
+            .is_none()
+        {}
+
+        assert_eq!(trace.steps.len(), 2);
+        assert_eq!(machine.step_count(), 2);
+    }
+}
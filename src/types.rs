@@ -3,23 +3,45 @@
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
 use thiserror::Error;
 
+use crate::syntax::{Location, SourceMap};
 use crate::Rule;
 
 /// The default blank symbol used on the Turing Machine tape.
 pub const DEFAULT_BLANK_SYMBOL: char = ' ';
 /// A special input symbol used in program definitions to represent the blank symbol.
 pub const INPUT_BLANK_SYMBOL: char = '_';
+/// A special symbol used in program definitions: read, it matches any symbol; written, it
+/// writes back the symbol that was read.
+pub const WILDCARD_SYMBOL: char = '*';
+/// Resolves `INPUT_BLANK_SYMBOL` to the blank symbol.
+pub(crate) fn resolve(symbol: char, blank: char) -> char {
+    if symbol == INPUT_BLANK_SYMBOL {
+        blank
+    } else {
+        symbol
+    }
+}
+
 /// The maximum allowed size for a Turing Machine program in bytes.
 pub const MAX_PROGRAM_SIZE: usize = 65536; // 64KB
-/// The maximum number of steps to execute before halting.
+/// The default maximum number of steps to execute before halting.
 pub const MAX_EXECUTION_STEPS: usize = 10000;
+/// The default maximum depth (steps per branch) explored in nondeterministic mode.
+pub const MAX_EXPLORATION_DEPTH: usize = 1000;
+/// The default maximum number of live branches explored at once in nondeterministic mode.
+pub const MAX_EXPLORATION_WIDTH: usize = 10000;
 
 /// Represents a Turing Machine program, supporting both single and multi-tape configurations.
 ///
 /// A program defines the initial setup of the machine and its transition rules.
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+///
+/// Programs are compared without their source maps: the same program parsed from differently
+/// laid out source is still the same program.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Program {
     /// The name of the Turing Machine program.
     pub name: String,
@@ -27,6 +49,16 @@ pub struct Program {
     pub mode: Mode,
     /// The initial state of the Turing Machine.
     pub initial_state: String,
+    /// States that halt the machine as soon as they are entered, even if they have
+    /// outgoing transitions.
+    #[serde(default)]
+    pub stop_states: Vec<String>,
+    /// Stop states in which the machine accepts its input.
+    #[serde(default)]
+    pub accept_states: Vec<String>,
+    /// Stop states in which the machine rejects its input.
+    #[serde(default)]
+    pub reject_states: Vec<String>,
     /// A vector of strings, where each string represents the initial content of a tape.
     pub tapes: Vec<String>,
     /// A vector of head positions, one for each tape, indicating the initial position of the head.
@@ -36,6 +68,25 @@ pub struct Program {
     /// A hash map representing the transition rules. The key is the current state,
     /// and the value is a vector of possible `Transition`s from that state.
     pub rules: HashMap<String, Vec<Transition>>,
+    /// Where the states and transitions are defined in the source the program was parsed
+    /// from, if it was.
+    #[serde(skip)]
+    pub source_map: Option<SourceMap>,
+}
+
+impl PartialEq for Program {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.mode == other.mode
+            && self.initial_state == other.initial_state
+            && self.stop_states == other.stop_states
+            && self.accept_states == other.accept_states
+            && self.reject_states == other.reject_states
+            && self.tapes == other.tapes
+            && self.heads == other.heads
+            && self.blank == other.blank
+            && self.rules == other.rules
+    }
 }
 
 /// The execution mode for a Turing Machine program.
@@ -43,6 +94,8 @@ pub struct Program {
 /// Controls how the simulator handles undefined transitions:
 /// - `Normal` (default): undefined transitions halt the machine normally (faithful to TM theory).
 /// - `Strict`: undefined transitions trigger an error, useful for debugging or catching missing rules.
+/// - `Nondeterministic`: several transitions may match the same state and symbols; execution
+///   explores every branch and accepts if any branch accepts.
 #[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Mode {
     /// Undefined transitions halt normally.
@@ -50,6 +103,9 @@ pub enum Mode {
     Normal,
     /// Undefined transitions are treated as errors.
     Strict,
+    /// All matching transitions are followed, each in its own branch. Undefined transitions
+    /// halt the branch normally.
+    Nondeterministic,
 }
 
 impl Program {
@@ -65,6 +121,34 @@ impl Program {
         self.heads.first().cloned().unwrap_or(0)
     }
 
+    /// Returns all declared stop states, including accept and reject states.
+    pub fn declared_stop_states(&self) -> impl Iterator<Item = &String> {
+        self.stop_states
+            .iter()
+            .chain(&self.accept_states)
+            .chain(&self.reject_states)
+    }
+
+    /// Checks if the given state is declared as a stop, accept or reject state.
+    pub fn is_stop_state(&self, state: &str) -> bool {
+        self.declared_stop_states().any(|s| s == state)
+    }
+
+    /// Checks if the given state is declared as an accept state.
+    pub fn is_accept_state(&self, state: &str) -> bool {
+        self.accept_states.iter().any(|s| s == state)
+    }
+
+    /// Checks if the given state is declared as a reject state.
+    pub fn is_reject_state(&self, state: &str) -> bool {
+        self.reject_states.iter().any(|s| s == state)
+    }
+
+    /// Checks if the program decides its input, i.e. it declares accept or reject states.
+    pub fn is_decider(&self) -> bool {
+        !self.accept_states.is_empty() || !self.reject_states.is_empty()
+    }
+
     /// Checks if the program is configured for a single-tape Turing Machine.
     pub fn is_single_tape(&self) -> bool {
         self.tapes.len() == 1
@@ -94,8 +178,63 @@ pub struct Transition {
     pub next_state: String,
 }
 
+impl Transition {
+    /// Checks if the transition's `read` symbols match the given symbols under the heads.
+    ///
+    /// `INPUT_BLANK_SYMBOL` matches the `blank` symbol, and `WILDCARD_SYMBOL` matches any symbol.
+    pub fn matches(&self, symbols: &[char], blank: char) -> bool {
+        self.read.len() == symbols.len()
+            && self
+                .read
+                .iter()
+                .zip(symbols)
+                .all(|(&read, &symbol)| match read {
+                    WILDCARD_SYMBOL => true,
+                    read => symbol == resolve(read, blank),
+                })
+    }
+
+    /// Checks if this transition matches every combination of symbols that `other` matches,
+    /// given the `blank` symbol. An earlier transition that covers a later one of the same
+    /// state shadows it.
+    pub fn covers(&self, other: &Transition, blank: char) -> bool {
+        self.read.len() == other.read.len()
+            && self.read.iter().zip(&other.read).all(|(&this, &other)| {
+                this == WILDCARD_SYMBOL
+                    || (other != WILDCARD_SYMBOL && resolve(this, blank) == resolve(other, blank))
+            })
+    }
+
+    /// Checks if this transition and `other` both match some combination of symbols, given the
+    /// `blank` symbol. The first of two overlapping transitions of the same state is taken for
+    /// the symbols they both match.
+    pub fn overlaps(&self, other: &Transition, blank: char) -> bool {
+        self.read.len() == other.read.len()
+            && self.read.iter().zip(&other.read).all(|(&this, &other)| {
+                this == WILDCARD_SYMBOL
+                    || other == WILDCARD_SYMBOL
+                    || resolve(this, blank) == resolve(other, blank)
+            })
+    }
+
+    /// Returns the symbols the transition writes when it reads `symbols`.
+    ///
+    /// `INPUT_BLANK_SYMBOL` writes the `blank` symbol, and `WILDCARD_SYMBOL` writes back the
+    /// symbol that was read from the same tape.
+    pub fn written(&self, symbols: &[char], blank: char) -> Vec<char> {
+        self.write
+            .iter()
+            .zip(symbols)
+            .map(|(&write, &symbol)| match write {
+                WILDCARD_SYMBOL => symbol,
+                write => resolve(write, blank),
+            })
+            .collect()
+    }
+}
+
 /// Represents the possible directions a Turing Machine head can move.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Direction {
     /// Move the head one position to the left.
     Left,
@@ -114,18 +253,123 @@ pub enum Step {
     Halt(Halt),
 }
 
+/// Represents the reason a Turing Machine halted.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Halt {
-    /// Halted in a specific state (no outgoing transitions).
+    /// Halted in a specific state (no outgoing transitions, or a declared stop state).
     Ok,
-
+    /// Halted in a declared accept state.
+    Accept,
+    /// Halted in a declared reject state.
+    Reject(Rejection),
+    /// Stopped after reaching the maximum number of steps without halting.
+    StepLimit,
+    /// Stopped after revisiting an earlier configuration, so the machine would never halt.
+    /// Only reported when loop detection is enabled.
+    Loop {
+        /// The step at which the repeated configuration was first seen.
+        first_seen_step: usize,
+        /// The number of steps between repetitions of the configuration.
+        period: usize,
+    },
     Err(TuringMachineError),
 }
 
+/// A condition that pauses execution in `TuringMachine::run_until_break`.
+///
+/// Breakpoints are checked after each step, against the configuration the step produced.
+///
+/// Breakpoints can be parsed from strings such as `accept`, `state=accept`, `read=a,b`,
+/// `write=X`, `head=-3` (first tape), `head=1:5` (tape `1`, position `5`) or `steps=100`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// Entering the given state.
+    State(String),
+    /// Reaching a configuration where the heads read the given symbols, one per tape.
+    Read(Vec<char>),
+    /// Writing the given symbol on any tape.
+    Write(char),
+    /// The head of a tape reaching an absolute position.
+    Head {
+        /// The index of the tape.
+        tape: usize,
+        /// The absolute position on the tape.
+        position: isize,
+    },
+    /// Having executed the given number of steps.
+    Steps(usize),
+}
+
+impl fmt::Display for Breakpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Breakpoint::State(state) => write!(f, "state={state}"),
+            Breakpoint::Read(symbols) => {
+                let symbols: Vec<String> = symbols.iter().map(char::to_string).collect();
+                write!(f, "read={}", symbols.join(","))
+            }
+            Breakpoint::Write(symbol) => write!(f, "write={symbol}"),
+            Breakpoint::Head { tape, position } => write!(f, "head={tape}:{position}"),
+            Breakpoint::Steps(steps) => write!(f, "steps={steps}"),
+        }
+    }
+}
+
+impl FromStr for Breakpoint {
+    type Err = TuringMachineError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || TuringMachineError::ValidationError(format!("Invalid breakpoint: {s}"));
+        let symbol = |value: &str| {
+            let mut chars = value.trim().chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => Ok(c),
+                _ => Err(invalid()),
+            }
+        };
+
+        let Some((kind, value)) = s.split_once('=') else {
+            return if s.is_empty() {
+                Err(invalid())
+            } else {
+                Ok(Breakpoint::State(s.to_string()))
+            };
+        };
+
+        match kind {
+            "state" if !value.is_empty() => Ok(Breakpoint::State(value.to_string())),
+            "read" => Ok(Breakpoint::Read(
+                value.split(',').map(symbol).collect::<Result<_, _>>()?,
+            )),
+            "write" => Ok(Breakpoint::Write(symbol(value)?)),
+            "head" => {
+                let (tape, position) = value.split_once(':').unwrap_or(("0", value));
+                Ok(Breakpoint::Head {
+                    tape: tape.parse().map_err(|_| invalid())?,
+                    position: position.parse().map_err(|_| invalid())?,
+                })
+            }
+            "steps" => Ok(Breakpoint::Steps(value.parse().map_err(|_| invalid())?)),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Represents why `TuringMachine::run_until_break` stopped.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pause {
+    /// A breakpoint was hit. The machine can be resumed.
+    Breakpoint(Breakpoint),
+    /// The machine halted.
+    Halt(Halt),
+}
+
 /// Details of a rejection outcome.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Rejection {
+    /// The reject state the machine halted in.
     pub state: String,
+    /// The symbols under each head when the machine halted.
     pub symbols: Vec<char>,
 }
 
@@ -135,9 +379,13 @@ pub enum TuringMachineError {
     /// Indicates an attempt to transition to an invalid or undefined state.
     #[error("Invalid state: {0}")]
     InvalidState(String),
-    /// Indicates that there's no rule defined for a particular set of symbols.
-    #[error("No rule defined for state {0} and symbols {1:?}")]
-    UndefinedTransition(String, Vec<char>),
+    /// Indicates that there's no rule defined for a particular set of symbols, with the
+    /// location of the state in the source if it's known.
+    #[error(
+        "No rule defined for state {0} and symbols {1:?}{}",
+        .2.map(|location| format!(" (state defined at {location})")).unwrap_or_default()
+    )]
+    UndefinedTransition(String, Vec<char>, Option<Location>),
     /// Indicates that a tape head attempted to move beyond the defined tape boundaries.
     #[error("Tape boundary exceeded")]
     TapeBoundary,
@@ -174,6 +422,24 @@ mod tests {
         assert_eq!(right, right_deserialized);
     }
 
+    #[test]
+    fn test_program_deserialization_without_halt_states() {
+        let json = r#"{
+            "name": "Simple",
+            "mode": "Normal",
+            "initial_state": "start",
+            "tapes": ["a"],
+            "heads": [0],
+            "blank": "_",
+            "rules": {"start": [], "halt": []}
+        }"#;
+
+        let program: Program = serde_json::from_str(json).unwrap();
+        assert!(program.stop_states.is_empty());
+        assert!(program.accept_states.is_empty());
+        assert!(program.reject_states.is_empty());
+    }
+
     #[test]
     fn test_transition_creation() {
         let transition = Transition {
@@ -188,6 +454,46 @@ mod tests {
         assert_eq!(transition.next_state, "q1");
     }
 
+    #[test]
+    fn test_breakpoint_from_str() {
+        let cases = [
+            ("accept", Breakpoint::State("accept".to_string())),
+            ("state=q1", Breakpoint::State("q1".to_string())),
+            ("read=a,_", Breakpoint::Read(vec!['a', '_'])),
+            ("write=X", Breakpoint::Write('X')),
+            (
+                "head=-3",
+                Breakpoint::Head {
+                    tape: 0,
+                    position: -3,
+                },
+            ),
+            (
+                "head=1:5",
+                Breakpoint::Head {
+                    tape: 1,
+                    position: 5,
+                },
+            ),
+            ("steps=100", Breakpoint::Steps(100)),
+        ];
+
+        for (input, expected) in cases {
+            let breakpoint: Breakpoint = input.parse().unwrap();
+            assert_eq!(breakpoint, expected);
+
+            // Displayed breakpoints parse back to themselves
+            assert_eq!(
+                breakpoint.to_string().parse::<Breakpoint>().unwrap(),
+                expected
+            );
+        }
+
+        for input in ["", "state=", "write=ab", "head=x", "steps=-1", "colour=red"] {
+            assert!(input.parse::<Breakpoint>().is_err(), "{input}");
+        }
+    }
+
     #[test]
     fn test_error_display() {
         let error = TuringMachineError::InvalidState("q0".to_string());